@@ -97,28 +97,61 @@ function generateRouterTypes(fns: ReturnType<typeof parseRouterFns>, sourcePrefi
     ])),
     `};`,
     ``,
+    `/** Non-terminal progress report matching Rust router::Progress */`,
+    `export interface Progress {`,
+    Code.indented([
+      `/** Estimated completion between 0 and 1 */`,
+      `fraction: number;`,
+      `stage: string;`,
+      `completed?: number | null;`,
+      `total?: number | null;`,
+    ]),
+    `}`,
+    ``,
+    `/** A call the server refused under its rate limits, matching Rust router::RateLimited */`,
+    `export interface RateLimited {`,
+    Code.indented([
+      `/** Which limit applied: "in-flight", "connection" or "user" */`,
+      `scope: string;`,
+      `/** How long until the call would be accepted, when the server knows */`,
+      `retry_after_ms?: number | null;`,
+      `/** Human-readable reason */`,
+      `message: string;`,
+    ]),
+    `}`,
+    ``,
     `/** Observable interface for streaming responses */`,
     `export interface Observable<T> {`,
     Code.indented([
       `subscribe(observer: {`,
       Code.indented([
         `next?: (value: T) => void;`,
-        `error?: (error: string) => void;`,
+        `progress?: (progress: Progress) => void;`,
+        `/** \`rateLimited\` is set when the server refused the call under its rate limits */`,
+        `error?: (error: string, rateLimited?: RateLimited) => void;`,
         `complete?: (notes: string) => void;`,
       ]),
       `}): void;`,
     ]),
     `}`,
     ``,
+    `/** Options accepted by the EnhancedObservable helpers */`,
+    `export interface EnhancedObservableOptions {`,
+    Code.indented([
+      `signal?: AbortSignal;`,
+      `onProgress?: (progress: Progress) => void;`,
+    ]),
+    `}`,
+    ``,
     `/** Enhanced observable with convenience methods */`,
     `export interface EnhancedObservable<T> extends Observable<T> {`,
     Code.indented([
-      `first(options?: { signal?: AbortSignal }): Promise<T>;`,
-      `all(options?: { signal?: AbortSignal }): Promise<T[]>;`,
+      `first(options?: EnhancedObservableOptions): Promise<T>;`,
+      `all(options?: EnhancedObservableOptions): Promise<T[]>;`,
       `each(`,
       Code.indented([
         `fn: (value: T) => void,`,
-        `options?: { signal?: AbortSignal },`,
+        `options?: EnhancedObservableOptions,`,
       ]),
       `): AbortController;`,
     ]),
@@ -170,7 +203,9 @@ function generateTypes(input: gen.Input, sourcePrefix = "") {
             const fieldsCode = typeCode.indented();
             for (const field of fields) {
               fieldsCode.lines.push(...Code.docString(field, undefined, [sourcePrefix, field.id_location]));
-              fieldsCode.add`${field.id}: ${createFormat(field.format).src};`;
+              // Option fields may be omitted entirely; serde reads a missing field as None
+              const optional = typeof field.format === "object" && "Option" in field.format ? "?" : "";
+              fieldsCode.add`${field.id}${optional}: ${createFormat(field.format).src};`;
             }
             typeCode.add`}`;
           }
@@ -217,7 +252,8 @@ function generateTypes(input: gen.Input, sourcePrefix = "") {
                   const structFields = variantsCode.indented();
                   for (const field of fields) {
                     structFields.lines.push(...Code.docString(field, undefined, [sourcePrefix, field.id_location]));
-                    structFields.add`${field.id}: ${createFormat(field.format).src};`;
+                    const optional = typeof field.format === "object" && "Option" in field.format ? "?" : "";
+                    structFields.add`${field.id}${optional}: ${createFormat(field.format).src};`;
                   }
                   variantsCode.add`}${i < variants.length - 1 ? "" : ";"}`;
                 }
@@ -0,0 +1,109 @@
+//! Single-ended against bidirectional Dijkstra on generated graphs
+//!
+//! Run with `cargo bench -p pathfinder-core --bench search`. Expanded node
+//! counts are printed before the timings, since criterion only measures time
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use pathfinder_core::bidirectional::{
+    bidirectional_search, bidirectional_shortest_path_in_graph, unidirectional_search,
+};
+use pathfinder_core::generate::generate_graph;
+use pathfinder_core::{build_graph, shortest_path_in_graph};
+use shared_types::{GraphMetricsParams, GraphModel};
+
+struct Case {
+    name: &'static str,
+    graph: GraphMetricsParams,
+    start: usize,
+    end: usize,
+}
+
+fn cases() -> Vec<Case> {
+    let grid = |side: usize| GraphModel::Grid {
+        rows: side,
+        cols: side,
+        diagonals: Some(true),
+    };
+    let knn = GraphModel::NearestNeighbours {
+        count: 20_000,
+        k: 4,
+    };
+    let geometric = GraphModel::RandomGeometric {
+        count: 20_000,
+        radius: 0.015,
+    };
+
+    vec![
+        Case {
+            name: "grid_100x100",
+            graph: generate_graph(&grid(100), 0, 1.0, 1.0).unwrap(),
+            start: 0,
+            end: 100 * 100 - 1,
+        },
+        Case {
+            name: "grid_300x300",
+            graph: generate_graph(&grid(300), 0, 1.0, 1.0).unwrap(),
+            start: 300 * 150 + 10,
+            end: 300 * 150 + 290,
+        },
+        Case {
+            name: "knn_20000",
+            graph: generate_graph(&knn, 1, 1.0, 1.0).unwrap(),
+            start: 0,
+            end: 19_999,
+        },
+        Case {
+            name: "geometric_20000",
+            graph: generate_graph(&geometric, 1, 1.0, 1.0).unwrap(),
+            start: 0,
+            end: 19_999,
+        },
+    ]
+}
+
+fn bench_search(c: &mut Criterion) {
+    let cases = cases();
+
+    println!(
+        "{:<18} {:>12} {:>14} {:>8}",
+        "graph", "dijkstra", "bidirectional", "ratio"
+    );
+    for case in &cases {
+        let (graph, _) = build_graph(&case.graph.points, &case.graph.edges);
+        let (_, single) = unidirectional_search(&graph, case.start, case.end);
+        let (_, double) = bidirectional_search(&graph, case.start, case.end);
+        println!(
+            "{:<18} {:>12} {:>14} {:>8.2}",
+            case.name,
+            single.expanded,
+            double.expanded,
+            single.expanded as f64 / double.expanded.max(1) as f64
+        );
+    }
+
+    let mut group = c.benchmark_group("shortest_path");
+    for case in &cases {
+        let (graph, nodes) = build_graph(&case.graph.points, &case.graph.edges);
+        group.bench_with_input(BenchmarkId::new("dijkstra", case.name), case, |b, case| {
+            b.iter(|| shortest_path_in_graph(&graph, &nodes, black_box(case.start), case.end))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("bidirectional", case.name),
+            case,
+            |b, case| {
+                b.iter(|| {
+                    bidirectional_shortest_path_in_graph(
+                        &graph,
+                        &nodes,
+                        black_box(case.start),
+                        case.end,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
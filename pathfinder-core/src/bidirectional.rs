@@ -0,0 +1,353 @@
+use petgraph::graph::{EdgeIndex, Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use shared_types::PathResult;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+type UndirectedGraph = Graph<(), f64, petgraph::Undirected>;
+
+/// Work done by one search, for comparing strategies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    /// Nodes settled (popped with their final distance), summed over both directions
+    pub expanded: usize,
+}
+
+/// Heap entry ordered so `BinaryHeap` pops the smallest distance first
+#[derive(Clone, Copy, PartialEq)]
+struct Entry {
+    distance: f64,
+    node: usize,
+}
+
+impl Eq for Entry {}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// One direction of the search: tentative distances and the edge each node was reached by
+struct Frontier {
+    distance: Vec<f64>,
+    via: Vec<Option<EdgeIndex>>,
+    settled: Vec<bool>,
+    heap: BinaryHeap<Entry>,
+}
+
+impl Frontier {
+    fn new(node_count: usize, source: usize) -> Self {
+        let mut distance = vec![f64::INFINITY; node_count];
+        distance[source] = 0.0;
+        let mut heap = BinaryHeap::new();
+        heap.push(Entry {
+            distance: 0.0,
+            node: source,
+        });
+        Self {
+            distance,
+            via: vec![None; node_count],
+            settled: vec![false; node_count],
+            heap,
+        }
+    }
+
+    /// Smallest distance still queued, skipping entries made stale by later improvements
+    fn peek(&mut self) -> f64 {
+        while let Some(entry) = self.heap.peek() {
+            if self.settled[entry.node] || entry.distance > self.distance[entry.node] {
+                self.heap.pop();
+            } else {
+                return entry.distance;
+            }
+        }
+        f64::INFINITY
+    }
+
+    /// Settles the closest queued node and relaxes its edges. Returns the node and
+    /// every neighbour whose distance improved, so the caller can test for a meeting
+    fn expand(&mut self, graph: &UndirectedGraph) -> Option<(usize, Vec<usize>)> {
+        self.peek();
+        let Entry { distance, node } = self.heap.pop()?;
+        self.settled[node] = true;
+
+        let mut improved = Vec::new();
+        for edge in graph.edges(NodeIndex::new(node)) {
+            let next = if edge.source().index() == node {
+                edge.target().index()
+            } else {
+                edge.source().index()
+            };
+            let candidate = distance + *edge.weight();
+            if candidate < self.distance[next] {
+                self.distance[next] = candidate;
+                self.via[next] = Some(edge.id());
+                self.heap.push(Entry {
+                    distance: candidate,
+                    node: next,
+                });
+                improved.push(next);
+            }
+        }
+        Some((node, improved))
+    }
+
+    /// Edges from `node` back to this frontier's source, nearest first
+    fn trail(&self, graph: &UndirectedGraph, mut node: usize) -> Vec<EdgeIndex> {
+        let mut edges = Vec::new();
+        while let Some(edge) = self.via[node] {
+            edges.push(edge);
+            let (a, b) = graph.edge_endpoints(edge).expect("edge from this graph");
+            node = if a.index() == node {
+                b.index()
+            } else {
+                a.index()
+            };
+        }
+        edges
+    }
+}
+
+/// Pure function to run bidirectional Dijkstra on a graph from `build_graph`
+/// Searches forward from `start` and backward from `end`, always expanding the
+/// side with the smaller queued distance, and stops once the two queue minima
+/// together reach the best meeting seen. Returns the path as node indices
+pub fn bidirectional_search(
+    graph: &UndirectedGraph,
+    start: usize,
+    end: usize,
+) -> (Option<Vec<usize>>, SearchStats) {
+    let mut stats = SearchStats::default();
+    if start == end {
+        return (Some(vec![start]), stats);
+    }
+
+    let node_count = graph.node_count();
+    let mut forward = Frontier::new(node_count, start);
+    let mut backward = Frontier::new(node_count, end);
+    let mut best = f64::INFINITY;
+    let mut meeting = None;
+
+    loop {
+        let (next_forward, next_backward) = (forward.peek(), backward.peek());
+        if next_forward + next_backward >= best || next_forward.min(next_backward).is_infinite() {
+            break;
+        }
+
+        let (this, other) = if next_forward <= next_backward {
+            (&mut forward, &backward)
+        } else {
+            (&mut backward, &forward)
+        };
+        let Some((node, improved)) = this.expand(graph) else {
+            break;
+        };
+        stats.expanded += 1;
+
+        for candidate in std::iter::once(node).chain(improved) {
+            let total = this.distance[candidate] + other.distance[candidate];
+            if total < best {
+                best = total;
+                meeting = Some(candidate);
+            }
+        }
+    }
+
+    let Some(meeting) = meeting else {
+        return (None, stats);
+    };
+    let mut path = vec![meeting];
+    let mut node = meeting;
+    for edge in forward.trail(graph, meeting) {
+        let (a, b) = graph.edge_endpoints(edge).expect("edge from this graph");
+        node = if a.index() == node {
+            b.index()
+        } else {
+            a.index()
+        };
+        path.push(node);
+    }
+    path.reverse();
+    node = meeting;
+    for edge in backward.trail(graph, meeting) {
+        let (a, b) = graph.edge_endpoints(edge).expect("edge from this graph");
+        node = if a.index() == node {
+            b.index()
+        } else {
+            a.index()
+        };
+        path.push(node);
+    }
+    (Some(path), stats)
+}
+
+/// Pure function to run plain Dijkstra from `start` until `end` is settled
+/// Counts expanded nodes the same way as `bidirectional_search`, for benchmarks
+pub fn unidirectional_search(
+    graph: &UndirectedGraph,
+    start: usize,
+    end: usize,
+) -> (Option<f64>, SearchStats) {
+    let mut stats = SearchStats::default();
+    let mut frontier = Frontier::new(graph.node_count(), start);
+    while let Some((node, _)) = frontier.expand(graph) {
+        stats.expanded += 1;
+        if node == end {
+            return (Some(frontier.distance[end]), stats);
+        }
+    }
+    (None, stats)
+}
+
+/// Pure function to sum edge weights along `path` from its first node
+/// Adding in path order reproduces the single-ended search's distance bit for bit
+fn path_length(graph: &UndirectedGraph, path: &[usize]) -> f64 {
+    path.windows(2)
+        .map(|pair| {
+            graph
+                .edges_connecting(NodeIndex::new(pair[0]), NodeIndex::new(pair[1]))
+                .map(|edge| *edge.weight())
+                .fold(f64::INFINITY, f64::min)
+        })
+        .fold(0.0, |total, weight| total + weight)
+}
+
+/// Pure function to find the shortest path with bidirectional Dijkstra
+/// Same distance and errors as `shortest_path_in_graph`; where several paths are
+/// equally short (as on a grid) it may return a different one of them
+pub fn bidirectional_shortest_path_in_graph(
+    graph: &UndirectedGraph,
+    nodes: &[NodeIndex],
+    start_idx: usize,
+    end_idx: usize,
+) -> Result<PathResult, String> {
+    let (start, end) = (nodes[start_idx].index(), nodes[end_idx].index());
+    match bidirectional_search(graph, start, end) {
+        (Some(path), _) => Ok(PathResult {
+            distance: path_length(graph, &path),
+            path,
+            turn_cost: None,
+            geometry: None,
+        }),
+        (None, _) => Err("No path found".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::generate_graph;
+    use crate::{build_graph, compute_shortest_path};
+    use shared_types::{Edge, GraphModel, Point};
+
+    #[test]
+    fn test_bidirectional_matches_dijkstra_on_random_graphs() {
+        for seed in 0..20 {
+            let model = GraphModel::NearestNeighbours { count: 120, k: 3 };
+            let graph = generate_graph(&model, seed, 100.0, 100.0).unwrap();
+            let (built, nodes) = build_graph(&graph.points, &graph.edges);
+
+            for (start, end) in [(0, 119), (5, 60), (42, 42), (17, 3)] {
+                let expected = compute_shortest_path(&graph.points, &graph.edges, start, end);
+                let found = bidirectional_shortest_path_in_graph(&built, &nodes, start, end);
+                match (expected, found) {
+                    (Ok(expected), Ok(found)) => {
+                        assert_eq!(expected.path, found.path, "seed {}", seed);
+                        assert_eq!(expected.distance, found.distance, "seed {}", seed);
+                    }
+                    (Err(expected), Err(found)) => assert_eq!(expected, found),
+                    (expected, found) => panic!("seed {}: {:?} vs {:?}", seed, expected, found),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_matches_dijkstra_distance_on_grid_ties() {
+        let model = GraphModel::Grid {
+            rows: 10,
+            cols: 10,
+            diagonals: None,
+        };
+        let graph = generate_graph(&model, 0, 1.0, 1.0).unwrap();
+        let (built, nodes) = build_graph(&graph.points, &graph.edges);
+
+        for start in 0..100 {
+            for end in 0..100 {
+                let expected = compute_shortest_path(&graph.points, &graph.edges, start, end);
+                let found =
+                    bidirectional_shortest_path_in_graph(&built, &nodes, start, end).unwrap();
+                let expected = expected.unwrap();
+                assert!(
+                    (expected.distance - found.distance).abs() < 1e-9,
+                    "{} -> {}",
+                    start,
+                    end
+                );
+                assert_eq!(found.path.len(), expected.path.len());
+                assert_eq!(
+                    (found.path[0], found.path[found.path.len() - 1]),
+                    (start, end)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_expands_fewer_nodes_on_a_grid() {
+        let model = GraphModel::Grid {
+            rows: 40,
+            cols: 40,
+            diagonals: None,
+        };
+        let graph = generate_graph(&model, 0, 1.0, 1.0).unwrap();
+        let (built, _) = build_graph(&graph.points, &graph.edges);
+
+        // Across the middle row: one ball of radius d against two of radius d / 2
+        let (start, end) = (20 * 40 + 5, 20 * 40 + 35);
+        let (distance, single) = unidirectional_search(&built, start, end);
+        let (path, double) = bidirectional_search(&built, start, end);
+
+        assert!(distance.is_some() && path.is_some());
+        assert!(
+            double.expanded < single.expanded,
+            "{} vs {}",
+            double.expanded,
+            single.expanded
+        );
+    }
+
+    #[test]
+    fn test_bidirectional_uses_cheapest_parallel_edge() {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+        ];
+        let edges = vec![Edge { from: 0, to: 1 }, Edge { from: 1, to: 0 }];
+        let (mut built, nodes) = build_graph(&points, &edges);
+        let first = built.edge_indices().next().unwrap();
+        built[first] = 5.0;
+
+        let result = bidirectional_shortest_path_in_graph(&built, &nodes, 0, 1).unwrap();
+        assert_eq!(result.path, vec![0, 1]);
+        assert_eq!(result.distance, 1.0);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::rng::SplitMix64;
+use crate::{build_graph, check_edges};
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use shared_types::router::Progress;
+use shared_types::{CentralityResult, Edge, Point};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Tolerance for treating two path lengths as equally short
+const TIE_EPSILON: f64 = 1e-10;
+
+/// Upper bound on progress frames sent while searching sources
+const PROGRESS_STEPS: usize = 100;
+
+/// Min-heap entry for Dijkstra ordered by distance
+#[derive(Copy, Clone, PartialEq)]
+struct State {
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Single-source shortest paths with path counts, as needed by Brandes
+struct ShortestPathDag {
+    /// Nodes in order of non-decreasing distance from the source
+    order: Vec<usize>,
+    distance: Vec<f64>,
+    /// Number of distinct shortest paths from the source
+    sigma: Vec<f64>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+fn shortest_path_dag(
+    graph: &Graph<(), f64, petgraph::Undirected>,
+    source: usize,
+) -> ShortestPathDag {
+    let n = graph.node_count();
+    let mut distance = vec![f64::INFINITY; n];
+    let mut sigma = vec![0.0; n];
+    let mut predecessors = vec![Vec::new(); n];
+    let mut visited = vec![false; n];
+    let mut order = Vec::new();
+    let mut heap = BinaryHeap::new();
+
+    distance[source] = 0.0;
+    sigma[source] = 1.0;
+    heap.push(State {
+        cost: 0.0,
+        node: source,
+    });
+
+    while let Some(State { cost, node }) = heap.pop() {
+        if visited[node] {
+            continue;
+        }
+        visited[node] = true;
+        order.push(node);
+
+        for edge in graph.edges(NodeIndex::new(node)) {
+            let neighbour = if edge.source().index() == node {
+                edge.target().index()
+            } else {
+                edge.source().index()
+            };
+            if visited[neighbour] {
+                continue;
+            }
+
+            let candidate = cost + *edge.weight();
+            if candidate < distance[neighbour] - TIE_EPSILON {
+                distance[neighbour] = candidate;
+                sigma[neighbour] = sigma[node];
+                predecessors[neighbour] = vec![node];
+                heap.push(State {
+                    cost: candidate,
+                    node: neighbour,
+                });
+            } else if (candidate - distance[neighbour]).abs() <= TIE_EPSILON {
+                sigma[neighbour] += sigma[node];
+                predecessors[neighbour].push(node);
+            }
+        }
+    }
+
+    ShortestPathDag {
+        order,
+        distance,
+        sigma,
+        predecessors,
+    }
+}
+
+/// Pure function to compute degree centrality: distinct neighbours over `n - 1`
+pub fn degree_centrality(node_count: usize, edges: &[Edge]) -> Vec<f64> {
+    let mut neighbours: Vec<HashSet<usize>> = vec![HashSet::new(); node_count];
+    for edge in edges.iter().filter(|edge| edge.from != edge.to) {
+        neighbours[edge.from].insert(edge.to);
+        neighbours[edge.to].insert(edge.from);
+    }
+
+    let scale = if node_count > 1 {
+        1.0 / (node_count - 1) as f64
+    } else {
+        0.0
+    };
+    neighbours.iter().map(|n| n.len() as f64 * scale).collect()
+}
+
+/// Core business logic: betweenness, closeness and degree centrality
+/// Edge lengths are Euclidean. With `sample_size` set, betweenness and closeness
+/// are estimated from that many randomly chosen sources and scaled up to the
+/// full node count; `on_progress` is called as sources are searched
+pub fn compute_centrality(
+    points: &[Point],
+    edges: &[Edge],
+    sample_size: Option<usize>,
+    seed: u64,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<CentralityResult, String> {
+    if sample_size == Some(0) {
+        return Err("Sample size must be at least 1".to_string());
+    }
+    check_edges(edges, points.len())?;
+
+    let n = points.len();
+    let (graph, _) = build_graph(points, edges);
+
+    let sources: Vec<usize> = match sample_size {
+        Some(k) if k < n => SplitMix64::new(seed).sample_indices(n, k),
+        _ => (0..n).collect(),
+    };
+    let scale = if sources.is_empty() {
+        0.0
+    } else {
+        n as f64 / sources.len() as f64
+    };
+
+    let mut betweenness = vec![0.0; n];
+    let mut distance_sum = vec![0.0; n];
+    let mut reached = vec![0.0; n];
+    let report_every = sources.len().div_ceil(PROGRESS_STEPS).max(1);
+
+    for (i, &source) in sources.iter().enumerate() {
+        let dag = shortest_path_dag(&graph, source);
+
+        let mut dependency = vec![0.0; n];
+        for &w in dag.order.iter().rev() {
+            for &v in &dag.predecessors[w] {
+                dependency[v] += dag.sigma[v] / dag.sigma[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                betweenness[w] += dependency[w];
+            }
+            // The graph is undirected, so distance from the source is distance to it
+            distance_sum[w] += dag.distance[w];
+            reached[w] += 1.0;
+        }
+
+        if (i + 1) % report_every == 0 || i + 1 == sources.len() {
+            on_progress(Progress::counted("centrality", i + 1, sources.len()));
+        }
+    }
+
+    // Each unordered pair is seen from both endpoints
+    let betweenness = betweenness.iter().map(|b| b * scale / 2.0).collect();
+
+    let closeness = (0..n)
+        .map(|v| {
+            let reachable = reached[v] * scale - 1.0;
+            let total = distance_sum[v] * scale;
+            if total > 0.0 && n > 1 {
+                (reachable / (n - 1) as f64) * (reachable / total)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    Ok(CentralityResult {
+        betweenness,
+        closeness,
+        degree: degree_centrality(n, edges),
+        sampled_sources: sources.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path graph 0 - 1 - 2 - 3 with unit spacing
+    fn line() -> (Vec<Point>, Vec<Edge>) {
+        let points = (0..4)
+            .map(|i| Point {
+                x: i as f64,
+                y: 0.0,
+                z: None,
+            })
+            .collect();
+        let edges = (0..3).map(|i| Edge { from: i, to: i + 1 }).collect();
+        (points, edges)
+    }
+
+    #[test]
+    fn test_centrality_on_line() {
+        let (points, edges) = line();
+        let result = compute_centrality(&points, &edges, None, 0, |_| {}).unwrap();
+
+        // Inner nodes lie on the shortest paths 0-2, 0-3 (node 1) and 1-3, 0-3 (node 2)
+        assert_eq!(result.betweenness, vec![0.0, 2.0, 2.0, 0.0]);
+        assert!((result.closeness[1] - 3.0 / 4.0).abs() < 1e-10);
+        assert!((result.closeness[0] - 3.0 / 6.0).abs() < 1e-10);
+        assert!((result.degree[1] - 2.0 / 3.0).abs() < 1e-10);
+        assert_eq!(result.sampled_sources, 4);
+    }
+
+    #[test]
+    fn test_betweenness_splits_between_equal_paths() {
+        // Square 0-1-2-3-0: two equally short paths between opposite corners
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: None,
+            },
+        ];
+        let edges = (0..4)
+            .map(|i| Edge {
+                from: i,
+                to: (i + 1) % 4,
+            })
+            .collect::<Vec<_>>();
+
+        let result = compute_centrality(&points, &edges, None, 0, |_| {}).unwrap();
+        for b in result.betweenness {
+            assert!((b - 0.5).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_sampled_centrality_is_deterministic() {
+        let (points, edges) = line();
+        let mut progress = Vec::new();
+
+        let first = compute_centrality(&points, &edges, Some(2), 7, |p| progress.push(p)).unwrap();
+        let second = compute_centrality(&points, &edges, Some(2), 7, |_| {}).unwrap();
+
+        assert_eq!(first.sampled_sources, 2);
+        assert_eq!(first.betweenness, second.betweenness);
+        assert_eq!(progress.last().unwrap().completed, Some(2));
+    }
+
+    #[test]
+    fn test_rejects_edges_to_missing_points() {
+        let (points, mut edges) = line();
+        edges.push(Edge { from: 3, to: 4 });
+
+        let result = compute_centrality(&points, &edges, None, 0, |_| {});
+        assert_eq!(
+            result.unwrap_err(),
+            "Edge 3-4 refers to a point that does not exist"
+        );
+    }
+}
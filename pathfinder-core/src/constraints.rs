@@ -0,0 +1,335 @@
+use crate::elevation::grade;
+use shared_types::{Edge, Point, Region, ShortestPathParams};
+
+/// Elements a path must avoid, gathered from `ShortestPathParams`
+#[derive(Debug, Clone, Default)]
+pub struct PathConstraints {
+    pub forbidden_nodes: Vec<usize>,
+    pub forbidden_edges: Vec<usize>,
+    pub forbidden_regions: Vec<Region>,
+    /// Edges steeper than this grade are dropped, see `elevation::grade`
+    pub max_grade: Option<f64>,
+}
+
+impl PathConstraints {
+    pub fn from_params(params: &ShortestPathParams) -> Self {
+        Self {
+            forbidden_nodes: params.forbidden_nodes.clone().unwrap_or_default(),
+            forbidden_edges: params.forbidden_edges.clone().unwrap_or_default(),
+            forbidden_regions: params.forbidden_regions.clone().unwrap_or_default(),
+            max_grade: params.max_grade,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forbidden_nodes.is_empty()
+            && self.forbidden_edges.is_empty()
+            && self.forbidden_regions.is_empty()
+            && self.max_grade.is_none()
+    }
+
+    /// Short human-readable summary used in error messages
+    pub fn describe(&self) -> String {
+        let mut summary = format!(
+            "{} forbidden nodes, {} forbidden edges, {} forbidden regions",
+            self.forbidden_nodes.len(),
+            self.forbidden_edges.len(),
+            self.forbidden_regions.len()
+        );
+        if let Some(max_grade) = self.max_grade {
+            summary.push_str(&format!(", max grade {}", max_grade));
+        }
+        summary
+    }
+
+    fn validate(&self, points: &[Point], edges: &[Edge]) -> Result<(), String> {
+        crate::check_edges(edges, points.len())?;
+        if let Some(&node) = self.forbidden_nodes.iter().find(|&&n| n >= points.len()) {
+            return Err(format!("Forbidden node index {} is out of bounds", node));
+        }
+        if let Some(&edge) = self.forbidden_edges.iter().find(|&&e| e >= edges.len()) {
+            return Err(format!("Forbidden edge index {} is out of bounds", edge));
+        }
+        if self.max_grade.is_some_and(|g| g.is_nan() || g < 0.0) {
+            return Err("Max grade must be a non-negative number".to_string());
+        }
+        for (i, region) in self.forbidden_regions.iter().enumerate() {
+            if let Region::Polygon { vertices } = region {
+                if vertices.len() < 3 {
+                    return Err(format!(
+                        "Forbidden region {} is a polygon with fewer than 3 vertices",
+                        i
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Per-point flags for points that are listed or lie inside a region
+    pub fn blocked_nodes(&self, points: &[Point]) -> Vec<bool> {
+        let mut blocked: Vec<bool> = points
+            .iter()
+            .map(|p| self.forbidden_regions.iter().any(|r| region_contains(r, p)))
+            .collect();
+        for &node in &self.forbidden_nodes {
+            blocked[node] = true;
+        }
+        blocked
+    }
+
+    /// Per-edge flags for edges the path may still use, after validating the constraints
+    /// An edge is dropped if listed, if either endpoint is blocked, if it
+    /// crosses a forbidden region, or if it is steeper than the max grade
+    pub fn allowed_edge_mask(&self, points: &[Point], edges: &[Edge]) -> Result<Vec<bool>, String> {
+        self.validate(points, edges)?;
+
+        let blocked = self.blocked_nodes(points);
+        let mut allowed = vec![true; edges.len()];
+        for &edge in &self.forbidden_edges {
+            allowed[edge] = false;
+        }
+
+        for (i, edge) in edges.iter().enumerate() {
+            let (from, to) = (&points[edge.from], &points[edge.to]);
+            allowed[i] = allowed[i]
+                && !blocked[edge.from]
+                && !blocked[edge.to]
+                && self.max_grade.is_none_or(|max| grade(from, to) <= max)
+                && !self
+                    .forbidden_regions
+                    .iter()
+                    .any(|r| segment_intersects_region(r, from, to));
+        }
+
+        Ok(allowed)
+    }
+
+    /// Edges the path may still use, see `allowed_edge_mask`
+    pub fn allowed_edges(&self, points: &[Point], edges: &[Edge]) -> Result<Vec<Edge>, String> {
+        let allowed = self.allowed_edge_mask(points, edges)?;
+        Ok(edges
+            .iter()
+            .zip(allowed)
+            .filter(|(_, allowed)| *allowed)
+            .map(|(edge, _)| edge.clone())
+            .collect())
+    }
+
+    /// Fails if the start or end point is missing or itself forbidden
+    pub fn check_endpoints(
+        &self,
+        points: &[Point],
+        start_idx: usize,
+        end_idx: usize,
+    ) -> Result<(), String> {
+        if start_idx >= points.len() || end_idx >= points.len() {
+            return Err("Start or end index is out of bounds".to_string());
+        }
+        let blocked = |index: usize| {
+            self.forbidden_nodes.contains(&index)
+                || self
+                    .forbidden_regions
+                    .iter()
+                    .any(|r| region_contains(r, &points[index]))
+        };
+        if blocked(start_idx) {
+            return Err(format!(
+                "Start point {} is forbidden by the constraints",
+                start_idx
+            ));
+        }
+        if blocked(end_idx) {
+            return Err(format!(
+                "End point {} is forbidden by the constraints",
+                end_idx
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn rect_bounds(min: &Point, max: &Point) -> (f64, f64, f64, f64) {
+    (
+        min.x.min(max.x),
+        min.y.min(max.y),
+        min.x.max(max.x),
+        min.y.max(max.y),
+    )
+}
+
+/// Pure function to test whether a point lies in a region (boundary included)
+pub fn region_contains(region: &Region, p: &Point) -> bool {
+    match region {
+        Region::Rect { min, max } => {
+            let (x0, y0, x1, y1) = rect_bounds(min, max);
+            p.x >= x0 && p.x <= x1 && p.y >= y0 && p.y <= y1
+        }
+        Region::Polygon { vertices } => {
+            let n = vertices.len();
+            let on_boundary = (0..n).any(|i| on_segment(&vertices[i], &vertices[(i + 1) % n], p));
+            on_boundary || point_in_polygon(vertices, p)
+        }
+    }
+}
+
+/// Pure function to test whether segment `a`-`b` touches a region
+pub fn segment_intersects_region(region: &Region, a: &Point, b: &Point) -> bool {
+    match region {
+        Region::Rect { min, max } => {
+            let (x0, y0, x1, y1) = rect_bounds(min, max);
+            clip_segment_to_rect(a, b, x0, y0, x1, y1)
+        }
+        Region::Polygon { vertices } => {
+            let n = vertices.len();
+            region_contains(region, a)
+                || region_contains(region, b)
+                || (0..n).any(|i| segments_intersect(a, b, &vertices[i], &vertices[(i + 1) % n]))
+        }
+    }
+}
+
+/// Liang-Barsky: true if any part of segment `a`-`b` lies within the rectangle
+fn clip_segment_to_rect(a: &Point, b: &Point, x0: f64, y0: f64, x1: f64, y1: f64) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+
+    for (p, q) in [
+        (-dx, a.x - x0),
+        (dx, x1 - a.x),
+        (-dy, a.y - y0),
+        (dy, y1 - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Even-odd ray casting
+fn point_in_polygon(vertices: &[Point], p: &Point) -> bool {
+    let mut inside = false;
+    let mut j = vertices.len() - 1;
+    for i in 0..vertices.len() {
+        let (vi, vj) = (&vertices[i], &vertices[j]);
+        if (vi.y > p.y) != (vj.y > p.y) && p.x < (vj.x - vi.x) * (p.y - vi.y) / (vj.y - vi.y) + vi.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn cross(o: &Point, a: &Point, b: &Point) -> f64 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+/// True if `p` lies on segment `a`-`b`
+fn on_segment(a: &Point, b: &Point, p: &Point) -> bool {
+    cross(a, b, p).abs() < 1e-12
+        && p.x >= a.x.min(b.x)
+        && p.x <= a.x.max(b.x)
+        && p.y >= a.y.min(b.y)
+        && p.y <= a.y.max(b.y)
+}
+
+/// True if segments `a`-`b` and `c`-`d` share at least one point
+fn segments_intersect(a: &Point, b: &Point, c: &Point, d: &Point) -> bool {
+    let d1 = cross(c, d, a);
+    let d2 = cross(c, d, b);
+    let d3 = cross(a, b, c);
+    let d4 = cross(a, b, d);
+
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+
+    on_segment(c, d, a) || on_segment(c, d, b) || on_segment(a, b, c) || on_segment(a, b, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point { x, y, z: None }
+    }
+
+    #[test]
+    fn test_constraints_reject_indices_out_of_range() {
+        let constraints = PathConstraints {
+            forbidden_nodes: vec![1],
+            ..Default::default()
+        };
+        let points = vec![p(0.0, 0.0), p(1.0, 0.0)];
+        assert_eq!(
+            constraints.check_endpoints(&points, 0, 2).unwrap_err(),
+            "Start or end index is out of bounds"
+        );
+        assert_eq!(
+            constraints.check_endpoints(&points, 0, 1).unwrap_err(),
+            "End point 1 is forbidden by the constraints"
+        );
+        let edges = vec![Edge { from: 0, to: 3 }];
+        assert_eq!(
+            constraints.allowed_edges(&points, &edges).unwrap_err(),
+            "Edge 0-3 refers to a point that does not exist"
+        );
+    }
+
+    #[test]
+    fn test_segment_crossing_rect_without_endpoints_inside() {
+        let region = Region::Rect {
+            min: p(1.0, -1.0),
+            max: p(2.0, 1.0),
+        };
+        assert!(segment_intersects_region(
+            &region,
+            &p(0.0, 0.0),
+            &p(3.0, 0.0)
+        ));
+        assert!(!segment_intersects_region(
+            &region,
+            &p(0.0, 2.0),
+            &p(3.0, 2.0)
+        ));
+    }
+
+    #[test]
+    fn test_polygon_contains_and_crosses() {
+        let triangle = Region::Polygon {
+            vertices: vec![p(0.0, 0.0), p(4.0, 0.0), p(0.0, 4.0)],
+        };
+        assert!(region_contains(&triangle, &p(1.0, 1.0)));
+        assert!(!region_contains(&triangle, &p(3.0, 3.0)));
+        assert!(segment_intersects_region(
+            &triangle,
+            &p(-1.0, 1.0),
+            &p(5.0, 1.0)
+        ));
+        assert!(!segment_intersects_region(
+            &triangle,
+            &p(3.0, 3.0),
+            &p(5.0, 5.0)
+        ));
+    }
+}
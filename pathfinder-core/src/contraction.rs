@@ -0,0 +1,529 @@
+use crate::{euclidean_distance, graph_fingerprint};
+use serde::{Deserialize, Serialize};
+use shared_types::{Edge, PathResult, Point};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Nodes a witness search may settle before giving up and keeping the shortcut.
+/// Extra shortcuts cost query time but never correctness
+const WITNESS_SETTLE_LIMIT: usize = 64;
+
+/// Arc from a node to a neighbour contracted after it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct UpArc {
+    to: usize,
+    weight: f64,
+    /// The contracted node this shortcut bypasses; `None` for an original edge
+    middle: Option<usize>,
+}
+
+/// Heap entry ordered so `BinaryHeap` pops the smallest key first
+#[derive(Clone, Copy, PartialEq)]
+struct Entry {
+    key: f64,
+    node: usize,
+}
+
+impl Eq for Entry {}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .key
+            .total_cmp(&self.key)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Multiplicative hash for node indices; query maps hold a few hundred entries,
+/// where the default SipHash dominates the search itself
+#[derive(Default)]
+struct NodeHasher(u64);
+
+impl Hasher for NodeHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ byte as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.0 = (value as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+type NodeMap<V> = HashMap<usize, V, BuildHasherDefault<NodeHasher>>;
+
+/// One direction of a hierarchy query: distance and predecessor of every node reached
+struct UpwardSearch {
+    reached: NodeMap<(f64, Option<usize>)>,
+    heap: BinaryHeap<Entry>,
+}
+
+impl UpwardSearch {
+    fn new(source: usize) -> Self {
+        Self {
+            reached: NodeMap::from_iter([(source, (0.0, None))]),
+            heap: BinaryHeap::from([Entry {
+                key: 0.0,
+                node: source,
+            }]),
+        }
+    }
+}
+
+/// Contraction hierarchy of an undirected, Euclidean-weighted graph
+/// Built once with `build`, then answers point-to-point queries by searching
+/// upward from both ends, which touches only a few hundred nodes even on large graphs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractionHierarchy {
+    /// `graph_fingerprint` of the graph this was built from
+    fingerprint: u64,
+    edge_count: usize,
+    /// Arcs from each node to higher-ranked neighbours, shortcuts included
+    up: Vec<Vec<UpArc>>,
+    shortcuts: usize,
+}
+
+/// State of an ongoing contraction
+struct Contractor {
+    /// Neighbours of each not-yet-contracted node: weight, and the node a shortcut
+    /// bypasses. Ordered maps keep the contraction, and so the stored hierarchy, deterministic
+    overlay: Vec<BTreeMap<usize, (f64, Option<usize>)>>,
+    contracted_neighbours: Vec<usize>,
+    level: Vec<usize>,
+    /// Witness search distances, reset through `touched` instead of reallocated
+    distance: Vec<f64>,
+    touched: Vec<usize>,
+    heap: BinaryHeap<Entry>,
+}
+
+impl Contractor {
+    /// Distances from `source` avoiding `skip`, settling at most the witness limit
+    fn witness_search(&mut self, source: usize, skip: usize, limit: f64) {
+        for node in self.touched.drain(..) {
+            self.distance[node] = f64::INFINITY;
+        }
+        self.heap.clear();
+        self.distance[source] = 0.0;
+        self.touched.push(source);
+        self.heap.push(Entry {
+            key: 0.0,
+            node: source,
+        });
+
+        let mut settled = 0;
+        while let Some(Entry { key, node }) = self.heap.pop() {
+            if key > self.distance[node] {
+                continue;
+            }
+            if key > limit || settled == WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            settled += 1;
+            for (&next, &(weight, _)) in &self.overlay[node] {
+                let candidate = key + weight;
+                if next != skip && candidate < self.distance[next] {
+                    if self.distance[next].is_infinite() {
+                        self.touched.push(next);
+                    }
+                    self.distance[next] = candidate;
+                    self.heap.push(Entry {
+                        key: candidate,
+                        node: next,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Shortcuts needed to contract `node` now, as (from, to, weight) triples
+    fn needed_shortcuts(&mut self, node: usize) -> Vec<(usize, usize, f64)> {
+        let neighbours: Vec<(usize, f64)> = self.overlay[node]
+            .iter()
+            .map(|(&n, &(w, _))| (n, w))
+            .collect();
+        let mut shortcuts = Vec::new();
+
+        for (i, &(source, to_source)) in neighbours.iter().enumerate() {
+            let targets = &neighbours[i + 1..];
+            let Some(limit) = targets.iter().map(|&(_, w)| to_source + w).reduce(f64::max) else {
+                continue;
+            };
+            self.witness_search(source, node, limit);
+            for &(target, from_target) in targets {
+                let via = to_source + from_target;
+                if self.distance[target] > via {
+                    shortcuts.push((source, target, via));
+                }
+            }
+        }
+        shortcuts
+    }
+
+    /// Edge difference plus contracted neighbours plus level: cheap nodes go first,
+    /// and the last two terms spread contraction evenly so the hierarchy stays shallow
+    fn priority(&mut self, node: usize) -> f64 {
+        let added = self.needed_shortcuts(node).len() as f64;
+        2.0 * added - self.overlay[node].len() as f64
+            + self.contracted_neighbours[node] as f64
+            + self.level[node] as f64
+    }
+}
+
+impl ContractionHierarchy {
+    /// Pure function to contract a graph, weighting edges by Euclidean length
+    pub fn build(points: &[Point], edges: &[Edge]) -> Result<Self, String> {
+        let n = points.len();
+        let mut overlay = vec![BTreeMap::new(); n];
+        for (i, edge) in edges.iter().enumerate() {
+            if edge.from >= n || edge.to >= n {
+                return Err(format!("Edge {} refers to a point that does not exist", i));
+            }
+            if edge.from == edge.to {
+                continue;
+            }
+            let weight = euclidean_distance(&points[edge.from], &points[edge.to]);
+            for (a, b) in [(edge.from, edge.to), (edge.to, edge.from)] {
+                let entry = overlay[a].entry(b).or_insert((weight, None));
+                entry.0 = f64::min(entry.0, weight);
+            }
+        }
+
+        let mut state = Contractor {
+            overlay,
+            contracted_neighbours: vec![0; n],
+            level: vec![0; n],
+            distance: vec![f64::INFINITY; n],
+            touched: Vec::new(),
+            heap: BinaryHeap::new(),
+        };
+        let mut queue: BinaryHeap<Entry> = (0..n)
+            .map(|node| Entry {
+                key: state.priority(node),
+                node,
+            })
+            .collect();
+        let mut up = vec![Vec::new(); n];
+        let mut shortcuts = 0;
+
+        while let Some(Entry { node, .. }) = queue.pop() {
+            // Lazy update: contract only if the node is still the cheapest
+            let current = state.priority(node);
+            if queue.peek().is_some_and(|next| current > next.key) {
+                queue.push(Entry { key: current, node });
+                continue;
+            }
+
+            for (from, to, weight) in state.needed_shortcuts(node) {
+                let existing = state.overlay[from]
+                    .get(&to)
+                    .map_or(f64::INFINITY, |&(w, _)| w);
+                if weight < existing {
+                    state.overlay[from].insert(to, (weight, Some(node)));
+                    state.overlay[to].insert(from, (weight, Some(node)));
+                    shortcuts += 1;
+                }
+            }
+
+            let neighbours = std::mem::take(&mut state.overlay[node]);
+            for (&neighbour, &(weight, middle)) in &neighbours {
+                state.overlay[neighbour].remove(&node);
+                state.contracted_neighbours[neighbour] += 1;
+                state.level[neighbour] = state.level[neighbour].max(state.level[node] + 1);
+                up[node].push(UpArc {
+                    to: neighbour,
+                    weight,
+                    middle,
+                });
+            }
+        }
+
+        Ok(Self {
+            fingerprint: graph_fingerprint(points, edges),
+            edge_count: edges.len(),
+            up,
+            shortcuts,
+        })
+    }
+
+    /// `graph_fingerprint` of the graph this hierarchy answers queries for
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.up.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    /// Shortcut edges added during contraction
+    pub fn shortcut_count(&self) -> usize {
+        self.shortcuts
+    }
+
+    /// The arc between `a` and `b`, stored on whichever was contracted first
+    fn arc(&self, a: usize, b: usize) -> UpArc {
+        self.up[a]
+            .iter()
+            .chain(&self.up[b])
+            .find(|arc| arc.to == a || arc.to == b)
+            .copied()
+            .expect("search only follows arcs of the hierarchy")
+    }
+
+    /// Settles the closest queued node of `search`, unless it cannot beat `best`.
+    /// Returns the settled node, or `None` once this direction has nothing left to add
+    fn settle_next(&self, search: &mut UpwardSearch, best: f64) -> Option<Option<usize>> {
+        let Entry { key, node } = search.heap.pop()?;
+        if key > search.reached[&node].0 {
+            return Some(None);
+        }
+        if key >= best {
+            search.heap.clear();
+            return None;
+        }
+        // Stall on demand: a higher neighbour already offers a shorter way here,
+        // so nothing reached from this node can be on a shortest path
+        let stalled = self.up[node].iter().any(|arc| {
+            search
+                .reached
+                .get(&arc.to)
+                .is_some_and(|&(d, _)| d + arc.weight < key)
+        });
+        if !stalled {
+            for arc in &self.up[node] {
+                let candidate = key + arc.weight;
+                if search
+                    .reached
+                    .get(&arc.to)
+                    .is_none_or(|&(d, _)| candidate < d)
+                {
+                    search.reached.insert(arc.to, (candidate, Some(node)));
+                    search.heap.push(Entry {
+                        key: candidate,
+                        node: arc.to,
+                    });
+                }
+            }
+        }
+        Some(Some(node))
+    }
+
+    /// Original edges hidden behind the hierarchy path `a`..`b`, appended to `path`
+    fn unpack(&self, a: usize, b: usize, path: &mut Vec<usize>, distance: &mut f64) {
+        let mut pending = vec![(a, b)];
+        while let Some((from, to)) = pending.pop() {
+            let arc = self.arc(from, to);
+            match arc.middle {
+                Some(middle) => {
+                    pending.push((middle, to));
+                    pending.push((from, middle));
+                }
+                None => {
+                    path.push(to);
+                    *distance += arc.weight;
+                }
+            }
+        }
+    }
+
+    /// Pure function to find the shortest path between two points of the graph
+    /// Returns the same distance as `compute_shortest_path`; where several paths are
+    /// equally short (as on a grid) it may return a different one of them
+    pub fn shortest_path(&self, start_idx: usize, end_idx: usize) -> Result<PathResult, String> {
+        for index in [start_idx, end_idx] {
+            if index >= self.point_count() {
+                return Err(format!("Point index {} is out of range", index));
+            }
+        }
+
+        let mut forward = UpwardSearch::new(start_idx);
+        let mut backward = UpwardSearch::new(end_idx);
+        let mut best = f64::INFINITY;
+        let mut meeting = None;
+        // Alternate directions; each stops once its queue cannot beat the best meeting
+        while !forward.heap.is_empty() || !backward.heap.is_empty() {
+            let next_key =
+                |search: &UpwardSearch| search.heap.peek().map_or(f64::INFINITY, |e| e.key);
+            let (this, other) = if next_key(&forward) <= next_key(&backward) {
+                (&mut forward, &backward)
+            } else {
+                (&mut backward, &forward)
+            };
+            let Some(settled) = self.settle_next(this, best) else {
+                continue;
+            };
+            if let Some(node) = settled {
+                if let Some(&(d, _)) = other.reached.get(&node) {
+                    let total = this.reached[&node].0 + d;
+                    if total < best {
+                        best = total;
+                        meeting = Some(node);
+                    }
+                }
+            }
+        }
+        let meeting = meeting.ok_or_else(|| "No path found".to_string())?;
+
+        let mut hierarchy_path = vec![meeting];
+        let mut node = meeting;
+        while let Some(previous) = forward.reached[&node].1 {
+            hierarchy_path.push(previous);
+            node = previous;
+        }
+        hierarchy_path.reverse();
+        node = meeting;
+        while let Some(next) = backward.reached[&node].1 {
+            hierarchy_path.push(next);
+            node = next;
+        }
+
+        // Summing original edges in path order matches plain Dijkstra bit for bit
+        // whenever both find the same path
+        let mut path = vec![start_idx];
+        let mut distance = 0.0;
+        for pair in hierarchy_path.windows(2) {
+            self.unpack(pair[0], pair[1], &mut path, &mut distance);
+        }
+        Ok(PathResult {
+            path,
+            distance,
+            turn_cost: None,
+            geometry: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_shortest_path;
+    use crate::generate::generate_graph;
+    use shared_types::GraphModel;
+    use std::collections::HashSet;
+
+    fn assert_matches_dijkstra(model: GraphModel, seeds: std::ops::Range<u64>) {
+        for seed in seeds {
+            let graph = generate_graph(&model, seed, 100.0, 100.0).unwrap();
+            let hierarchy = ContractionHierarchy::build(&graph.points, &graph.edges).unwrap();
+            let n = graph.points.len();
+            let edges: HashSet<_> = graph
+                .edges
+                .iter()
+                .map(|e| (e.from.min(e.to), e.from.max(e.to)))
+                .collect();
+
+            for i in 0..25 {
+                let (start, end) = ((i * 37 + seed as usize) % n, (i * 91 + 7) % n);
+                let expected = compute_shortest_path(&graph.points, &graph.edges, start, end);
+                match (expected, hierarchy.shortest_path(start, end)) {
+                    (Ok(expected), Ok(found)) => {
+                        let context = format!("seed {} {}->{}", seed, start, end);
+                        assert!(
+                            (expected.distance - found.distance).abs() < 1e-9,
+                            "{}",
+                            context
+                        );
+                        assert_eq!(found.path.first(), Some(&start), "{}", context);
+                        assert_eq!(found.path.last(), Some(&end), "{}", context);
+                        for pair in found.path.windows(2) {
+                            let edge = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                            assert!(edges.contains(&edge), "{}", context);
+                        }
+                    }
+                    (Err(expected), Err(found)) => assert_eq!(expected, found),
+                    (expected, found) => panic!("seed {}: {:?} vs {:?}", seed, expected, found),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hierarchy_matches_dijkstra_on_random_graphs() {
+        assert_matches_dijkstra(GraphModel::NearestNeighbours { count: 300, k: 3 }, 0..8);
+        assert_matches_dijkstra(
+            GraphModel::RandomGeometric {
+                count: 300,
+                radius: 9.0,
+            },
+            0..8,
+        );
+        assert_matches_dijkstra(
+            GraphModel::BarabasiAlbert {
+                count: 200,
+                attachments: 2,
+            },
+            0..4,
+        );
+        // Many equally short paths, so only the distances have to agree
+        assert_matches_dijkstra(
+            GraphModel::Grid {
+                rows: 10,
+                cols: 10,
+                diagonals: None,
+            },
+            0..4,
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_survives_serialization() {
+        let model = GraphModel::NearestNeighbours { count: 200, k: 4 };
+        let graph = generate_graph(&model, 11, 1.0, 1.0).unwrap();
+        let hierarchy = ContractionHierarchy::build(&graph.points, &graph.edges).unwrap();
+
+        let bytes = serde_json::to_vec(&hierarchy).unwrap();
+        let restored: ContractionHierarchy = serde_json::from_slice(&bytes).unwrap();
+        let rebuilt = ContractionHierarchy::build(&graph.points, &graph.edges).unwrap();
+
+        assert_eq!(bytes, serde_json::to_vec(&rebuilt).unwrap());
+        assert_eq!(
+            restored.shortest_path(3, 150).unwrap().path,
+            hierarchy.shortest_path(3, 150).unwrap().path
+        );
+        assert_eq!(
+            restored.fingerprint(),
+            graph_fingerprint(&graph.points, &graph.edges)
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_rejects_bad_indices() {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+        ];
+        let edges = vec![Edge { from: 0, to: 1 }];
+        let hierarchy = ContractionHierarchy::build(&points, &edges).unwrap();
+
+        assert_eq!(hierarchy.shortest_path(0, 0).unwrap().path, vec![0]);
+        assert_eq!(
+            hierarchy.shortest_path(0, 2).unwrap_err(),
+            "Point index 2 is out of range"
+        );
+        assert!(ContractionHierarchy::build(&points, &[Edge { from: 0, to: 5 }]).is_err());
+    }
+}
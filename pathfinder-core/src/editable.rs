@@ -0,0 +1,476 @@
+use crate::{build_weighted_graph, check_edge, euclidean_distance, shortest_path_in_graph};
+use serde::{Deserialize, Serialize};
+use shared_types::router::CallLimits;
+use shared_types::{Edge, GraphEdit, PathKey, PathResult, Point};
+
+/// Slack for floating point noise when comparing path lengths
+const LENGTH_EPSILON: f64 = 1e-9;
+
+/// Paths kept per graph; the oldest is dropped to make room for a new one
+const MAX_CACHED_PATHS: usize = 256;
+
+/// A shortest path computed on some version of the graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedPath {
+    start_idx: usize,
+    end_idx: usize,
+    result: PathResult,
+}
+
+/// Cost of the link between two points before and after an edit
+/// An infinite weight stands for "no edge"
+struct WeightChange {
+    from: usize,
+    to: usize,
+    old: f64,
+    new: f64,
+}
+
+/// A graph kept between calls, with the shortest paths computed on it
+/// Edits only drop the cached paths they can affect, so a client dragging one
+/// point around does not pay for recomputing every route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditableGraph {
+    points: Vec<Point>,
+    edges: Vec<Edge>,
+    /// Weight overrides indexed like `edges`; `None` means Euclidean length
+    weights: Vec<Option<f64>>,
+    version: u64,
+    cache: Vec<CachedPath>,
+}
+
+fn check_weight(weight: Option<f64>) -> Result<(), String> {
+    match weight {
+        Some(w) if !w.is_finite() || w < 0.0 => {
+            Err(format!("Edge weight {} must be a non-negative number", w))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl EditableGraph {
+    pub fn new(
+        points: Vec<Point>,
+        edges: Vec<Edge>,
+        weights: Option<Vec<Option<f64>>>,
+    ) -> Result<Self, String> {
+        let weights = weights.unwrap_or_else(|| vec![None; edges.len()]);
+        if weights.len() != edges.len() {
+            return Err(format!(
+                "Expected {} weights, one per edge, but got {}",
+                edges.len(),
+                weights.len()
+            ));
+        }
+        for edge in &edges {
+            check_edge(edge, points.len())?;
+        }
+        for &weight in &weights {
+            check_weight(weight)?;
+        }
+
+        Ok(Self {
+            points,
+            edges,
+            weights,
+            version: 0,
+            cache: Vec::new(),
+        })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn cached_paths(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn weight_of(points: &[Point], edge: &Edge, weight: Option<f64>) -> f64 {
+        weight.unwrap_or_else(|| euclidean_distance(&points[edge.from], &points[edge.to]))
+    }
+
+    /// Current cost of edge `index`
+    pub fn edge_weight(&self, index: usize) -> f64 {
+        Self::weight_of(&self.points, &self.edges[index], self.weights[index])
+    }
+
+    /// Shortest path between two points, and whether it came from the cache
+    pub fn shortest_path(
+        &mut self,
+        start_idx: usize,
+        end_idx: usize,
+    ) -> Result<(PathResult, bool), String> {
+        if start_idx >= self.points.len() || end_idx >= self.points.len() {
+            return Err("Start or end index is out of bounds".to_string());
+        }
+        if let Some(cached) = self
+            .cache
+            .iter()
+            .find(|c| c.start_idx == start_idx && c.end_idx == end_idx)
+        {
+            return Ok((cached.result.clone(), true));
+        }
+
+        let (graph, nodes) =
+            build_weighted_graph(self.points.len(), &self.edges, |i| self.edge_weight(i));
+        let result = shortest_path_in_graph(&graph, &nodes, start_idx, end_idx)?;
+
+        if self.cache.len() >= MAX_CACHED_PATHS {
+            self.cache.remove(0);
+        }
+        self.cache.push(CachedPath {
+            start_idx,
+            end_idx,
+            result: result.clone(),
+        });
+        Ok((result, false))
+    }
+
+    /// Applies `edits` in order, all or nothing, and bumps the version
+    /// Fails if the edited graph would be over `limits`.
+    /// Returns the cached paths that had to be dropped
+    pub fn apply_edits(
+        &mut self,
+        edits: &[GraphEdit],
+        limits: &CallLimits,
+    ) -> Result<Vec<PathKey>, String> {
+        let mut points = self.points.clone();
+        let mut edges = self.edges.clone();
+        let mut weights = self.weights.clone();
+        let mut changes = Vec::new();
+
+        for (i, edit) in edits.iter().enumerate() {
+            Self::apply_edit(&mut points, &mut edges, &mut weights, edit, &mut changes)
+                .map_err(|e| format!("Edit {}: {}", i, e))?;
+        }
+        limits.check_graph(points.len() as u64, edges.len() as u64)?;
+
+        self.points = points;
+        self.edges = edges;
+        self.weights = weights;
+        self.version += 1;
+        Ok(self.invalidate(&changes))
+    }
+
+    fn apply_edit(
+        points: &mut [Point],
+        edges: &mut Vec<Edge>,
+        weights: &mut Vec<Option<f64>>,
+        edit: &GraphEdit,
+        changes: &mut Vec<WeightChange>,
+    ) -> Result<(), String> {
+        match edit {
+            GraphEdit::MovePoint { index, point } => {
+                if *index >= points.len() {
+                    return Err(format!("Point index {} is out of bounds", index));
+                }
+                if !point.x.is_finite() || !point.y.is_finite() {
+                    return Err("Point coordinates must be finite".to_string());
+                }
+                let incident: Vec<(usize, f64)> = edges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| e.from == *index || e.to == *index)
+                    .map(|(i, e)| (i, Self::weight_of(points, e, weights[i])))
+                    .collect();
+                points[*index] = point.clone();
+                for (i, old) in incident {
+                    let edge = &edges[i];
+                    changes.push(WeightChange {
+                        from: edge.from,
+                        to: edge.to,
+                        old,
+                        new: Self::weight_of(points, edge, weights[i]),
+                    });
+                }
+            }
+            GraphEdit::AddEdge { edge, weight } => {
+                check_edge(edge, points.len())?;
+                check_weight(*weight)?;
+                changes.push(WeightChange {
+                    from: edge.from,
+                    to: edge.to,
+                    old: f64::INFINITY,
+                    new: Self::weight_of(points, edge, *weight),
+                });
+                edges.push(edge.clone());
+                weights.push(*weight);
+            }
+            GraphEdit::RemoveEdge { index } => {
+                if *index >= edges.len() {
+                    return Err(format!("Edge index {} is out of bounds", index));
+                }
+                let edge = edges.remove(*index);
+                let weight = weights.remove(*index);
+                changes.push(WeightChange {
+                    from: edge.from,
+                    to: edge.to,
+                    old: Self::weight_of(points, &edge, weight),
+                    new: f64::INFINITY,
+                });
+            }
+            GraphEdit::SetEdgeWeight { index, weight } => {
+                if *index >= edges.len() {
+                    return Err(format!("Edge index {} is out of bounds", index));
+                }
+                check_weight(*weight)?;
+                let edge = &edges[*index];
+                changes.push(WeightChange {
+                    from: edge.from,
+                    to: edge.to,
+                    old: Self::weight_of(points, edge, weights[*index]),
+                    new: Self::weight_of(points, edge, *weight),
+                });
+                weights[*index] = *weight;
+            }
+        }
+        Ok(())
+    }
+
+    /// True if no edge is cheaper than the straight line between its endpoints,
+    /// which makes Euclidean distance a lower bound on path length
+    fn is_metric(&self) -> bool {
+        self.edges.iter().zip(&self.weights).all(|(edge, weight)| {
+            weight.is_none_or(|w| {
+                w + LENGTH_EPSILON
+                    >= euclidean_distance(&self.points[edge.from], &self.points[edge.to])
+            })
+        })
+    }
+
+    /// Drops cached paths that `changes` may have made wrong or suboptimal
+    /// A path is stale if it uses a changed link, or if a link got cheaper and
+    /// the Euclidean lower bound through that link beats the cached distance
+    fn invalidate(&mut self, changes: &[WeightChange]) -> Vec<PathKey> {
+        let changes: Vec<&WeightChange> = changes.iter().filter(|c| c.old != c.new).collect();
+        let metric = self.is_metric();
+        let points = &self.points;
+        let straight = |a: usize, b: usize| euclidean_distance(&points[a], &points[b]);
+
+        let mut invalidated = Vec::new();
+        self.cache.retain(|cached| {
+            let path = &cached.result.path;
+            let uses = |c: &WeightChange| {
+                path.windows(2)
+                    .any(|w| (w[0], w[1]) == (c.from, c.to) || (w[0], w[1]) == (c.to, c.from))
+            };
+            let shortcut = |c: &WeightChange| {
+                if c.new >= c.old {
+                    return false;
+                }
+                if !metric {
+                    return true;
+                }
+                let (s, t) = (cached.start_idx, cached.end_idx);
+                let bound = (straight(s, c.from) + straight(c.to, t))
+                    .min(straight(s, c.to) + straight(c.from, t))
+                    + c.new;
+                bound < cached.result.distance - LENGTH_EPSILON
+            };
+
+            let stale = changes.iter().any(|c| uses(c) || shortcut(c));
+            if stale {
+                invalidated.push(PathKey {
+                    start_idx: cached.start_idx,
+                    end_idx: cached.end_idx,
+                });
+            }
+            !stale
+        });
+        invalidated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 ladder: bottom row 0-1-2, top row 3-4-5, rungs between them
+    fn ladder() -> EditableGraph {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 1.0,
+                z: None,
+            },
+        ];
+        let edges = vec![
+            Edge { from: 0, to: 1 },
+            Edge { from: 1, to: 2 },
+            Edge { from: 3, to: 4 },
+            Edge { from: 4, to: 5 },
+            Edge { from: 0, to: 3 },
+            Edge { from: 1, to: 4 },
+            Edge { from: 2, to: 5 },
+        ];
+        EditableGraph::new(points, edges, None).unwrap()
+    }
+
+    #[test]
+    fn test_cached_path_is_reused() {
+        let mut graph = ladder();
+        let (first, cached) = graph.shortest_path(0, 2).unwrap();
+        assert!(!cached);
+        let (second, cached) = graph.shortest_path(0, 2).unwrap();
+        assert!(cached);
+        assert_eq!(first.path, second.path);
+    }
+
+    #[test]
+    fn test_cache_drops_oldest_path_when_full() {
+        let points = (0..20)
+            .map(|i| Point {
+                x: i as f64,
+                y: 0.0,
+                z: None,
+            })
+            .collect();
+        let edges = (0..19).map(|i| Edge { from: i, to: i + 1 }).collect();
+        let mut graph = EditableGraph::new(points, edges, None).unwrap();
+        let pairs: Vec<_> = (0..20)
+            .flat_map(|s| (0..20).map(move |t| (s, t)))
+            .take(MAX_CACHED_PATHS + 1)
+            .collect();
+        for &(s, t) in &pairs {
+            graph.shortest_path(s, t).unwrap();
+        }
+        assert_eq!(graph.cached_paths(), MAX_CACHED_PATHS);
+
+        let (s, t) = pairs[MAX_CACHED_PATHS];
+        assert!(graph.shortest_path(s, t).unwrap().1);
+        assert!(!graph.shortest_path(0, 0).unwrap().1);
+    }
+
+    #[test]
+    fn test_weight_increase_only_drops_paths_using_the_edge() {
+        let mut graph = ladder();
+        graph.shortest_path(0, 2).unwrap();
+        graph.shortest_path(3, 5).unwrap();
+
+        let invalidated = graph
+            .apply_edits(
+                &[GraphEdit::SetEdgeWeight {
+                    index: 1,
+                    weight: Some(10.0),
+                }],
+                &CallLimits::unlimited(),
+            )
+            .unwrap();
+
+        assert_eq!(invalidated.len(), 1);
+        assert_eq!((invalidated[0].start_idx, invalidated[0].end_idx), (0, 2));
+        assert_eq!(graph.cached_paths(), 1);
+        assert_eq!(graph.version(), 1);
+
+        let (detour, cached) = graph.shortest_path(0, 2).unwrap();
+        assert!(!cached);
+        assert_eq!(detour.path, vec![0, 1, 4, 5, 2]);
+    }
+
+    #[test]
+    fn test_new_shortcut_drops_paths_it_could_improve() {
+        let mut graph = ladder();
+        graph.shortest_path(0, 5).unwrap();
+        graph.shortest_path(0, 1).unwrap();
+
+        // The diagonal can shorten 0 -> 5 but never beats the direct edge 0-1
+        let invalidated = graph
+            .apply_edits(
+                &[GraphEdit::AddEdge {
+                    edge: Edge { from: 0, to: 5 },
+                    weight: None,
+                }],
+                &CallLimits::unlimited(),
+            )
+            .unwrap();
+
+        assert_eq!(invalidated.len(), 1);
+        assert_eq!((invalidated[0].start_idx, invalidated[0].end_idx), (0, 5));
+        assert_eq!(graph.shortest_path(0, 5).unwrap().0.path, vec![0, 5]);
+    }
+
+    #[test]
+    fn test_edits_may_not_grow_the_graph_past_limits() {
+        let mut graph = ladder();
+        let limits = CallLimits {
+            max_edges: Some(7),
+            ..CallLimits::unlimited()
+        };
+        let add = [GraphEdit::AddEdge {
+            edge: Edge { from: 0, to: 5 },
+            weight: None,
+        }];
+
+        assert_eq!(
+            graph.apply_edits(&add, &limits).unwrap_err(),
+            "Graph has 8 edges; at most 7 are allowed"
+        );
+        assert_eq!((graph.edge_count(), graph.version()), (7, 0));
+        let swap = [GraphEdit::RemoveEdge { index: 0 }, add[0].clone()];
+        graph.apply_edits(&swap, &limits).unwrap();
+        assert_eq!(graph.edge_count(), 7);
+    }
+
+    #[test]
+    fn test_failed_edit_leaves_graph_untouched() {
+        let mut graph = ladder();
+        graph.shortest_path(0, 2).unwrap();
+
+        let result = graph.apply_edits(
+            &[
+                GraphEdit::RemoveEdge { index: 0 },
+                GraphEdit::MovePoint {
+                    index: 9,
+                    point: Point {
+                        x: 0.0,
+                        y: 0.0,
+                        z: None,
+                    },
+                },
+            ],
+            &CallLimits::unlimited(),
+        );
+
+        assert_eq!(
+            result.unwrap_err(),
+            "Edit 1: Point index 9 is out of bounds"
+        );
+        assert_eq!(graph.edge_count(), 7);
+        assert_eq!(graph.version(), 0);
+        assert!(graph.shortest_path(0, 2).unwrap().1);
+    }
+}
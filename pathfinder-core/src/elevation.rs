@@ -0,0 +1,100 @@
+use shared_types::Point;
+
+/// Pure function to compute the elevation change from `a` to `b`
+/// Zero unless both points have a `z`, so 2D graphs behave as before
+pub fn elevation_change(a: &Point, b: &Point) -> f64 {
+    match (a.z, b.z) {
+        (Some(za), Some(zb)) => zb - za,
+        _ => 0.0,
+    }
+}
+
+/// Pure function to compute the grade of a segment: elevation change over
+/// horizontal length, ignoring direction. A vertical segment is infinitely steep
+pub fn grade(a: &Point, b: &Point) -> f64 {
+    let rise = elevation_change(a, b).abs();
+    if rise == 0.0 {
+        return 0.0;
+    }
+    rise / (b.x - a.x).hypot(b.y - a.y)
+}
+
+/// Pure function to weight an edge for routing on terrain
+/// Its 3D length plus `slope_penalty` per unit of elevation gained or lost
+pub fn edge_cost(a: &Point, b: &Point, slope_penalty: f64) -> f64 {
+    crate::euclidean_distance(a, b) + slope_penalty * elevation_change(a, b).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compute_route, euclidean_distance};
+    use shared_types::{Edge, ShortestPathParams};
+
+    fn p(x: f64, y: f64, z: f64) -> Point {
+        Point { x, y, z: Some(z) }
+    }
+
+    /// Straight over a hill (0-1-3) or the long way round the flat (0-2-3)
+    fn hill(slope_penalty: Option<f64>, max_grade: Option<f64>) -> ShortestPathParams {
+        ShortestPathParams {
+            points: vec![
+                p(0.0, 0.0, 0.0),
+                p(1.0, 0.0, 0.5),
+                p(1.0, 1.0, 0.0),
+                p(2.0, 0.0, 0.0),
+            ],
+            edges: vec![
+                Edge { from: 0, to: 1 },
+                Edge { from: 1, to: 3 },
+                Edge { from: 0, to: 2 },
+                Edge { from: 2, to: 3 },
+            ],
+            start_idx: 0,
+            end_idx: 3,
+            forbidden_nodes: None,
+            forbidden_edges: None,
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: None,
+            bidirectional: None,
+            post_processing: None,
+            slope_penalty,
+            max_grade,
+        }
+    }
+
+    #[test]
+    fn test_distance_and_grade_use_elevation_only_when_both_points_have_it() {
+        let flat = Point {
+            x: 3.0,
+            y: 0.0,
+            z: None,
+        };
+        assert_eq!(
+            euclidean_distance(&p(0.0, 0.0, 0.0), &p(3.0, 0.0, 4.0)),
+            5.0
+        );
+        assert_eq!(euclidean_distance(&p(0.0, 0.0, 9.0), &flat), 3.0);
+        assert_eq!(grade(&p(0.0, 0.0, 1.0), &p(4.0, 3.0, 0.0)), 0.2);
+        assert_eq!(grade(&p(1.0, 1.0, 0.0), &p(1.0, 1.0, 2.0)), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_slope_penalty_and_max_grade_route_around_the_hill() {
+        let over = compute_route(&hill(None, None)).unwrap();
+        assert_eq!(over.path, vec![0, 1, 3]);
+        assert!((over.distance - 2.0 * 1.25f64.sqrt()).abs() < 1e-12);
+
+        // Climbing 0.5 and descending 0.5 costs 1.0 extra, more than the detour
+        let penalised = compute_route(&hill(Some(1.0), None)).unwrap();
+        assert_eq!(penalised.path, vec![0, 2, 3]);
+        assert!((penalised.distance - 2.0 * 2f64.sqrt()).abs() < 1e-12);
+
+        let capped = compute_route(&hill(None, Some(0.3))).unwrap();
+        assert_eq!(capped.path, vec![0, 2, 3]);
+
+        let error = compute_route(&hill(Some(-1.0), None)).unwrap_err();
+        assert_eq!(error, "Slope penalty must be a non-negative number");
+    }
+}
@@ -0,0 +1,432 @@
+use crate::PathConstraints;
+use serde_json::{json, Value};
+use shared_types::{ExportFormat, PathResult, Point, Region, ShortestPathParams};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Width of exported SVG images in pixels; the height follows the aspect ratio
+const SVG_WIDTH: f64 = 800.0;
+/// Blank border around the drawing in SVG images
+const SVG_MARGIN: f64 = 20.0;
+
+/// What to draw, resolved once for all formats
+struct Scene<'a> {
+    path: &'a [usize],
+    /// Unordered point pairs along the path
+    path_pairs: HashSet<(usize, usize)>,
+    blocked_nodes: Vec<bool>,
+    allowed_edges: Vec<bool>,
+}
+
+impl<'a> Scene<'a> {
+    fn new(params: &'a ShortestPathParams, result: Option<&'a PathResult>) -> Result<Self, String> {
+        let points = &params.points;
+        if let Some((i, _)) = params
+            .edges
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.from >= points.len() || e.to >= points.len())
+        {
+            return Err(format!("Edge {} refers to a point that does not exist", i));
+        }
+        let path = result.map_or(&[][..], |r| r.path.as_slice());
+        if let Some(&node) = path.iter().find(|&&n| n >= points.len()) {
+            return Err(format!("Path point {} does not exist", node));
+        }
+
+        let constraints = PathConstraints::from_params(params);
+        Ok(Self {
+            path,
+            path_pairs: path
+                .windows(2)
+                .map(|w| (w[0].min(w[1]), w[0].max(w[1])))
+                .collect(),
+            blocked_nodes: constraints.blocked_nodes(points),
+            allowed_edges: constraints.allowed_edge_mask(points, &params.edges)?,
+        })
+    }
+
+    fn on_path(&self, from: usize, to: usize) -> bool {
+        self.path_pairs.contains(&(from.min(to), from.max(to)))
+    }
+}
+
+fn position(point: &Point) -> Value {
+    match point.z {
+        Some(z) => json!([point.x, point.y, z]),
+        None => json!([point.x, point.y]),
+    }
+}
+
+fn region_ring(region: &Region) -> Vec<Point> {
+    match region {
+        Region::Rect { min, max } => vec![
+            Point {
+                x: min.x,
+                y: min.y,
+                z: None,
+            },
+            Point {
+                x: max.x,
+                y: min.y,
+                z: None,
+            },
+            Point {
+                x: max.x,
+                y: max.y,
+                z: None,
+            },
+            Point {
+                x: min.x,
+                y: max.y,
+                z: None,
+            },
+        ],
+        Region::Polygon { vertices } => vertices.clone(),
+    }
+}
+
+/// Pure function to export a graph and optional path as a GeoJSON FeatureCollection
+/// Points and edges carry their index and flags in `properties`; the path is a
+/// single LineString with `"kind": "path"` and forbidden regions are Polygons
+pub fn to_geojson(
+    params: &ShortestPathParams,
+    result: Option<&PathResult>,
+) -> Result<String, String> {
+    let scene = Scene::new(params, result)?;
+    let points = &params.points;
+    let mut features = Vec::new();
+
+    for (i, point) in points.iter().enumerate() {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position(point) },
+            "properties": {
+                "kind": "point",
+                "index": i,
+                "forbidden": scene.blocked_nodes[i],
+                "start": i == params.start_idx,
+                "end": i == params.end_idx,
+            },
+        }));
+    }
+
+    for (i, edge) in params.edges.iter().enumerate() {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": [position(&points[edge.from]), position(&points[edge.to])],
+            },
+            "properties": {
+                "kind": "edge",
+                "index": i,
+                "forbidden": !scene.allowed_edges[i],
+                "on_path": scene.on_path(edge.from, edge.to),
+            },
+        }));
+    }
+
+    for region in params.forbidden_regions.iter().flatten() {
+        let mut ring: Vec<Value> = region_ring(region).iter().map(position).collect();
+        ring.push(ring[0].clone());
+        features.push(json!({
+            "type": "Feature",
+            "geometry": { "type": "Polygon", "coordinates": [ring] },
+            "properties": { "kind": "forbidden_region" },
+        }));
+    }
+
+    if let Some(result) = result.filter(|r| r.path.len() > 1) {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": result.path.iter().map(|&i| position(&points[i])).collect::<Vec<_>>(),
+            },
+            "properties": {
+                "kind": "path",
+                "distance": result.distance,
+                "turn_cost": result.turn_cost,
+            },
+        }));
+    }
+
+    serde_json::to_string_pretty(&json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+    .map_err(|e| e.to_string())
+}
+
+/// Pure function to export a graph as Graphviz DOT with the path highlighted
+/// Nodes are named by index and pinned with `pos`, so `neato -n` keeps the layout
+pub fn to_dot(params: &ShortestPathParams, result: Option<&PathResult>) -> Result<String, String> {
+    let scene = Scene::new(params, result)?;
+    let on_path: HashSet<usize> = scene.path.iter().copied().collect();
+    let mut dot =
+        String::from("graph pathfinder {\n    node [shape=circle, width=0.1, label=\"\"];\n");
+
+    for (i, point) in params.points.iter().enumerate() {
+        let mut attributes = vec![format!("pos=\"{},{}!\"", point.x, point.y)];
+        if i == params.start_idx || i == params.end_idx {
+            attributes.push(format!("xlabel=\"{}\"", i));
+        }
+        if on_path.contains(&i) {
+            attributes.push("color=red, style=filled, fillcolor=red".to_string());
+        } else if scene.blocked_nodes[i] {
+            attributes.push("color=gray, style=dashed".to_string());
+        }
+        let _ = writeln!(dot, "    {} [{}];", i, attributes.join(", "));
+    }
+
+    for (i, edge) in params.edges.iter().enumerate() {
+        let style = if scene.on_path(edge.from, edge.to) {
+            " [color=red, penwidth=3]"
+        } else if !scene.allowed_edges[i] {
+            " [color=gray, style=dashed]"
+        } else {
+            ""
+        };
+        let _ = writeln!(dot, "    {} -- {}{};", edge.from, edge.to, style);
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+/// Pure function to render a graph and optional path as a standalone SVG image
+/// The drawing is scaled to fit and flipped so that y grows upwards
+pub fn to_svg(params: &ShortestPathParams, result: Option<&PathResult>) -> Result<String, String> {
+    let scene = Scene::new(params, result)?;
+    let regions: Vec<Vec<Point>> = params
+        .forbidden_regions
+        .iter()
+        .flatten()
+        .map(region_ring)
+        .collect();
+
+    let all_points = params.points.iter().chain(regions.iter().flatten());
+    let (min_x, min_y, max_x, max_y) = all_points.fold(
+        (
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(x0, y0, x1, y1), p| (x0.min(p.x), y0.min(p.y), x1.max(p.x), y1.max(p.y)),
+    );
+    let (span_x, span_y) = if min_x.is_finite() {
+        ((max_x - min_x).max(1e-9), (max_y - min_y).max(1e-9))
+    } else {
+        (1.0, 1.0)
+    };
+    let scale = (SVG_WIDTH - 2.0 * SVG_MARGIN) / span_x.max(span_y);
+    let width = span_x * scale + 2.0 * SVG_MARGIN;
+    let height = span_y * scale + 2.0 * SVG_MARGIN;
+    let project = |p: &Point| {
+        (
+            SVG_MARGIN + (p.x - min_x) * scale,
+            height - SVG_MARGIN - (p.y - min_y) * scale,
+        )
+    };
+    let polyline = |points: &mut dyn Iterator<Item = &Point>| {
+        points
+            .map(|p| {
+                let (x, y) = project(p);
+                format!("{:.2},{:.2}", x, y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.2} {:.2}\">",
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        "  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>"
+    );
+
+    for ring in &regions {
+        let _ = writeln!(
+            svg,
+            "  <polygon points=\"{}\" fill=\"#f4cccc\" stroke=\"#cc0000\" stroke-dasharray=\"4\"/>",
+            polyline(&mut ring.iter())
+        );
+    }
+
+    for (i, edge) in params.edges.iter().enumerate() {
+        let (x1, y1) = project(&params.points[edge.from]);
+        let (x2, y2) = project(&params.points[edge.to]);
+        let style = if scene.allowed_edges[i] {
+            "stroke=\"#999999\" stroke-width=\"1\""
+        } else {
+            "stroke=\"#cccccc\" stroke-width=\"1\" stroke-dasharray=\"3\""
+        };
+        let _ = writeln!(
+            svg,
+            "  <line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" {}/>",
+            x1, y1, x2, y2, style
+        );
+    }
+
+    if scene.path.len() > 1 {
+        let _ = writeln!(
+            svg,
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"#d62728\" stroke-width=\"3\" stroke-linejoin=\"round\"/>",
+            polyline(&mut scene.path.iter().map(|&i| &params.points[i]))
+        );
+    }
+
+    for (i, point) in params.points.iter().enumerate() {
+        let (x, y) = project(point);
+        let (radius, fill) = if i == params.start_idx {
+            (5.0, "#2ca02c")
+        } else if i == params.end_idx {
+            (5.0, "#d62728")
+        } else if scene.blocked_nodes[i] {
+            (3.0, "#cccccc")
+        } else {
+            (3.0, "#333333")
+        };
+        let _ = writeln!(
+            svg,
+            "  <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{}\" fill=\"{}\"><title>{}</title></circle>",
+            x, y, radius, fill, i
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Core business logic: export a graph and optional path in the requested format
+pub fn export_graph(
+    params: &ShortestPathParams,
+    result: Option<&PathResult>,
+    format: &ExportFormat,
+) -> Result<String, String> {
+    match format {
+        ExportFormat::GeoJson => to_geojson(params, result),
+        ExportFormat::Dot => to_dot(params, result),
+        ExportFormat::Svg => to_svg(params, result),
+    }
+}
+
+/// MIME type of each export format
+pub fn content_type(format: &ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::GeoJson => "application/geo+json",
+        ExportFormat::Dot => "text/vnd.graphviz",
+        ExportFormat::Svg => "image/svg+xml",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared_types::Edge;
+
+    fn triangle() -> (ShortestPathParams, PathResult) {
+        let params = ShortestPathParams {
+            points: vec![
+                Point {
+                    x: 0.0,
+                    y: 0.0,
+                    z: None,
+                },
+                Point {
+                    x: 1.0,
+                    y: 0.0,
+                    z: None,
+                },
+                Point {
+                    x: 1.0,
+                    y: 1.0,
+                    z: None,
+                },
+            ],
+            edges: vec![
+                Edge { from: 0, to: 1 },
+                Edge { from: 1, to: 2 },
+                Edge { from: 0, to: 2 },
+            ],
+            start_idx: 0,
+            end_idx: 2,
+            forbidden_nodes: None,
+            forbidden_edges: Some(vec![2]),
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: None,
+            bidirectional: None,
+            post_processing: None,
+            slope_penalty: None,
+            max_grade: None,
+        };
+        let result = PathResult {
+            path: vec![0, 1, 2],
+            distance: 2.0,
+            turn_cost: None,
+            geometry: None,
+        };
+        (params, result)
+    }
+
+    #[test]
+    fn test_geojson_marks_path_and_forbidden_edges() {
+        let (params, result) = triangle();
+        let text = to_geojson(&params, Some(&result)).unwrap();
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        let features = parsed["features"].as_array().unwrap();
+
+        // 3 points, 3 edges and the path
+        assert_eq!(features.len(), 7);
+        assert_eq!(features[3]["properties"]["on_path"], true);
+        assert_eq!(features[5]["properties"]["forbidden"], true);
+        assert_eq!(features[6]["geometry"]["coordinates"][2], json!([1.0, 1.0]));
+
+        // The import module reads its own exports back
+        let imported = crate::import::parse_geojson(&text, 1e-9).unwrap();
+        assert_eq!(imported.points.len(), 3);
+        assert_eq!(imported.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_dot_highlights_path_and_round_trips() {
+        let (params, result) = triangle();
+        let dot = to_dot(&params, Some(&result)).unwrap();
+
+        assert!(dot.contains("0 -- 1 [color=red, penwidth=3];"));
+        assert!(dot.contains("0 -- 2 [color=gray, style=dashed];"));
+
+        let imported =
+            crate::import::parse_dot(&dot, 0.0, &shared_types::router::CallLimits::unlimited())
+                .unwrap();
+        assert_eq!(imported.points[2].y, 1.0);
+        assert_eq!(imported.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_svg_is_standalone_and_flips_y() {
+        let (params, result) = triangle();
+        let svg = export_graph(&params, Some(&result), &ExportFormat::Svg).unwrap();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("<polyline points=\"20.00,780.00 780.00,780.00 780.00,20.00\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_export_rejects_path_outside_graph() {
+        let (params, mut result) = triangle();
+        result.path.push(7);
+        assert_eq!(
+            export_graph(&params, Some(&result), &ExportFormat::Dot).unwrap_err(),
+            "Path point 7 does not exist"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::check_edges;
+use shared_types::router::Progress;
+use shared_types::{Edge, MaxFlowResult};
+use std::collections::VecDeque;
+
+/// Residual capacity below this is treated as saturated
+const CAPACITY_EPSILON: f64 = 1e-12;
+
+/// Residual network where edge `i` owns arcs `2 * i` (from -> to) and
+/// `2 * i + 1` (to -> from), each acting as the other's reverse arc
+struct ResidualNetwork {
+    adjacency: Vec<Vec<usize>>,
+    head: Vec<usize>,
+    residual: Vec<f64>,
+}
+
+impl ResidualNetwork {
+    fn new(node_count: usize, edges: &[Edge], capacities: &[f64]) -> Self {
+        let mut adjacency = vec![Vec::new(); node_count];
+        let mut head = Vec::with_capacity(edges.len() * 2);
+        let mut residual = Vec::with_capacity(edges.len() * 2);
+
+        for (i, (edge, &capacity)) in edges.iter().zip(capacities).enumerate() {
+            adjacency[edge.from].push(2 * i);
+            adjacency[edge.to].push(2 * i + 1);
+            head.push(edge.to);
+            head.push(edge.from);
+            residual.push(capacity);
+            residual.push(capacity);
+        }
+
+        Self {
+            adjacency,
+            head,
+            residual,
+        }
+    }
+
+    /// BFS distances from `source` over arcs with spare capacity
+    fn levels(&self, source: usize) -> Vec<Option<usize>> {
+        let mut level = vec![None; self.adjacency.len()];
+        let mut queue = VecDeque::from([source]);
+        level[source] = Some(0);
+
+        while let Some(u) = queue.pop_front() {
+            for &arc in &self.adjacency[u] {
+                let v = self.head[arc];
+                if level[v].is_none() && self.residual[arc] > CAPACITY_EPSILON {
+                    level[v] = level[u].map(|l| l + 1);
+                    queue.push_back(v);
+                }
+            }
+        }
+
+        level
+    }
+
+    /// Saturates every shortest augmenting path in the level graph
+    fn blocking_flow(&mut self, source: usize, sink: usize, level: &mut [Option<usize>]) -> f64 {
+        let mut next_arc = vec![0; self.adjacency.len()];
+        let mut path: Vec<usize> = Vec::new();
+        let mut total = 0.0;
+        let mut u = source;
+
+        loop {
+            if u == sink {
+                let bottleneck = path
+                    .iter()
+                    .map(|&arc| self.residual[arc])
+                    .fold(f64::INFINITY, f64::min);
+                for &arc in &path {
+                    self.residual[arc] -= bottleneck;
+                    self.residual[arc ^ 1] += bottleneck;
+                }
+                total += bottleneck;
+                path.clear();
+                u = source;
+                continue;
+            }
+
+            let advance = self.adjacency[u][next_arc[u]..].iter().position(|&arc| {
+                let v = self.head[arc];
+                self.residual[arc] > CAPACITY_EPSILON
+                    && level[v].is_some()
+                    && level[v] == level[u].map(|l| l + 1)
+            });
+
+            match advance {
+                Some(offset) => {
+                    next_arc[u] += offset;
+                    let arc = self.adjacency[u][next_arc[u]];
+                    path.push(arc);
+                    u = self.head[arc];
+                }
+                None => {
+                    // Dead end: prune this node from the level graph and retreat
+                    next_arc[u] = self.adjacency[u].len();
+                    if u == source {
+                        return total;
+                    }
+                    level[u] = None;
+                    let arc = path.pop().unwrap();
+                    u = self.head[arc ^ 1];
+                    next_arc[u] += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Core business logic: maximum flow and minimum cut between two points
+/// Uses Dinic's algorithm; every edge can carry up to its capacity in either direction.
+/// Each phase lengthens the shortest augmenting path, which can span at most every
+/// point, so `on_progress` reports that length against the point count after each phase
+pub fn compute_max_flow(
+    node_count: usize,
+    edges: &[Edge],
+    capacities: &[f64],
+    source_idx: usize,
+    sink_idx: usize,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<MaxFlowResult, String> {
+    if capacities.len() != edges.len() {
+        return Err(format!(
+            "Expected {} capacities, one per edge, but got {}",
+            edges.len(),
+            capacities.len()
+        ));
+    }
+    if let Some(i) = capacities.iter().position(|c| !c.is_finite() || *c < 0.0) {
+        return Err(format!(
+            "Capacity of edge {} must be a non-negative number",
+            i
+        ));
+    }
+    check_edges(edges, node_count)?;
+    if source_idx >= node_count || sink_idx >= node_count {
+        return Err("Source or sink index is out of bounds".to_string());
+    }
+    if source_idx == sink_idx {
+        return Err("Source and sink must be different points".to_string());
+    }
+
+    let mut network = ResidualNetwork::new(node_count, edges, capacities);
+    let mut flow_value = 0.0;
+
+    loop {
+        let mut level = network.levels(source_idx);
+        let Some(path_length) = level[sink_idx] else {
+            break;
+        };
+        flow_value += network.blocking_flow(source_idx, sink_idx, &mut level);
+        on_progress(Progress::counted(
+            "augmenting paths",
+            path_length,
+            node_count - 1,
+        ));
+    }
+
+    // Whatever the source can still reach after max flow is the source side of the min cut
+    let reachable = network.levels(source_idx);
+    let source_side: Vec<usize> = (0..node_count)
+        .filter(|&v| reachable[v].is_some())
+        .collect();
+
+    let edge_flows = capacities
+        .iter()
+        .enumerate()
+        .map(|(i, capacity)| capacity - network.residual[2 * i])
+        .collect();
+
+    let min_cut = edges
+        .iter()
+        .enumerate()
+        .filter(|(_, edge)| reachable[edge.from].is_some() != reachable[edge.to].is_some())
+        .map(|(i, _)| i)
+        .collect();
+
+    Ok(MaxFlowResult {
+        flow_value,
+        edge_flows,
+        min_cut,
+        source_side,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_flow_limited_by_bottleneck() {
+        // Two parallel routes 0-1-3 and 0-2-3, each limited by one narrow edge
+        let edges = vec![
+            Edge { from: 0, to: 1 },
+            Edge { from: 1, to: 3 },
+            Edge { from: 0, to: 2 },
+            Edge { from: 2, to: 3 },
+        ];
+        let capacities = vec![3.0, 1.0, 2.0, 5.0];
+
+        let result = compute_max_flow(4, &edges, &capacities, 0, 3, |_| {}).unwrap();
+
+        assert!((result.flow_value - 3.0).abs() < 1e-10);
+        assert_eq!(result.min_cut, vec![1, 2]);
+        assert_eq!(result.source_side, vec![0, 1]);
+        assert!((result.edge_flows[3] - 2.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_max_flow_reports_progress_per_phase() {
+        // A direct edge saturates in the first phase, the two-hop route in the second
+        let edges = vec![
+            Edge { from: 0, to: 2 },
+            Edge { from: 0, to: 1 },
+            Edge { from: 1, to: 2 },
+        ];
+        let mut reports = Vec::new();
+
+        compute_max_flow(3, &edges, &[1.0, 1.0, 1.0], 0, 2, |p| reports.push(p)).unwrap();
+
+        let lengths: Vec<_> = reports.iter().map(|p| p.completed).collect();
+        assert_eq!(lengths, vec![Some(1), Some(2)]);
+        assert!((reports[1].fraction - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_max_flow_reports_reverse_direction() {
+        let edges = vec![Edge { from: 1, to: 0 }];
+        let result = compute_max_flow(2, &edges, &[4.0], 0, 1, |_| {}).unwrap();
+
+        assert!((result.flow_value - 4.0).abs() < 1e-10);
+        assert!((result.edge_flows[0] + 4.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_max_flow_rejects_mismatched_capacities() {
+        let edges = vec![Edge { from: 0, to: 1 }];
+        let result = compute_max_flow(2, &edges, &[], 0, 1, |_| {});
+        assert_eq!(
+            result.unwrap_err(),
+            "Expected 1 capacities, one per edge, but got 0"
+        );
+    }
+
+    #[test]
+    fn test_max_flow_rejects_edges_to_missing_points() {
+        let edges = vec![Edge { from: 0, to: 1 }, Edge { from: 1, to: 7 }];
+        let result = compute_max_flow(2, &edges, &[1.0, 1.0], 0, 1, |_| {});
+        assert_eq!(
+            result.unwrap_err(),
+            "Edge 1-7 refers to a point that does not exist"
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use crate::euclidean_distance;
+use crate::rng::SplitMix64;
+use shared_types::{Edge, GraphMetricsParams, GraphModel, Point};
+use std::collections::{HashMap, HashSet};
+
+/// Collects undirected edges, skipping repeats
+#[derive(Default)]
+struct EdgeSet {
+    seen: HashSet<(usize, usize)>,
+    edges: Vec<Edge>,
+}
+
+impl EdgeSet {
+    fn add(&mut self, a: usize, b: usize) {
+        if a != b && self.seen.insert((a.min(b), a.max(b))) {
+            self.edges.push(Edge { from: a, to: b });
+        }
+    }
+}
+
+fn random_points(rng: &mut SplitMix64, count: usize, width: f64, height: f64) -> Vec<Point> {
+    (0..count)
+        .map(|_| Point {
+            x: rng.next_f64() * width,
+            y: rng.next_f64() * height,
+            z: None,
+        })
+        .collect()
+}
+
+fn nearest_neighbours(points: &[Point], k: usize) -> Vec<Edge> {
+    let mut edges = EdgeSet::default();
+    let mut others: Vec<(f64, usize)> = Vec::with_capacity(points.len());
+    for (i, p) in points.iter().enumerate() {
+        others.clear();
+        others.extend(
+            points
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(j, q)| (euclidean_distance(p, q), j)),
+        );
+        if k < others.len() {
+            others.select_nth_unstable_by(k, |a, b| a.0.total_cmp(&b.0));
+        }
+        for &(_, j) in others.iter().take(k) {
+            edges.add(i, j);
+        }
+    }
+    edges.edges
+}
+
+fn grid(rows: usize, cols: usize, diagonals: bool, width: f64, height: f64) -> GraphMetricsParams {
+    let step = |extent: f64, n: usize| if n > 1 { extent / (n - 1) as f64 } else { 0.0 };
+    let (dx, dy) = (step(width, cols), step(height, rows));
+    let index = |r: usize, c: usize| r * cols + c;
+
+    let mut points = Vec::with_capacity(rows * cols);
+    let mut edges = Vec::new();
+    for r in 0..rows {
+        for c in 0..cols {
+            points.push(Point {
+                x: c as f64 * dx,
+                y: r as f64 * dy,
+                z: None,
+            });
+            if c + 1 < cols {
+                edges.push(Edge {
+                    from: index(r, c),
+                    to: index(r, c + 1),
+                });
+            }
+            if r + 1 < rows {
+                edges.push(Edge {
+                    from: index(r, c),
+                    to: index(r + 1, c),
+                });
+            }
+            if diagonals && r + 1 < rows && c + 1 < cols {
+                edges.push(Edge {
+                    from: index(r, c),
+                    to: index(r + 1, c + 1),
+                });
+                edges.push(Edge {
+                    from: index(r, c + 1),
+                    to: index(r + 1, c),
+                });
+            }
+        }
+    }
+    GraphMetricsParams { points, edges }
+}
+
+/// G(n, p) by geometric skipping (Batagelj & Brandes), linear in the edge count
+fn erdos_renyi(rng: &mut SplitMix64, count: usize, probability: f64) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    if probability <= 0.0 {
+        return edges;
+    }
+    if probability >= 1.0 {
+        for v in 1..count {
+            for w in 0..v {
+                edges.push(Edge { from: v, to: w });
+            }
+        }
+        return edges;
+    }
+
+    let log_q = (1.0 - probability).ln();
+    let (mut v, mut w): (usize, i64) = (1, -1);
+    while v < count {
+        let skip = ((1.0 - rng.next_f64()).ln() / log_q).floor();
+        w += 1 + skip.min(i64::MAX as f64 / 2.0) as i64;
+        while w >= v as i64 && v < count {
+            w -= v as i64;
+            v += 1;
+        }
+        if v < count {
+            edges.push(Edge {
+                from: v,
+                to: w as usize,
+            });
+        }
+    }
+    edges
+}
+
+/// Starts from a clique of `attachments + 1` points. Sampling uniformly from a
+/// list holding each point once per incident edge picks points by degree
+fn barabasi_albert(rng: &mut SplitMix64, count: usize, attachments: usize) -> Vec<Edge> {
+    let mut edges = EdgeSet::default();
+    let mut endpoints: Vec<usize> = Vec::new();
+    let seed_size = (attachments + 1).min(count);
+
+    for v in 1..seed_size {
+        for w in 0..v {
+            edges.add(v, w);
+            endpoints.extend([v, w]);
+        }
+    }
+
+    let mut targets = Vec::with_capacity(attachments);
+    for v in seed_size..count {
+        targets.clear();
+        while targets.len() < attachments {
+            let target = endpoints[rng.next_below(endpoints.len())];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for &target in &targets {
+            edges.add(v, target);
+            endpoints.extend([v, target]);
+        }
+    }
+    edges.edges
+}
+
+/// Buckets points into cells of side `radius` so only neighbouring cells are compared
+fn random_geometric(points: &[Point], radius: f64) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    if radius <= 0.0 {
+        return edges;
+    }
+    let cell = |p: &Point| ((p.x / radius).floor() as i64, (p.y / radius).floor() as i64);
+    let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, p) in points.iter().enumerate() {
+        cells.entry(cell(p)).or_default().push(i);
+    }
+
+    for (i, p) in points.iter().enumerate() {
+        let (cx, cy) = cell(p);
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for &j in cells.get(&(x, y)).into_iter().flatten() {
+                    if j > i && euclidean_distance(p, &points[j]) <= radius {
+                        edges.push(Edge { from: i, to: j });
+                    }
+                }
+            }
+        }
+    }
+    edges
+}
+
+fn validate(model: &GraphModel, width: f64, height: f64) -> Result<(), String> {
+    if !width.is_finite() || !height.is_finite() || width <= 0.0 || height <= 0.0 {
+        return Err("Width and height must be positive numbers".to_string());
+    }
+    match *model {
+        GraphModel::NearestNeighbours { count, k } if k >= count.max(1) => Err(format!(
+            "k must be less than the point count, got k = {} for {} points",
+            k, count
+        )),
+        GraphModel::ErdosRenyi { probability, .. } if !(0.0..=1.0).contains(&probability) => {
+            Err("Probability must be between 0 and 1".to_string())
+        }
+        GraphModel::BarabasiAlbert { count, attachments }
+            if attachments == 0 || attachments >= count =>
+        {
+            Err(format!(
+                "Attachments must be between 1 and the point count minus one, got {} for {} points",
+                attachments, count
+            ))
+        }
+        GraphModel::RandomGeometric { radius, .. } if !radius.is_finite() || radius < 0.0 => {
+            Err("Radius must be a non-negative number".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Core business logic: generate a random graph from a model and seed
+/// The same seed and parameters always give the same graph, on every platform
+pub fn generate_graph(
+    model: &GraphModel,
+    seed: u64,
+    width: f64,
+    height: f64,
+) -> Result<GraphMetricsParams, String> {
+    validate(model, width, height)?;
+    let mut rng = SplitMix64::new(seed);
+
+    let graph = match *model {
+        GraphModel::Grid {
+            rows,
+            cols,
+            diagonals,
+        } => grid(rows, cols, diagonals.unwrap_or(false), width, height),
+        GraphModel::NearestNeighbours { count, k } => {
+            let points = random_points(&mut rng, count, width, height);
+            let edges = nearest_neighbours(&points, k);
+            GraphMetricsParams { points, edges }
+        }
+        GraphModel::ErdosRenyi { count, probability } => {
+            let points = random_points(&mut rng, count, width, height);
+            let edges = erdos_renyi(&mut rng, count, probability);
+            GraphMetricsParams { points, edges }
+        }
+        GraphModel::BarabasiAlbert { count, attachments } => {
+            let points = random_points(&mut rng, count, width, height);
+            let edges = barabasi_albert(&mut rng, count, attachments);
+            GraphMetricsParams { points, edges }
+        }
+        GraphModel::RandomGeometric { count, radius } => {
+            let points = random_points(&mut rng, count, width, height);
+            let edges = random_geometric(&points, radius);
+            GraphMetricsParams { points, edges }
+        }
+    };
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    fn degrees(graph: &GraphMetricsParams) -> Vec<usize> {
+        let mut degree = vec![0; graph.points.len()];
+        for edge in &graph.edges {
+            degree[edge.from] += 1;
+            degree[edge.to] += 1;
+        }
+        degree
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_graph() {
+        let model = GraphModel::NearestNeighbours { count: 50, k: 3 };
+        let a = generate_graph(&model, 7, 600.0, 400.0).unwrap();
+        let b = generate_graph(&model, 7, 600.0, 400.0).unwrap();
+        let c = generate_graph(&model, 8, 600.0, 400.0).unwrap();
+
+        assert_eq!(json(&a.points), json(&b.points));
+        assert_eq!(json(&a.edges), json(&b.edges));
+        assert_ne!(json(&a.points), json(&c.points));
+        assert!(degrees(&a).iter().all(|&d| d >= 3));
+        assert!(a.points.iter().all(|p| p.x <= 600.0 && p.y <= 400.0));
+    }
+
+    #[test]
+    fn test_grid_shape() {
+        let model = GraphModel::Grid {
+            rows: 3,
+            cols: 4,
+            diagonals: Some(true),
+        };
+        let graph = generate_graph(&model, 0, 3.0, 2.0).unwrap();
+
+        assert_eq!(graph.points.len(), 12);
+        // 3 * 3 horizontal + 2 * 4 vertical + 2 * 6 diagonal
+        assert_eq!(graph.edges.len(), 29);
+        assert_eq!((graph.points[11].x, graph.points[11].y), (3.0, 2.0));
+    }
+
+    #[test]
+    fn test_erdos_renyi_extremes_and_density() {
+        let complete = GraphModel::ErdosRenyi {
+            count: 10,
+            probability: 1.0,
+        };
+        assert_eq!(
+            generate_graph(&complete, 1, 1.0, 1.0).unwrap().edges.len(),
+            45
+        );
+
+        let sparse = GraphModel::ErdosRenyi {
+            count: 400,
+            probability: 0.05,
+        };
+        let graph = generate_graph(&sparse, 1, 1.0, 1.0).unwrap();
+        let expected = 0.05 * (400.0 * 399.0 / 2.0);
+        let found = graph.edges.len() as f64;
+        assert!((found - expected).abs() < expected * 0.1, "{} edges", found);
+        let unique: HashSet<(usize, usize)> = graph.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(unique.len(), graph.edges.len());
+        assert!(graph.edges.iter().all(|e| e.to < e.from));
+    }
+
+    #[test]
+    fn test_barabasi_albert_and_geometric_edge_rules() {
+        let model = GraphModel::BarabasiAlbert {
+            count: 100,
+            attachments: 2,
+        };
+        let graph = generate_graph(&model, 3, 1.0, 1.0).unwrap();
+        // A triangle to start with, then two edges per new point
+        assert_eq!(graph.edges.len(), 3 + 97 * 2);
+
+        let model = GraphModel::RandomGeometric {
+            count: 200,
+            radius: 0.1,
+        };
+        let graph = generate_graph(&model, 3, 1.0, 1.0).unwrap();
+        let brute_force = (0..200)
+            .flat_map(|i| (i + 1..200).map(move |j| (i, j)))
+            .filter(|&(i, j)| euclidean_distance(&graph.points[i], &graph.points[j]) <= 0.1)
+            .count();
+        assert_eq!(graph.edges.len(), brute_force);
+    }
+}
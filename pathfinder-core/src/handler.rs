@@ -1,19 +1,128 @@
-use crate::compute_shortest_path;
+use crate::contraction::ContractionHierarchy;
+use crate::{compute_route, graph_fingerprint, EditableGraph, PathConstraints};
 use shared_types::context::Context;
 use shared_types::router::{CallHandler, ObserverImpl};
 use shared_types::storage::Storage;
-use shared_types::{GraphMetrics, GraphMetricsParams, PathResult, ShortestPathParams};
-use std::sync::Arc;
+use shared_types::{
+    CentralityParams, CentralityResult, EditGraphParams, ExportGraphParams, ExportedGraph,
+    GenerateGraphParams, GraphEditResult, GraphMetrics, GraphMetricsParams, GraphVersion,
+    HierarchyInfo, ImportGraphParams, ImportedGraph, LoadGraphParams, MaxFlowParams, MaxFlowResult,
+    ParetoPath, ParetoPathParams, PathResult, PrepareGraphParams, PreparedPathParams,
+    PreparedPathResult, ShortestPathParams, StoredPathParams, StoredPathResult, TourParams,
+    TourResult,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 /// PathfinderHandler implements the CallHandler trait
 /// This is the transport-agnostic business logic handler
 pub struct PathfinderHandler<S: Storage> {
     storage: Option<Arc<S>>,
+    /// Decoded hierarchies by storage key, so repeated queries skip deserializing;
+    /// an entry is only used while its fingerprint matches the stored one
+    hierarchies: Mutex<HashMap<String, Arc<ContractionHierarchy>>>,
+    /// Decoded editable graphs by storage key; storage is only written when a
+    /// graph changes, not when a query adds to its path cache
+    graphs: Mutex<HashMap<String, Arc<Mutex<EditableGraph>>>>,
 }
 
 impl<S: Storage> PathfinderHandler<S> {
     pub fn new(storage: Option<Arc<S>>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            hierarchies: Mutex::new(HashMap::new()),
+            graphs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn session_storage(&self) -> Result<&Arc<S>, String> {
+        self.storage
+            .as_ref()
+            .ok_or_else(|| "Stored graphs need a handler with session storage".to_string())
+    }
+
+    fn load_stored_graph(&self, ctx: &Context) -> Result<Arc<Mutex<EditableGraph>>, String> {
+        let key = graph_key(ctx);
+        let mut graphs = self.graphs.lock().map_err(|e| e.to_string())?;
+        if let Some(graph) = graphs.get(&key) {
+            return Ok(Arc::clone(graph));
+        }
+        let bytes = self
+            .session_storage()?
+            .get(&key)
+            .ok_or_else(|| "No graph stored for this session, call load_graph first".to_string())?;
+        let graph: EditableGraph = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Stored graph is unreadable: {}", e))?;
+        let graph = Arc::new(Mutex::new(graph));
+        graphs.insert(key, Arc::clone(&graph));
+        Ok(graph)
+    }
+
+    fn save_stored_graph(&self, ctx: &Context, graph: &EditableGraph) -> Result<(), String> {
+        let bytes = serde_json::to_vec(graph).map_err(|e| e.to_string())?;
+        self.session_storage()?.set(&graph_key(ctx), bytes);
+        Ok(())
+    }
+
+    fn load_hierarchy(&self, ctx: &Context) -> Result<Arc<ContractionHierarchy>, String> {
+        let storage = self.session_storage()?;
+        let missing = || "No prepared graph for this session, call prepare_graph first".to_string();
+        let fingerprint = storage
+            .get(&hierarchy_fingerprint_key(ctx))
+            .and_then(|bytes| Some(u64::from_le_bytes(bytes.try_into().ok()?)))
+            .ok_or_else(missing)?;
+
+        let key = hierarchy_key(ctx);
+        let mut hierarchies = self.hierarchies.lock().map_err(|e| e.to_string())?;
+        if let Some(hierarchy) = hierarchies.get(&key) {
+            if hierarchy.fingerprint() == fingerprint {
+                return Ok(Arc::clone(hierarchy));
+            }
+        }
+        let bytes = storage.get(&key).ok_or_else(missing)?;
+        let hierarchy: ContractionHierarchy = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Stored hierarchy is unreadable: {}", e))?;
+        let hierarchy = Arc::new(hierarchy);
+        hierarchies.insert(key, Arc::clone(&hierarchy));
+        Ok(hierarchy)
+    }
+
+    fn save_hierarchy(&self, ctx: &Context, hierarchy: ContractionHierarchy) -> Result<(), String> {
+        let storage = self.session_storage()?;
+        let bytes = serde_json::to_vec(&hierarchy).map_err(|e| e.to_string())?;
+        storage.set(&hierarchy_key(ctx), bytes);
+        storage.set(
+            &hierarchy_fingerprint_key(ctx),
+            hierarchy.fingerprint().to_le_bytes().to_vec(),
+        );
+        self.hierarchies
+            .lock()
+            .map_err(|e| e.to_string())?
+            .insert(hierarchy_key(ctx), Arc::new(hierarchy));
+        Ok(())
+    }
+}
+
+/// Storage key of the session's editable graph
+fn graph_key(ctx: &Context) -> String {
+    format!("graph:{}", ctx.session_id)
+}
+
+/// Storage key of the session's contraction hierarchy
+fn hierarchy_key(ctx: &Context) -> String {
+    format!("hierarchy:{}", ctx.session_id)
+}
+
+/// Storage key of the fingerprint of the session's hierarchy, cheap to read per query
+fn hierarchy_fingerprint_key(ctx: &Context) -> String {
+    format!("hierarchy-fingerprint:{}", ctx.session_id)
+}
+
+fn graph_version(graph: &EditableGraph) -> GraphVersion {
+    GraphVersion {
+        version: graph.version(),
+        point_count: graph.point_count(),
+        edge_count: graph.edge_count(),
     }
 }
 
@@ -24,12 +133,26 @@ impl<S: Storage> CallHandler for PathfinderHandler<S> {
         params: ShortestPathParams,
         tx: ObserverImpl<PathResult>,
     ) {
+        // Cache keys only encode the graph, endpoints and search mode, so only plain
+        // queries are cached; the modes may break ties differently, so each has its own
+        let plain = PathConstraints::from_params(&params).is_empty()
+            && params.turn_penalty.is_none()
+            && params.banned_turns.is_none()
+            && params.post_processing.is_none()
+            && params.slope_penalty.is_none_or(|p| p == 0.0);
+        let cache = self.storage.as_ref().filter(|_| plain);
+        let mode = if params.bidirectional == Some(true) { "bidirectional" } else { "dijkstra" };
+        let cache_key = format!(
+            "path:{}:{:016x}:{}:{}:{}",
+            ctx.session_id,
+            graph_fingerprint(&params.points, &params.edges),
+            params.start_idx,
+            params.end_idx,
+            mode
+        );
+
         // Optional: Check cache in storage
-        if let Some(storage) = &self.storage {
-            let cache_key = format!(
-                "path:{}:{}:{}",
-                ctx.session_id, params.start_idx, params.end_idx
-            );
+        if let Some(storage) = cache {
             if let Some(cached_bytes) = storage.get(&cache_key) {
                 // Try to deserialize cached result
                 if let Ok(cached_result) = serde_json::from_slice::<PathResult>(&cached_bytes) {
@@ -41,15 +164,10 @@ impl<S: Storage> CallHandler for PathfinderHandler<S> {
         }
 
         // Compute the shortest path using core logic
-        match compute_shortest_path(&params.points, &params.edges, params.start_idx, params.end_idx)
-        {
+        match compute_route(&params) {
             Ok(result) => {
                 // Optional: Cache the result
-                if let Some(storage) = &self.storage {
-                    let cache_key = format!(
-                        "path:{}:{}:{}",
-                        ctx.session_id, params.start_idx, params.end_idx
-                    );
+                if let Some(storage) = cache {
                     if let Ok(serialized) = serde_json::to_vec(&result) {
                         storage.set(&cache_key, serialized);
                     }
@@ -95,6 +213,307 @@ impl<S: Storage> CallHandler for PathfinderHandler<S> {
         tx.next(metrics);
         tx.complete("Metrics computed successfully".to_string());
     }
+
+    fn solve_tour(&self, ctx: &Context, params: TourParams, tx: ObserverImpl<TourResult>) {
+        // Stream every improved tour so clients can display progress and stop early
+        match crate::tour::solve_tour(
+            &params.points,
+            &params.edges,
+            &params.stops,
+            params.or_opt,
+            |progress| tx.progress(progress),
+            |tour| tx.next(tour),
+            || ctx.is_cancelled(),
+        ) {
+            // The abort was already answered with `Aborted`
+            Ok(_) if ctx.is_cancelled() => {}
+            Ok(best) => {
+                tx.complete(format!("Tour found after {} improvements", best.iteration));
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn compute_centrality(
+        &self,
+        _ctx: &Context,
+        params: CentralityParams,
+        tx: ObserverImpl<CentralityResult>,
+    ) {
+        match crate::centrality::compute_centrality(
+            &params.points,
+            &params.edges,
+            params.sample_size,
+            params.seed.unwrap_or(0),
+            |progress| tx.progress(progress),
+        ) {
+            Ok(result) => {
+                let notes = format!("Centrality computed from {} sources", result.sampled_sources);
+                tx.next(result);
+                tx.complete(notes);
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn compute_max_flow(
+        &self,
+        _ctx: &Context,
+        params: MaxFlowParams,
+        tx: ObserverImpl<MaxFlowResult>,
+    ) {
+        match crate::flow::compute_max_flow(
+            params.points.len(),
+            &params.edges,
+            &params.capacities,
+            params.source_idx,
+            params.sink_idx,
+            |progress| tx.progress(progress),
+        ) {
+            Ok(result) => {
+                tx.next(result);
+                tx.complete("Max flow computed successfully".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn find_pareto_paths(
+        &self,
+        ctx: &Context,
+        params: ParetoPathParams,
+        tx: ObserverImpl<ParetoPath>,
+    ) {
+        // Each path is final as soon as it is found, so stream it right away
+        match crate::pareto::find_pareto_paths(
+            &params.points,
+            &params.edges,
+            &params.secondary_costs,
+            params.start_idx,
+            params.end_idx,
+            params.max_paths,
+            |progress| tx.progress(progress),
+            |path| tx.next(path),
+            || ctx.is_cancelled(),
+        ) {
+            // The abort was already answered with `Aborted`
+            Ok(_) if ctx.is_cancelled() => {}
+            Ok(found) => {
+                tx.complete(format!("Found {} Pareto-optimal paths", found));
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn load_graph(&self, ctx: &Context, params: LoadGraphParams, tx: ObserverImpl<GraphVersion>) {
+        let loaded = EditableGraph::new(params.points, params.edges, params.weights)
+            .and_then(|graph| {
+                self.save_stored_graph(ctx, &graph)?;
+                let version = graph_version(&graph);
+                self.graphs
+                    .lock()
+                    .map_err(|e| e.to_string())?
+                    .insert(graph_key(ctx), Arc::new(Mutex::new(graph)));
+                Ok(version)
+            });
+        match loaded {
+            Ok(version) => {
+                tx.next(version);
+                tx.complete("Graph stored".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn edit_graph(
+        &self,
+        ctx: &Context,
+        params: EditGraphParams,
+        tx: ObserverImpl<GraphEditResult>,
+    ) {
+        let edited = self.load_stored_graph(ctx).and_then(|graph| {
+            let mut graph = graph.lock().map_err(|e| e.to_string())?;
+            let invalidated = graph.apply_edits(&params.edits, ctx.limits())?;
+            self.save_stored_graph(ctx, &graph)?;
+            Ok(GraphEditResult {
+                version: graph.version(),
+                invalidated,
+                retained: graph.cached_paths(),
+            })
+        });
+        match edited {
+            Ok(result) => {
+                let notes = format!(
+                    "Applied {} edits, invalidated {} cached paths",
+                    params.edits.len(),
+                    result.invalidated.len()
+                );
+                tx.next(result);
+                tx.complete(notes);
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn find_stored_path(
+        &self,
+        ctx: &Context,
+        params: StoredPathParams,
+        tx: ObserverImpl<StoredPathResult>,
+    ) {
+        let found = self.load_stored_graph(ctx).and_then(|graph| {
+            let mut graph = graph.lock().map_err(|e| e.to_string())?;
+            let (path, cached) = graph.shortest_path(params.start_idx, params.end_idx)?;
+            Ok(StoredPathResult {
+                path: path.path,
+                distance: path.distance,
+                version: graph.version(),
+                cached,
+            })
+        });
+        match found {
+            Ok(result) => {
+                tx.next(result);
+                tx.complete("Path found successfully".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn prepare_graph(
+        &self,
+        ctx: &Context,
+        params: PrepareGraphParams,
+        tx: ObserverImpl<HierarchyInfo>,
+    ) {
+        let prepared = ContractionHierarchy::build(&params.points, &params.edges).and_then(|h| {
+            let info = HierarchyInfo {
+                point_count: h.point_count(),
+                edge_count: h.edge_count(),
+                shortcut_count: h.shortcut_count(),
+            };
+            self.save_hierarchy(ctx, h).map(|_| info)
+        });
+        match prepared {
+            Ok(info) => {
+                tx.next(info);
+                tx.complete("Graph prepared".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn find_prepared_path(
+        &self,
+        ctx: &Context,
+        params: PreparedPathParams,
+        tx: ObserverImpl<PreparedPathResult>,
+    ) {
+        let found = self
+            .load_hierarchy(ctx)
+            .and_then(|hierarchy| hierarchy.shortest_path(params.start_idx, params.end_idx));
+        match found {
+            Ok(result) => {
+                tx.next(PreparedPathResult {
+                    path: result.path,
+                    distance: result.distance,
+                });
+                tx.complete("Path found successfully".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn import_graph(
+        &self,
+        ctx: &Context,
+        params: ImportGraphParams,
+        tx: ObserverImpl<ImportedGraph>,
+    ) {
+        match crate::import::import_graph(
+            &params.source,
+            params.tolerance.unwrap_or(crate::import::DEFAULT_TOLERANCE),
+            ctx.limits(),
+        ) {
+            Ok(graph) => {
+                let notes = format!(
+                    "Imported {} points and {} edges",
+                    graph.points.len(),
+                    graph.edges.len()
+                );
+                tx.next(graph);
+                tx.complete(notes);
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn export_graph(
+        &self,
+        _ctx: &Context,
+        params: ExportGraphParams,
+        tx: ObserverImpl<ExportedGraph>,
+    ) {
+        match crate::export::export_graph(&params.graph, params.path.as_ref(), &params.format) {
+            Ok(text) => {
+                tx.next(ExportedGraph {
+                    text,
+                    content_type: crate::export::content_type(&params.format).to_string(),
+                });
+                tx.complete("Graph exported successfully".to_string());
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
+
+    fn generate_graph(
+        &self,
+        _ctx: &Context,
+        params: GenerateGraphParams,
+        tx: ObserverImpl<GraphMetricsParams>,
+    ) {
+        match crate::generate::generate_graph(
+            &params.model,
+            params.seed.unwrap_or(0),
+            params.width.unwrap_or(1.0),
+            params.height.unwrap_or(1.0),
+        ) {
+            Ok(graph) => {
+                let notes = format!(
+                    "Generated {} points and {} edges",
+                    graph.points.len(),
+                    graph.edges.len()
+                );
+                tx.next(graph);
+                tx.complete(notes);
+            }
+            Err(error) => {
+                tx.error(error);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -109,8 +528,9 @@ mod tests {
     }
 
     impl shared_types::router::WireResponseSender for MockSender {
-        fn send_response(&self, _wire_response: shared_types::router::WireResponse) {
-            // For testing, we could capture responses here
+        fn send_response(&self, wire_response: shared_types::router::WireResponse) {
+            let json = serde_json::to_string(&wire_response).unwrap();
+            self.responses.lock().unwrap().push(json);
         }
     }
 
@@ -119,9 +539,9 @@ mod tests {
         let handler: PathfinderHandler<InMemoryStorage> = PathfinderHandler::new(None);
 
         let points = vec![
-            Point { x: 0.0, y: 0.0 },
-            Point { x: 1.0, y: 0.0 },
-            Point { x: 0.0, y: 1.0 },
+            Point { x: 0.0, y: 0.0, z: None },
+            Point { x: 1.0, y: 0.0, z: None },
+            Point { x: 0.0, y: 1.0, z: None },
         ];
 
         let edges = vec![
@@ -135,6 +555,15 @@ mod tests {
             edges,
             start_idx: 0,
             end_idx: 2,
+            forbidden_nodes: None,
+            forbidden_edges: None,
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: None,
+            bidirectional: None,
+            post_processing: None,
+            slope_penalty: None,
+            max_grade: None,
         };
 
         let ctx = Context::new("test-session".to_string(), 1);
@@ -148,4 +577,294 @@ mod tests {
         // This should not panic
         handler.find_shortest_path(&ctx, params, tx);
     }
+
+    #[test]
+    fn test_handler_does_not_cache_slope_penalised_paths() {
+        let handler = PathfinderHandler::new(Some(Arc::new(InMemoryStorage::new())));
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = || {
+            Box::new(MockSender {
+                responses: Arc::clone(&responses),
+            })
+        };
+        // Straight over a hill (0-1-3) or the long way round the flat (0-2-3)
+        let hill = |slope_penalty| ShortestPathParams {
+            points: vec![
+                Point { x: 0.0, y: 0.0, z: Some(0.0) },
+                Point { x: 1.0, y: 0.0, z: Some(0.5) },
+                Point { x: 1.0, y: 1.0, z: Some(0.0) },
+                Point { x: 2.0, y: 0.0, z: Some(0.0) },
+            ],
+            edges: vec![
+                Edge { from: 0, to: 1 },
+                Edge { from: 1, to: 3 },
+                Edge { from: 0, to: 2 },
+                Edge { from: 2, to: 3 },
+            ],
+            start_idx: 0,
+            end_idx: 3,
+            forbidden_nodes: None,
+            forbidden_edges: None,
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: None,
+            bidirectional: None,
+            post_processing: None,
+            slope_penalty,
+            max_grade: None,
+        };
+
+        handler.find_shortest_path(&ctx, hill(Some(1.0)), ObserverImpl::new(1, sender()));
+        handler.find_shortest_path(&ctx, hill(None), ObserverImpl::new(2, sender()));
+
+        let responses = responses.lock().unwrap();
+        assert!(responses[0].contains("\"path\":[0,2,3]"));
+        assert!(responses[2].contains("\"path\":[0,1,3]"));
+    }
+
+    #[test]
+    fn test_handler_caches_each_search_mode_separately() {
+        let handler = PathfinderHandler::new(Some(Arc::new(InMemoryStorage::new())));
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = || {
+            Box::new(MockSender {
+                responses: Arc::clone(&responses),
+            })
+        };
+        // Two equally short routes round a square, which the modes may pick differently
+        let square = |bidirectional| ShortestPathParams {
+            points: vec![
+                Point { x: 0.0, y: 0.0, z: None },
+                Point { x: 1.0, y: 0.0, z: None },
+                Point { x: 0.0, y: 1.0, z: None },
+                Point { x: 1.0, y: 1.0, z: None },
+            ],
+            edges: vec![
+                Edge { from: 0, to: 1 },
+                Edge { from: 1, to: 3 },
+                Edge { from: 0, to: 2 },
+                Edge { from: 2, to: 3 },
+            ],
+            start_idx: 0,
+            end_idx: 3,
+            forbidden_nodes: None,
+            forbidden_edges: None,
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: None,
+            bidirectional,
+            post_processing: None,
+            slope_penalty: None,
+            max_grade: None,
+        };
+
+        handler.find_shortest_path(&ctx, square(None), ObserverImpl::new(1, sender()));
+        handler.find_shortest_path(&ctx, square(Some(true)), ObserverImpl::new(2, sender()));
+        handler.find_shortest_path(&ctx, square(Some(false)), ObserverImpl::new(3, sender()));
+
+        let responses = responses.lock().unwrap();
+        assert!(!responses[3].contains("(cached)"));
+        assert!(responses[5].contains("(cached)"));
+    }
+
+    #[test]
+    fn test_handler_solve_tour_streams_then_completes() {
+        let handler: PathfinderHandler<InMemoryStorage> = PathfinderHandler::new(None);
+
+        let params = TourParams {
+            points: vec![
+                Point { x: 0.0, y: 0.0, z: None },
+                Point { x: 1.0, y: 0.0, z: None },
+                Point { x: 2.0, y: 0.0, z: None },
+            ],
+            edges: vec![Edge { from: 0, to: 1 }, Edge { from: 1, to: 2 }],
+            stops: vec![0, 2],
+            or_opt: true,
+        };
+
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = Box::new(MockSender {
+            responses: Arc::clone(&responses),
+        });
+
+        handler.solve_tour(&ctx, params, ObserverImpl::new(1, sender));
+
+        let responses = responses.lock().unwrap();
+        assert!(responses[0].contains("Progress"));
+        assert!(responses.iter().any(|r| r.contains("solve_tour")));
+        assert!(responses.last().unwrap().contains("Complete"));
+    }
+
+    #[test]
+    fn test_handler_solve_tour_stops_after_abort() {
+        let handler: PathfinderHandler<InMemoryStorage> = PathfinderHandler::new(None);
+
+        let params = TourParams {
+            points: vec![
+                Point { x: 0.0, y: 0.0, z: None },
+                Point { x: 1.0, y: 0.0, z: None },
+                Point { x: 1.0, y: 1.0, z: None },
+                Point { x: 0.0, y: 1.0, z: None },
+            ],
+            edges: vec![
+                Edge { from: 0, to: 1 },
+                Edge { from: 1, to: 2 },
+                Edge { from: 2, to: 3 },
+                Edge { from: 3, to: 0 },
+            ],
+            stops: vec![0, 2, 1, 3],
+            or_opt: true,
+        };
+
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let ctx = Context::new("test-session".to_string(), 1).with_cancellation(cancelled);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = Box::new(MockSender {
+            responses: Arc::clone(&responses),
+        });
+
+        handler.solve_tour(&ctx, params, ObserverImpl::new(1, sender));
+
+        // Only the starting tour goes out, and the Aborted frame ends the call
+        let responses = responses.lock().unwrap();
+        assert_eq!(responses.iter().filter(|r| r.contains("solve_tour")).count(), 1);
+        assert!(!responses.iter().any(|r| r.contains("Complete")));
+    }
+
+    #[test]
+    fn test_handler_stored_graph_round_trip() {
+        let handler = PathfinderHandler::new(Some(Arc::new(InMemoryStorage::new())));
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = || {
+            Box::new(MockSender {
+                responses: Arc::clone(&responses),
+            })
+        };
+
+        handler.load_graph(
+            &ctx,
+            LoadGraphParams {
+                points: vec![
+                    Point { x: 0.0, y: 0.0, z: None },
+                    Point { x: 1.0, y: 0.0, z: None },
+                    Point { x: 2.0, y: 0.0, z: None },
+                ],
+                edges: vec![Edge { from: 0, to: 1 }, Edge { from: 1, to: 2 }],
+                weights: None,
+            },
+            ObserverImpl::new(1, sender()),
+        );
+        let query = || StoredPathParams {
+            start_idx: 0,
+            end_idx: 2,
+        };
+        handler.find_stored_path(&ctx, query(), ObserverImpl::new(1, sender()));
+        handler.find_stored_path(&ctx, query(), ObserverImpl::new(1, sender()));
+        handler.edit_graph(
+            &ctx,
+            EditGraphParams {
+                edits: vec![shared_types::GraphEdit::RemoveEdge { index: 1 }],
+            },
+            ObserverImpl::new(1, sender()),
+        );
+        handler.find_stored_path(&ctx, query(), ObserverImpl::new(1, sender()));
+
+        let responses = responses.lock().unwrap();
+        assert!(responses[2].contains("\"cached\":false"));
+        assert!(responses[4].contains("\"cached\":true"));
+        assert!(responses[6].contains("\"invalidated\":[{\"start_idx\":0,\"end_idx\":2}]"));
+        assert!(responses[8].contains("No path found"));
+    }
+
+    #[test]
+    fn test_handler_keeps_stored_graph_in_memory() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let handler = PathfinderHandler::new(Some(Arc::clone(&storage)));
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = || {
+            Box::new(MockSender {
+                responses: Arc::clone(&responses),
+            })
+        };
+
+        handler.load_graph(
+            &ctx,
+            LoadGraphParams {
+                points: vec![
+                    Point { x: 0.0, y: 0.0, z: None },
+                    Point { x: 1.0, y: 0.0, z: None },
+                ],
+                edges: vec![Edge { from: 0, to: 1 }],
+                weights: None,
+            },
+            ObserverImpl::new(1, sender()),
+        );
+        let stored = storage.get(&graph_key(&ctx)).unwrap();
+        let query = || StoredPathParams {
+            start_idx: 0,
+            end_idx: 1,
+        };
+        handler.find_stored_path(&ctx, query(), ObserverImpl::new(1, sender()));
+        // Caching a path is not an edit, so storage is left alone
+        assert_eq!(storage.get(&graph_key(&ctx)).unwrap(), stored);
+        handler.find_stored_path(&ctx, query(), ObserverImpl::new(1, sender()));
+
+        // A second handler over the same storage decodes the stored graph
+        PathfinderHandler::new(Some(storage)).find_stored_path(
+            &ctx,
+            query(),
+            ObserverImpl::new(1, sender()),
+        );
+
+        let responses = responses.lock().unwrap();
+        assert!(responses[2].contains("\"cached\":false"));
+        assert!(responses[4].contains("\"cached\":true"));
+        assert!(responses[6].contains("\"cached\":false"));
+    }
+
+    #[test]
+    fn test_handler_prepared_graph_matches_plain_query() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let ctx = Context::new("test-session".to_string(), 1);
+        let responses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sender = || {
+            Box::new(MockSender {
+                responses: Arc::clone(&responses),
+            })
+        };
+        let model = shared_types::GraphModel::NearestNeighbours { count: 100, k: 3 };
+        let graph = crate::generate::generate_graph(&model, 5, 10.0, 10.0).unwrap();
+
+        let handler = PathfinderHandler::new(Some(Arc::clone(&storage)));
+        let query = || PreparedPathParams {
+            start_idx: 4,
+            end_idx: 90,
+        };
+        handler.find_prepared_path(&ctx, query(), ObserverImpl::new(1, sender()));
+        handler.prepare_graph(
+            &ctx,
+            PrepareGraphParams {
+                points: graph.points.clone(),
+                edges: graph.edges.clone(),
+            },
+            ObserverImpl::new(1, sender()),
+        );
+        // A second handler over the same storage decodes the stored hierarchy
+        PathfinderHandler::new(Some(storage)).find_prepared_path(
+            &ctx,
+            query(),
+            ObserverImpl::new(1, sender()),
+        );
+
+        let expected = crate::compute_shortest_path(&graph.points, &graph.edges, 4, 90).unwrap();
+        let responses = responses.lock().unwrap();
+        assert!(responses[0].contains("call prepare_graph first"));
+        assert!(responses[1].contains("\"point_count\":100"));
+        assert!(responses[3].contains(&serde_json::to_string(&expected.path).unwrap()));
+    }
 }
@@ -0,0 +1,152 @@
+use super::{parse_coordinate, GraphBuilder};
+use shared_types::{ImportedGraph, Point};
+use std::collections::HashMap;
+
+/// Splits one CSV record, honouring double quotes and `""` escapes
+fn split_record(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields.into_iter().map(|f| f.trim().to_string()).collect())
+}
+
+/// Non-empty, non-comment records with their 1-based line numbers
+/// `file` ("Nodes" or "Edges") prefixes error messages
+fn records(text: &str, file: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            split_record(line)
+                .map(|fields| (number, fields))
+                .map_err(|e| format!("{} line {}: {}", file, number, e))
+        })
+        .collect()
+}
+
+/// Pure function to parse a CSV node list (`id,x,y`) and edge list (`from,to`)
+/// Header rows are skipped when their coordinates are not numbers; extra
+/// columns are ignored. Errors name the file ("Nodes" or "Edges") and line
+pub fn parse_csv(nodes: &str, edges: &str, tolerance: f64) -> Result<ImportedGraph, String> {
+    let mut builder = GraphBuilder::new(tolerance);
+    let mut ids: HashMap<String, usize> = HashMap::new();
+
+    for (position, (line, fields)) in records(nodes, "Nodes")?.into_iter().enumerate() {
+        if fields.len() < 3 {
+            return Err(format!(
+                "Nodes line {}: expected id,x,y but found {} columns",
+                line,
+                fields.len()
+            ));
+        }
+        let is_header = position == 0 && fields[1].parse::<f64>().is_err();
+        if is_header {
+            continue;
+        }
+        let coordinate = |text: &str, what: &str| {
+            parse_coordinate(text, what).map_err(|e| format!("Nodes line {}: {}", line, e))
+        };
+        let x = coordinate(&fields[1], "x")?;
+        let y = coordinate(&fields[2], "y")?;
+        if ids.contains_key(&fields[0]) {
+            return Err(format!(
+                "Nodes line {}: node id '{}' is defined twice",
+                line, fields[0]
+            ));
+        }
+        let index = builder.add_point(Point { x, y, z: None }, &fields[0]);
+        ids.insert(fields[0].clone(), index);
+    }
+
+    for (position, (line, fields)) in records(edges, "Edges")?.into_iter().enumerate() {
+        if fields.len() < 2 {
+            return Err(format!(
+                "Edges line {}: expected from,to but found {} columns",
+                line,
+                fields.len()
+            ));
+        }
+        let lookup = |id: &str| ids.get(id).copied();
+        match (lookup(&fields[0]), lookup(&fields[1])) {
+            (Some(from), Some(to)) => builder.add_edge(from, to),
+            // A first row naming no known nodes is taken to be the header
+            (None, None) if position == 0 => continue,
+            (from, _) => {
+                let missing = if from.is_none() {
+                    &fields[0]
+                } else {
+                    &fields[1]
+                };
+                return Err(format!(
+                    "Edges line {}: unknown node id '{}'",
+                    line, missing
+                ));
+            }
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_with_headers_and_quotes() {
+        let nodes = "id,x,y\n\"a, the first\",0,0\nb,1,0\n# comment\n\nc,1,1\n";
+        let edges = "source,target,weight\n\"a, the first\",b,3\nb,c,1\n";
+
+        let graph = parse_csv(nodes, edges, 0.0).unwrap();
+
+        assert_eq!(graph.points.len(), 3);
+        assert_eq!(graph.names[0], "a, the first");
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!((graph.edges[1].from, graph.edges[1].to), (1, 2));
+    }
+
+    #[test]
+    fn test_parse_csv_reports_line_numbers() {
+        let nodes = "a,0,0\nb,1,zero\n";
+        assert_eq!(
+            parse_csv(nodes, "", 0.0).unwrap_err(),
+            "Nodes line 2: y 'zero' is not a number"
+        );
+
+        let nodes = "a,0,0\nb,1,0\n";
+        assert_eq!(
+            parse_csv(nodes, "a,b\n\nb,q\n", 0.0).unwrap_err(),
+            "Edges line 3: unknown node id 'q'"
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_with_huge_coordinates() {
+        let nodes = "a,1e300,0\nb,-1e300,0\nc,1e300,1e300\nd,1e300,0\n";
+        let graph = parse_csv(nodes, "a,b\nb,c\n", 1e-9).unwrap();
+        assert_eq!(graph.points.len(), 3);
+        assert_eq!(graph.merged_points, 1);
+    }
+}
@@ -0,0 +1,541 @@
+use super::{parse_coordinate, GraphBuilder};
+use shared_types::router::CallLimits;
+use shared_types::{ImportedGraph, Point};
+use std::collections::HashMap;
+
+/// Subgraphs nested deeper than this are refused; each level is a recursive call
+const MAX_SUBGRAPH_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Identifier, number, quoted string or HTML string; `quoted` ones are never keywords
+    Id {
+        text: String,
+        quoted: bool,
+    },
+    Symbol(char),
+    EdgeOp,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    /// True when only whitespace has been seen on the current line
+    line_start: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            line_start: true,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = true;
+        } else if !c.is_whitespace() {
+            self.line_start = false;
+        }
+        Some(c)
+    }
+
+    fn skip_line(&mut self) {
+        while self.chars.peek().is_some_and(|&c| c != '\n') {
+            self.bump();
+        }
+    }
+
+    fn tokens(mut self) -> Result<Vec<(Token, usize)>, String> {
+        let mut tokens = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            let line = self.line;
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '#' && self.line_start {
+                // C preprocessor output lines are ignored
+                self.skip_line();
+            } else if c == '/' {
+                self.bump();
+                match self.bump() {
+                    Some('/') => self.skip_line(),
+                    Some('*') => self.skip_block_comment(line)?,
+                    _ => return Err(format!("Line {}: unexpected '/'", line)),
+                }
+            } else if c == '-' && self.eat_edge_op() {
+                tokens.push((Token::EdgeOp, line));
+            } else if c == '"' {
+                let text = self.quoted(line)?;
+                tokens.push((Token::Id { text, quoted: true }, line));
+            } else if c == '<' {
+                let text = self.html(line)?;
+                tokens.push((Token::Id { text, quoted: true }, line));
+            } else if "{}[];,=:".contains(c) {
+                self.bump();
+                tokens.push((Token::Symbol(c), line));
+            } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+                let mut text = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '.' || (c == '-' && text.is_empty())
+                    {
+                        text.push(c);
+                        self.bump();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((
+                    Token::Id {
+                        text,
+                        quoted: false,
+                    },
+                    line,
+                ));
+            } else {
+                return Err(format!("Line {}: unexpected character '{}'", line, c));
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Consumes `--` or `->` if that is what comes next
+    fn eat_edge_op(&mut self) -> bool {
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        if matches!(ahead.next(), Some('-') | Some('>')) {
+            self.bump();
+            self.bump();
+            return true;
+        }
+        false
+    }
+
+    fn skip_block_comment(&mut self, line: usize) -> Result<(), String> {
+        let mut previous = ' ';
+        while let Some(c) = self.bump() {
+            if previous == '*' && c == '/' {
+                return Ok(());
+            }
+            previous = c;
+        }
+        Err(format!("Line {}: unterminated comment", line))
+    }
+
+    fn quoted(&mut self, line: usize) -> Result<String, String> {
+        self.bump();
+        let mut text = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '"' => return Ok(text),
+                '\\' => match self.bump() {
+                    Some('"') => text.push('"'),
+                    // Line continuation
+                    Some('\n') => {}
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => break,
+                },
+                _ => text.push(c),
+            }
+        }
+        Err(format!("Line {}: unterminated string", line))
+    }
+
+    fn html(&mut self, line: usize) -> Result<String, String> {
+        self.bump();
+        let mut depth = 1;
+        let mut text = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '<' => depth += 1,
+                '>' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(text);
+                    }
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+        Err(format!("Line {}: unterminated HTML string", line))
+    }
+}
+
+/// A node as declared in the file, before positions are resolved
+struct DotNode {
+    line: usize,
+    pos: Option<(String, usize)>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    nodes: HashMap<String, usize>,
+    declared: Vec<(String, DotNode)>,
+    /// Every node reference in order, used to find the members of subgraphs
+    mentioned: Vec<usize>,
+    edges: Vec<(usize, usize)>,
+    /// Subgraphs open around the current statement
+    depth: usize,
+    /// An edge between subgraphs stands for all their pairs, so edges are
+    /// counted against the limits while parsing rather than after
+    limits: CallLimits,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id { text, quoted: false }) if text.eq_ignore_ascii_case(word))
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.next += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        Err(format!("Line {}: expected '{}'", self.line(), symbol))
+    }
+
+    fn id(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Id { text, .. }) => {
+                let text = text.clone();
+                self.next += 1;
+                Ok(text)
+            }
+            _ => Err(format!("Line {}: expected an identifier", self.line())),
+        }
+    }
+
+    fn node(&mut self, name: &str, line: usize) -> usize {
+        let index = match self.nodes.get(name) {
+            Some(&index) => index,
+            None => {
+                let index = self.declared.len();
+                self.nodes.insert(name.to_string(), index);
+                self.declared
+                    .push((name.to_string(), DotNode { line, pos: None }));
+                index
+            }
+        };
+        self.mentioned.push(index);
+        index
+    }
+
+    fn graph(&mut self) -> Result<(), String> {
+        if self.keyword("strict") {
+            self.next += 1;
+        }
+        if !self.keyword("graph") && !self.keyword("digraph") {
+            return Err(format!(
+                "Line {}: expected 'graph' or 'digraph'",
+                self.line()
+            ));
+        }
+        self.next += 1;
+        if matches!(self.peek(), Some(Token::Id { .. })) {
+            self.next += 1;
+        }
+        self.expect('{')?;
+        self.statements()?;
+        if self.next < self.tokens.len() {
+            return Err(format!(
+                "Line {}: unexpected text after the graph",
+                self.line()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Statements up to and including the closing brace
+    fn statements(&mut self) -> Result<(), String> {
+        loop {
+            if self.eat('}') {
+                return Ok(());
+            }
+            if self.peek().is_none() {
+                return Err(format!("Line {}: missing closing '}}'", self.line()));
+            }
+            self.statement()?;
+            self.eat(';');
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        if self.keyword("graph") || self.keyword("node") || self.keyword("edge") {
+            self.next += 1;
+            return self.attributes().map(|_| ());
+        }
+        // `a = b` sets a graph attribute
+        let assignment = matches!(
+            self.tokens.get(self.next + 1),
+            Some((Token::Symbol('='), _))
+        );
+        if assignment {
+            self.id()?;
+            self.expect('=')?;
+            return self.id().map(|_| ());
+        }
+
+        let line = self.line();
+        let mut operands = vec![self.operand()?];
+        while self.peek() == Some(&Token::EdgeOp) {
+            self.next += 1;
+            operands.push(self.operand()?);
+        }
+
+        if operands.len() == 1 {
+            if let Some(name) = operands[0].1.clone() {
+                let attributes = self.attributes()?;
+                let index = self.node(&name, line);
+                if let Some(pos) = attributes.get("pos") {
+                    self.declared[index].1.pos = Some(pos.clone());
+                }
+            }
+            return Ok(());
+        }
+
+        self.attributes()?;
+        let added: u64 = operands
+            .windows(2)
+            .map(|pair| (pair[0].0.len() as u64).saturating_mul(pair[1].0.len() as u64))
+            .fold(0, u64::saturating_add);
+        self.limits
+            .check_graph(
+                self.declared.len() as u64,
+                (self.edges.len() as u64).saturating_add(added),
+            )
+            .map_err(|e| format!("Line {}: {}", line, e))?;
+        for pair in operands.windows(2) {
+            for &from in &pair[0].0 {
+                for &to in &pair[1].0 {
+                    self.edges.push((from, to));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A node id or a subgraph; returns the nodes it stands for, and the
+    /// name if it was a single node id
+    fn operand(&mut self) -> Result<(Vec<usize>, Option<String>), String> {
+        if self.keyword("subgraph") || self.peek() == Some(&Token::Symbol('{')) {
+            if self.keyword("subgraph") {
+                self.next += 1;
+                if matches!(self.peek(), Some(Token::Id { .. })) {
+                    self.next += 1;
+                }
+            }
+            if self.depth >= MAX_SUBGRAPH_DEPTH {
+                return Err(format!("Line {}: subgraphs nested too deeply", self.line()));
+            }
+            let first = self.mentioned.len();
+            self.expect('{')?;
+            self.depth += 1;
+            self.statements()?;
+            self.depth -= 1;
+            // Edge operands stand for every node mentioned in the subgraph
+            let mut members = self.mentioned[first..].to_vec();
+            members.sort_unstable();
+            members.dedup();
+            return Ok((members, None));
+        }
+
+        let line = self.line();
+        let name = self.id()?;
+        // Ports (`node:port:compass`) do not change which node is meant
+        while self.eat(':') {
+            self.id()?;
+        }
+        let index = self.node(&name, line);
+        Ok((vec![index], Some(name)))
+    }
+
+    /// Zero or more `[a=b, c=d]` lists, values with their line numbers
+    fn attributes(&mut self) -> Result<HashMap<String, (String, usize)>, String> {
+        let mut attributes = HashMap::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let line = self.line();
+                let key = self.id()?;
+                self.expect('=')?;
+                let value = self.id()?;
+                attributes.insert(key, (value, line));
+                if !self.eat(',') {
+                    self.eat(';');
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+/// Parses a Graphviz `pos` value: "x,y", optionally pinned with "!" or with a z part
+fn parse_pos(value: &str, line: usize) -> Result<Point, String> {
+    let value = value.trim().trim_end_matches('!');
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(format!("Line {}: pos \"{}\" is not \"x,y\"", line, value));
+    }
+    let coordinate = |text: &str, what: &str| {
+        parse_coordinate(text, what).map_err(|e| format!("Line {}: {}", line, e))
+    };
+    Ok(Point {
+        x: coordinate(parts[0], "x")?,
+        y: coordinate(parts[1], "y")?,
+        z: None,
+    })
+}
+
+/// Pure function to parse a Graphviz DOT graph
+/// Every node needs a `pos="x,y"` attribute; edges are read as undirected
+/// whether written `--` or `->`. Errors give the line number, and edges past
+/// `limits` are refused as they are read
+pub fn parse_dot(text: &str, tolerance: f64, limits: &CallLimits) -> Result<ImportedGraph, String> {
+    let mut parser = Parser {
+        tokens: Lexer::new(text).tokens()?,
+        next: 0,
+        nodes: HashMap::new(),
+        declared: Vec::new(),
+        mentioned: Vec::new(),
+        edges: Vec::new(),
+        depth: 0,
+        limits: *limits,
+    };
+    parser.graph()?;
+
+    let mut builder = GraphBuilder::new(tolerance);
+    let mut indices = Vec::with_capacity(parser.declared.len());
+    for (name, node) in &parser.declared {
+        let Some((pos, line)) = &node.pos else {
+            return Err(format!(
+                "Line {}: node '{}' has no pos attribute",
+                node.line, name
+            ));
+        };
+        indices.push(builder.add_point(parse_pos(pos, *line)?, name));
+    }
+    for &(from, to) in &parser.edges {
+        builder.add_edge(indices[from], indices[to]);
+    }
+
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dot_with_positions_and_chains() {
+        let text = r#"
+            // Road network
+            strict graph roads {
+                node [shape=point];
+                a [pos="0,0!"]; b [pos="1,0"]
+                "c d" [label="C", pos="1,1"]
+                a -- b -- "c d" [weight=2];
+                /* back to the start */
+                "c d" -> a
+            }
+        "#;
+
+        let graph = parse_dot(text, 0.0, &CallLimits::unlimited()).unwrap();
+
+        assert_eq!(graph.names, vec!["a", "b", "c d"]);
+        assert_eq!(graph.points[2].y, 1.0);
+        assert_eq!(graph.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_dot_subgraph_operand() {
+        let text = r#"graph {
+            rankdir = LR
+            a [pos="0,0"] b [pos="1,0"] c [pos="0,1"]
+            a -- { b c }
+        }"#;
+        let graph = parse_dot(text, 0.0, &CallLimits::unlimited()).unwrap();
+        assert_eq!(graph.edges.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_dot_reports_line_numbers() {
+        let missing_pos = "graph {\n  a [pos=\"0,0\"]\n  a -- b\n}";
+        assert_eq!(
+            parse_dot(missing_pos, 0.0, &CallLimits::unlimited()).unwrap_err(),
+            "Line 3: node 'b' has no pos attribute"
+        );
+
+        let bad_pos = "graph {\n  a [pos=\"0,north\"]\n}";
+        assert_eq!(
+            parse_dot(bad_pos, 0.0, &CallLimits::unlimited()).unwrap_err(),
+            "Line 2: y 'north' is not a number"
+        );
+
+        let unclosed = "graph {\n  a -- b\n";
+        assert_eq!(
+            parse_dot(unclosed, 0.0, &CallLimits::unlimited()).unwrap_err(),
+            "Line 2: missing closing '}'"
+        );
+    }
+
+    #[test]
+    fn test_parse_dot_refuses_deep_nesting() {
+        let depth = 10_000;
+        let text = format!("graph {{\n{}{}}}", "{".repeat(depth), "}".repeat(depth));
+        assert_eq!(
+            parse_dot(&text, 0.0, &CallLimits::unlimited()).unwrap_err(),
+            "Line 2: subgraphs nested too deeply"
+        );
+
+        let nested = format!(
+            "graph {{ {} a [pos=\"0,0\"] {} }}",
+            "{".repeat(64),
+            "}".repeat(64)
+        );
+        assert!(parse_dot(&nested, 0.0, &CallLimits::unlimited()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_dot_counts_subgraph_edges_against_limits() {
+        let side = |prefix: &str| {
+            (0..100)
+                .map(|i| format!("{}{} [pos=\"{},0\"]", prefix, i, i))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let text = format!("graph {{\n{{ {} }} -> {{ {} }}\n}}", side("a"), side("b"));
+        let limits = CallLimits {
+            max_edges: Some(9_999),
+            ..CallLimits::unlimited()
+        };
+        assert_eq!(
+            parse_dot(&text, 0.0, &limits).unwrap_err(),
+            "Line 2: Graph has 10000 edges; at most 9999 are allowed"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use super::GraphBuilder;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+use shared_types::{ImportedGraph, Point};
+use std::fmt;
+
+/// Nested coordinate arrays, validated while parsing so malformed positions
+/// are reported with serde_json's line and column
+enum Coordinates {
+    Number(f64),
+    Position(Point),
+    List(Vec<Coordinates>),
+}
+
+struct CoordinatesVisitor;
+
+impl<'de> Visitor<'de> for CoordinatesVisitor {
+    type Value = Coordinates;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number or an array of coordinates")
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Coordinates, E> {
+        Ok(Coordinates::Number(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Coordinates, E> {
+        Ok(Coordinates::Number(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Coordinates, E> {
+        Ok(Coordinates::Number(v as f64))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Coordinates, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element::<Coordinates>()? {
+            items.push(item);
+        }
+
+        let numbers: Vec<f64> = items
+            .iter()
+            .filter_map(|item| match item {
+                Coordinates::Number(v) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        if numbers.is_empty() {
+            return Ok(Coordinates::List(items));
+        }
+        if numbers.len() != items.len() {
+            return Err(de::Error::custom("position mixes numbers and arrays"));
+        }
+        // A third number is the altitude; anything after it (measures) is ignored
+        if numbers.len() < 2 {
+            return Err(de::Error::custom("position needs at least x and y"));
+        }
+        Ok(Coordinates::Position(Point {
+            x: numbers[0],
+            y: numbers[1],
+            z: numbers.get(2).copied(),
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Coordinates {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(CoordinatesVisitor)
+    }
+}
+
+/// Any GeoJSON object; which fields matter depends on `kind`
+#[derive(Deserialize)]
+struct GeoObject {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    features: Vec<GeoObject>,
+    #[serde(default)]
+    geometry: Option<Box<GeoObject>>,
+    #[serde(default)]
+    geometries: Vec<GeoObject>,
+    #[serde(default)]
+    coordinates: Option<Coordinates>,
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    #[serde(default)]
+    properties: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl GeoObject {
+    /// Feature `id`, or else `properties.name`, as a point name
+    fn name(&self) -> String {
+        let name = self.id.as_ref().or_else(|| {
+            self.properties
+                .as_ref()
+                .and_then(|properties| properties.get("name"))
+        });
+        match name {
+            Some(serde_json::Value::String(text)) => text.clone(),
+            Some(serde_json::Value::Number(number)) => number.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+fn position(coordinates: &Coordinates) -> Option<&Point> {
+    match coordinates {
+        Coordinates::Position(point) => Some(point),
+        _ => None,
+    }
+}
+
+/// The positions of a LineString or MultiPoint
+fn positions(coordinates: &Coordinates) -> Option<Vec<&Point>> {
+    match coordinates {
+        Coordinates::List(items) => items.iter().map(position).collect(),
+        _ => None,
+    }
+}
+
+/// The position lists of a MultiLineString
+fn lines(coordinates: &Coordinates) -> Option<Vec<Vec<&Point>>> {
+    match coordinates {
+        Coordinates::List(items) => items.iter().map(positions).collect(),
+        _ => None,
+    }
+}
+
+struct GeoJsonImport {
+    builder: GraphBuilder,
+}
+
+impl GeoJsonImport {
+    fn add_line(&mut self, line: &[&Point]) {
+        let indices: Vec<usize> = line
+            .iter()
+            .map(|&point| self.builder.add_point(point.clone(), ""))
+            .collect();
+        for pair in indices.windows(2) {
+            self.builder.add_edge(pair[0], pair[1]);
+        }
+    }
+
+    /// Adds `object`; `feature` is its index in the collection, for error messages
+    fn add(&mut self, object: &GeoObject, feature: usize, name: &str) -> Result<(), String> {
+        let shape_error = || {
+            format!(
+                "GeoJSON feature {}: {} coordinates have the wrong nesting",
+                feature, object.kind
+            )
+        };
+        let coordinates = || {
+            object.coordinates.as_ref().ok_or_else(|| {
+                format!(
+                    "GeoJSON feature {}: {} has no coordinates",
+                    feature, object.kind
+                )
+            })
+        };
+
+        match object.kind.as_str() {
+            "FeatureCollection" => {
+                for (i, child) in object.features.iter().enumerate() {
+                    self.add(child, i, "")?;
+                }
+            }
+            "Feature" => {
+                if let Some(geometry) = &object.geometry {
+                    self.add(geometry, feature, &object.name())?;
+                }
+            }
+            "GeometryCollection" => {
+                for geometry in &object.geometries {
+                    self.add(geometry, feature, name)?;
+                }
+            }
+            "Point" => {
+                let point = position(coordinates()?).ok_or_else(shape_error)?;
+                self.builder.add_point(point.clone(), name);
+            }
+            "MultiPoint" => {
+                for point in positions(coordinates()?).ok_or_else(shape_error)? {
+                    self.builder.add_point(point.clone(), name);
+                }
+            }
+            "LineString" => {
+                let line = positions(coordinates()?).ok_or_else(shape_error)?;
+                self.add_line(&line);
+            }
+            "MultiLineString" => {
+                for line in lines(coordinates()?).ok_or_else(shape_error)? {
+                    self.add_line(&line);
+                }
+            }
+            other => {
+                return Err(format!(
+                    "GeoJSON feature {}: {} geometries are not supported, use LineString or Point",
+                    feature, other
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pure function to parse GeoJSON Points and LineStrings into a graph
+/// Accepts a FeatureCollection, a Feature or a bare geometry. Each pair of
+/// consecutive LineString positions becomes an edge, and positions within
+/// `tolerance` of each other become one point, which joins lines into a network
+pub fn parse_geojson(text: &str, tolerance: f64) -> Result<ImportedGraph, String> {
+    let object: GeoObject =
+        serde_json::from_str(text).map_err(|e| format!("Invalid GeoJSON: {}", e))?;
+
+    let mut import = GeoJsonImport {
+        builder: GraphBuilder::new(tolerance),
+    };
+    import.add(&object, 0, "")?;
+    Ok(import.builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_geojson_joins_lines_at_shared_positions() {
+        let text = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {"type": "Feature", "properties": {},
+                 "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 0], [1, 1]]}},
+                {"type": "Feature", "properties": {"name": "depot"},
+                 "geometry": {"type": "Point", "coordinates": [1.0000001, 1, 12.5]}},
+                {"type": "Feature", "properties": null,
+                 "geometry": {"type": "MultiLineString", "coordinates": [[[1, 1], [2, 1]]]}}
+            ]
+        }"#;
+
+        let graph = parse_geojson(text, 1e-3).unwrap();
+
+        assert_eq!(graph.points.len(), 4);
+        assert_eq!(graph.names[2], "depot");
+        // The merged point picks up the altitude it lacked
+        assert_eq!(graph.points[2].z, Some(12.5));
+        assert_eq!(graph.points[0].z, None);
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.merged_points, 2);
+    }
+
+    #[test]
+    fn test_parse_geojson_reports_line_of_bad_position() {
+        let text = "{\n  \"type\": \"LineString\",\n  \"coordinates\": [[0, 0], [1]]\n}";
+        let error = parse_geojson(text, 0.0).unwrap_err();
+        assert!(
+            error.contains("position needs at least x and y at line 3"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_parse_geojson_rejects_polygons() {
+        let text = r#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [0, 1], [0, 0]]]}"#;
+        assert_eq!(
+            parse_geojson(text, 0.0).unwrap_err(),
+            "GeoJSON feature 0: Polygon geometries are not supported, use LineString or Point"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+mod csv;
+mod dot;
+mod geojson;
+
+pub use csv::parse_csv;
+pub use dot::parse_dot;
+pub use geojson::parse_geojson;
+
+use shared_types::router::CallLimits;
+use shared_types::{Edge, GraphSource, ImportedGraph, Point};
+use std::collections::{HashMap, HashSet};
+
+/// Points closer than this are merged when no tolerance is given
+pub const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+/// Core business logic: parse any supported text format into a graph
+/// Fails if the graph would be over `limits`
+pub fn import_graph(
+    source: &GraphSource,
+    tolerance: f64,
+    limits: &CallLimits,
+) -> Result<ImportedGraph, String> {
+    if !tolerance.is_finite() || tolerance < 0.0 {
+        return Err("Tolerance must be a non-negative number".to_string());
+    }
+    let graph = match source {
+        GraphSource::GeoJson { text } => parse_geojson(text, tolerance),
+        GraphSource::Dot { text } => parse_dot(text, tolerance, limits),
+        GraphSource::Csv { nodes, edges } => parse_csv(nodes, edges, tolerance),
+    }?;
+    limits.check_graph(graph.points.len() as u64, graph.edges.len() as u64)?;
+    Ok(graph)
+}
+
+/// Collects parsed nodes and edges, merging points within `tolerance`
+/// Points are bucketed into square cells of side `tolerance`, so a lookup only
+/// scans the 3x3 block of cells around the new point
+struct GraphBuilder {
+    tolerance: f64,
+    points: Vec<Point>,
+    names: Vec<String>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    edges: Vec<Edge>,
+    seen_edges: HashSet<(usize, usize)>,
+    merged_points: usize,
+    dropped_edges: usize,
+}
+
+impl GraphBuilder {
+    fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            points: Vec::new(),
+            names: Vec::new(),
+            cells: HashMap::new(),
+            edges: Vec::new(),
+            seen_edges: HashSet::new(),
+            merged_points: 0,
+            dropped_edges: 0,
+        }
+    }
+
+    fn cell(&self, point: &Point) -> (i64, i64) {
+        if self.tolerance > 0.0 {
+            (
+                (point.x / self.tolerance).floor() as i64,
+                (point.y / self.tolerance).floor() as i64,
+            )
+        } else {
+            // Exact matching; adding 0.0 folds -0.0 into 0.0
+            (
+                (point.x + 0.0).to_bits() as i64,
+                (point.y + 0.0).to_bits() as i64,
+            )
+        }
+    }
+
+    fn find(&self, point: &Point) -> Option<usize> {
+        let (cx, cy) = self.cell(point);
+        let reach = if self.tolerance > 0.0 { 1 } else { 0 };
+        // Cells saturate for huge coordinates, so the block must too
+        (cx.saturating_sub(reach)..=cx.saturating_add(reach))
+            .flat_map(|x| {
+                (cy.saturating_sub(reach)..=cy.saturating_add(reach)).map(move |y| (x, y))
+            })
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .copied()
+            .filter(|&i| crate::euclidean_distance(&self.points[i], point) <= self.tolerance)
+            .min()
+    }
+
+    /// Index of the point at `point`, adding it unless an earlier one is close enough
+    fn add_point(&mut self, point: Point, name: &str) -> usize {
+        if let Some(existing) = self.find(&point) {
+            self.merged_points += 1;
+            if self.names[existing].is_empty() {
+                self.names[existing] = name.to_string();
+            }
+            if self.points[existing].z.is_none() {
+                self.points[existing].z = point.z;
+            }
+            return existing;
+        }
+        let index = self.points.len();
+        self.cells.entry(self.cell(&point)).or_default().push(index);
+        self.points.push(point);
+        self.names.push(name.to_string());
+        index
+    }
+
+    /// Adds an edge unless merging turned it into a self-loop or a repeat
+    fn add_edge(&mut self, from: usize, to: usize) {
+        if from == to || !self.seen_edges.insert((from.min(to), from.max(to))) {
+            self.dropped_edges += 1;
+            return;
+        }
+        self.edges.push(Edge { from, to });
+    }
+
+    fn finish(self) -> ImportedGraph {
+        ImportedGraph {
+            points: self.points,
+            edges: self.edges,
+            names: self.names,
+            merged_points: self.merged_points,
+            dropped_edges: self.dropped_edges,
+        }
+    }
+}
+
+/// Parses a coordinate written as text
+fn parse_coordinate(text: &str, what: &str) -> Result<f64, String> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("{} '{}' is not a number", what, text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_merges_points_within_tolerance() {
+        let mut builder = GraphBuilder::new(0.01);
+        let a = builder.add_point(
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            "a",
+        );
+        let b = builder.add_point(
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            "b",
+        );
+        let a2 = builder.add_point(
+            Point {
+                x: 0.005,
+                y: -0.005,
+                z: None,
+            },
+            "a2",
+        );
+        builder.add_edge(a, b);
+        builder.add_edge(b, a2);
+        builder.add_edge(a2, a2);
+
+        let graph = builder.finish();
+        assert_eq!(a, a2);
+        assert_eq!(graph.points.len(), 2);
+        assert_eq!(graph.names, vec!["a", "b"]);
+        assert_eq!(graph.merged_points, 1);
+        assert_eq!(graph.dropped_edges, 2);
+    }
+
+    #[test]
+    fn test_import_rejects_negative_tolerance() {
+        let source = GraphSource::Dot {
+            text: "graph {}".to_string(),
+        };
+        assert_eq!(
+            import_graph(&source, -1.0, &CallLimits::unlimited()).unwrap_err(),
+            "Tolerance must be a non-negative number"
+        );
+    }
+}
@@ -1,14 +1,67 @@
 use petgraph::algo::dijkstra;
 use petgraph::graph::{Graph, NodeIndex};
-use shared_types::{Edge, PathResult, Point};
+use shared_types::{Edge, PathResult, Point, ShortestPathParams};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
+pub mod bidirectional;
+pub mod centrality;
+pub mod constraints;
+pub mod contraction;
+pub mod editable;
+pub mod elevation;
+pub mod export;
+pub mod flow;
+pub mod generate;
 pub mod handler;
+pub mod import;
+pub mod pareto;
+mod rng;
+pub mod smoothing;
+pub mod tour;
+pub mod turns;
+pub use constraints::PathConstraints;
+pub use editable::EditableGraph;
 pub use handler::PathfinderHandler;
 
 /// Pure function to compute Euclidean distance between two points
+/// Includes elevation when both points have a `z`
 pub fn euclidean_distance(p1: &Point, p2: &Point) -> f64 {
-    ((p2.x - p1.x).powi(2) + (p2.y - p1.y).powi(2)).sqrt()
+    let dz = elevation::elevation_change(p1, p2);
+    ((p2.x - p1.x).powi(2) + (p2.y - p1.y).powi(2) + dz.powi(2)).sqrt()
+}
+
+/// Err naming the first edge with an endpoint outside `0..point_count`
+/// Graph builders index points by edge endpoint, so calls check this up front
+pub fn check_edges(edges: &[Edge], point_count: usize) -> Result<(), String> {
+    edges
+        .iter()
+        .try_for_each(|edge| check_edge(edge, point_count))
+}
+
+pub(crate) fn check_edge(edge: &Edge, point_count: usize) -> Result<(), String> {
+    if edge.from >= point_count || edge.to >= point_count {
+        return Err(format!(
+            "Edge {}-{} refers to a point that does not exist",
+            edge.from, edge.to
+        ));
+    }
+    Ok(())
+}
+
+/// Err unless `edges` and both endpoints of a path query lie within `0..point_count`
+pub(crate) fn check_path_query(
+    edges: &[Edge],
+    point_count: usize,
+    start_idx: usize,
+    end_idx: usize,
+) -> Result<(), String> {
+    check_edges(edges, point_count)?;
+    if start_idx >= point_count || end_idx >= point_count {
+        return Err("Start or end index is out of bounds".to_string());
+    }
+    Ok(())
 }
 
 /// Pure function to reconstruct the shortest path from Dijkstra results
@@ -48,60 +101,266 @@ pub fn reconstruct_path(
     path
 }
 
-/// Core business logic: compute the shortest path
-/// This is completely transport-agnostic
-pub fn compute_shortest_path(
+/// Pure function to build an undirected graph weighted by Euclidean edge length
+/// Node `i` in the returned list corresponds to `points[i]`
+pub fn build_graph(
     points: &[Point],
     edges: &[Edge],
-    start_idx: usize,
-    end_idx: usize,
-) -> Result<PathResult, String> {
-    // Build the graph
+) -> (Graph<(), f64, petgraph::Undirected>, Vec<NodeIndex>) {
+    build_weighted_graph(points.len(), edges, |i| {
+        euclidean_distance(&points[edges[i].from], &points[edges[i].to])
+    })
+}
+
+/// Pure function to build an undirected graph, weighting edge `i` by `weight(i)`
+pub fn build_weighted_graph(
+    node_count: usize,
+    edges: &[Edge],
+    weight: impl Fn(usize) -> f64,
+) -> (Graph<(), f64, petgraph::Undirected>, Vec<NodeIndex>) {
     let mut graph: Graph<(), f64, petgraph::Undirected> = Graph::new_undirected();
 
-    let nodes: Vec<NodeIndex> = (0..points.len()).map(|_| graph.add_node(())).collect();
+    let nodes: Vec<NodeIndex> = (0..node_count).map(|_| graph.add_node(())).collect();
 
-    for edge in edges {
-        let distance = euclidean_distance(&points[edge.from], &points[edge.to]);
-        graph.add_edge(nodes[edge.from], nodes[edge.to], distance);
+    for (i, edge) in edges.iter().enumerate() {
+        graph.add_edge(nodes[edge.from], nodes[edge.to], weight(i));
     }
 
+    (graph, nodes)
+}
+
+/// Pure function to run Dijkstra between two nodes of a graph from `build_graph`
+pub fn shortest_path_in_graph(
+    graph: &Graph<(), f64, petgraph::Undirected>,
+    nodes: &[NodeIndex],
+    start_idx: usize,
+    end_idx: usize,
+) -> Result<PathResult, String> {
     let start_node = nodes[start_idx];
     let end_node = nodes[end_idx];
 
     // Run Dijkstra's algorithm
-    let result = dijkstra(&graph, start_node, Some(end_node), |e| *e.weight());
+    let result = dijkstra(graph, start_node, Some(end_node), |e| *e.weight());
 
     if let Some(&distance) = result.get(&end_node) {
-        let path = reconstruct_path(&graph, &result, start_node, end_node);
+        let path = reconstruct_path(graph, &result, start_node, end_node);
         let path_indices: Vec<usize> = path.iter().map(|&n| n.index()).collect();
 
         Ok(PathResult {
             path: path_indices,
             distance,
+            turn_cost: None,
+            geometry: None,
         })
     } else {
         Err("No path found".to_string())
     }
 }
 
+/// Core business logic: compute the shortest path
+/// This is completely transport-agnostic
+pub fn compute_shortest_path(
+    points: &[Point],
+    edges: &[Edge],
+    start_idx: usize,
+    end_idx: usize,
+) -> Result<PathResult, String> {
+    compute_shortest_path_with(points, edges, start_idx, end_idx, &SearchOptions::default())
+}
+
+/// How a shortest path search runs and weights edges
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Search from both ends at once; finds an equally short path, though not
+    /// always the same one when there are ties
+    pub bidirectional: bool,
+    /// Extra cost per unit of elevation gained or lost, see `elevation::edge_cost`
+    pub slope_penalty: f64,
+}
+
+impl SearchOptions {
+    pub fn from_params(params: &ShortestPathParams) -> Result<Self, String> {
+        let slope_penalty = params.slope_penalty.unwrap_or(0.0);
+        if !slope_penalty.is_finite() || slope_penalty < 0.0 {
+            return Err("Slope penalty must be a non-negative number".to_string());
+        }
+        Ok(Self {
+            bidirectional: params.bidirectional.unwrap_or(false),
+            slope_penalty,
+        })
+    }
+}
+
+/// Core business logic: compute the shortest path with the given search options
+/// `distance` is always the length of the path, whatever the edges weighed
+pub fn compute_shortest_path_with(
+    points: &[Point],
+    edges: &[Edge],
+    start_idx: usize,
+    end_idx: usize,
+    options: &SearchOptions,
+) -> Result<PathResult, String> {
+    check_path_query(edges, points.len(), start_idx, end_idx)?;
+
+    // Build the graph
+    let (graph, nodes) = if options.slope_penalty > 0.0 {
+        build_weighted_graph(points.len(), edges, |i| {
+            let edge = &edges[i];
+            elevation::edge_cost(&points[edge.from], &points[edge.to], options.slope_penalty)
+        })
+    } else {
+        build_graph(points, edges)
+    };
+
+    let mut result = if options.bidirectional {
+        bidirectional::bidirectional_shortest_path_in_graph(&graph, &nodes, start_idx, end_idx)?
+    } else {
+        shortest_path_in_graph(&graph, &nodes, start_idx, end_idx)?
+    };
+    if options.slope_penalty > 0.0 {
+        result.distance = result
+            .path
+            .windows(2)
+            .map(|pair| euclidean_distance(&points[pair[0]], &points[pair[1]]))
+            .sum();
+    }
+    Ok(result)
+}
+
+/// Pure function to hash the geometry and topology of a graph
+/// Used to key cached results so they never outlive the graph they came from
+pub fn graph_fingerprint(points: &[Point], edges: &[Edge]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for point in points {
+        point.x.to_bits().hash(&mut hasher);
+        point.y.to_bits().hash(&mut hasher);
+        point.z.map(f64::to_bits).hash(&mut hasher);
+    }
+    for edge in edges {
+        (edge.from, edge.to).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Core business logic: compute the shortest path that avoids `constraints`
+/// Forbidden elements are dropped from the graph, so clients never rebuild it;
+/// when only the constraints cut off the target, the error says so
+pub fn compute_constrained_shortest_path(
+    points: &[Point],
+    edges: &[Edge],
+    start_idx: usize,
+    end_idx: usize,
+    constraints: &PathConstraints,
+    options: &SearchOptions,
+) -> Result<PathResult, String> {
+    if constraints.is_empty() {
+        return compute_shortest_path_with(points, edges, start_idx, end_idx, options);
+    }
+
+    let allowed_edges = constraints.allowed_edges(points, edges)?;
+    constraints.check_endpoints(points, start_idx, end_idx)?;
+
+    match compute_shortest_path_with(points, &allowed_edges, start_idx, end_idx, options) {
+        Err(_) if compute_shortest_path(points, edges, start_idx, end_idx).is_ok() => Err(format!(
+            "No path found: the constraints ({}) block every route from {} to {}",
+            constraints.describe(),
+            start_idx,
+            end_idx
+        )),
+        result => result,
+    }
+}
+
+/// Core business logic: answer a `find_shortest_path` request
+/// Picks the routing mode from the optional fields of `ShortestPathParams`
+pub fn compute_route(params: &ShortestPathParams) -> Result<PathResult, String> {
+    check_path_query(
+        &params.edges,
+        params.points.len(),
+        params.start_idx,
+        params.end_idx,
+    )?;
+    let constraints = PathConstraints::from_params(params);
+    let options = SearchOptions::from_params(params)?;
+
+    let mut result = if params.turn_penalty.is_some() || params.banned_turns.is_some() {
+        let penalty = params
+            .turn_penalty
+            .as_ref()
+            .map(turns::turn_penalty_fn)
+            .transpose()?;
+        turns::compute_turn_aware_path(
+            &params.points,
+            &params.edges,
+            params.start_idx,
+            params.end_idx,
+            &constraints,
+            options.slope_penalty,
+            |angle| penalty.as_ref().map_or(0.0, |f| f(angle)),
+            params.banned_turns.as_deref().unwrap_or_default(),
+        )?
+    } else {
+        compute_constrained_shortest_path(
+            &params.points,
+            &params.edges,
+            params.start_idx,
+            params.end_idx,
+            &constraints,
+            &options,
+        )?
+    };
+
+    if let Some(options) = &params.post_processing {
+        // Forbidden regions are obstacles for the straight stretches too
+        let mut obstacles = constraints.forbidden_regions;
+        obstacles.extend(options.obstacles.iter().flatten().cloned());
+        result.geometry = Some(smoothing::post_process(
+            &params.points,
+            &result.path,
+            options,
+            &obstacles,
+        )?);
+    }
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_euclidean_distance() {
-        let p1 = Point { x: 0.0, y: 0.0 };
-        let p2 = Point { x: 3.0, y: 4.0 };
+        let p1 = Point {
+            x: 0.0,
+            y: 0.0,
+            z: None,
+        };
+        let p2 = Point {
+            x: 3.0,
+            y: 4.0,
+            z: None,
+        };
         assert_eq!(euclidean_distance(&p1, &p2), 5.0);
     }
 
     #[test]
     fn test_compute_shortest_path_simple() {
         let points = vec![
-            Point { x: 0.0, y: 0.0 },
-            Point { x: 1.0, y: 0.0 },
-            Point { x: 0.0, y: 1.0 },
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: None,
+            },
         ];
 
         let edges = vec![
@@ -121,9 +380,21 @@ mod tests {
     #[test]
     fn test_compute_shortest_path_no_path() {
         let points = vec![
-            Point { x: 0.0, y: 0.0 },
-            Point { x: 1.0, y: 0.0 },
-            Point { x: 10.0, y: 10.0 },
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 10.0,
+                y: 10.0,
+                z: None,
+            },
         ];
 
         let edges = vec![Edge { from: 0, to: 1 }];
@@ -132,4 +403,144 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "No path found");
     }
+
+    #[test]
+    fn test_route_rejects_indices_out_of_range() {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+        ];
+        let mut params = ShortestPathParams {
+            points,
+            edges: vec![Edge { from: 0, to: 1 }],
+            start_idx: 0,
+            end_idx: 2,
+            forbidden_nodes: None,
+            forbidden_edges: None,
+            forbidden_regions: None,
+            turn_penalty: None,
+            banned_turns: Some(Vec::new()),
+            bidirectional: None,
+            post_processing: None,
+            slope_penalty: None,
+            max_grade: None,
+        };
+        assert_eq!(
+            compute_route(&params).unwrap_err(),
+            "Start or end index is out of bounds"
+        );
+
+        params.banned_turns = None;
+        params.end_idx = 1;
+        params.edges.push(Edge { from: 1, to: 5 });
+        assert_eq!(
+            compute_route(&params).unwrap_err(),
+            "Edge 1-5 refers to a point that does not exist"
+        );
+        let result = compute_shortest_path(&params.points, &params.edges[..1], 0, 7);
+        assert_eq!(result.unwrap_err(), "Start or end index is out of bounds");
+    }
+
+    #[test]
+    fn test_constrained_shortest_path_detours_around_region() {
+        // Direct edge 0-1 crosses the box; the detour goes through point 2
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 4.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 3.0,
+                z: None,
+            },
+        ];
+        let edges = vec![
+            Edge { from: 0, to: 1 },
+            Edge { from: 0, to: 2 },
+            Edge { from: 2, to: 1 },
+        ];
+        let constraints = PathConstraints {
+            forbidden_regions: vec![shared_types::Region::Rect {
+                min: Point {
+                    x: 1.0,
+                    y: -1.0,
+                    z: None,
+                },
+                max: Point {
+                    x: 3.0,
+                    y: 1.0,
+                    z: None,
+                },
+            }],
+            ..Default::default()
+        };
+
+        let result = compute_constrained_shortest_path(
+            &points,
+            &edges,
+            0,
+            1,
+            &constraints,
+            &SearchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.path, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_constrained_shortest_path_explains_blocked_target() {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 0.0,
+                z: None,
+            },
+        ];
+        let edges = vec![Edge { from: 0, to: 1 }, Edge { from: 1, to: 2 }];
+        let constraints = PathConstraints {
+            forbidden_nodes: vec![1],
+            ..Default::default()
+        };
+
+        let result = compute_constrained_shortest_path(
+            &points,
+            &edges,
+            0,
+            2,
+            &constraints,
+            &SearchOptions {
+                bidirectional: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "No path found: the constraints (1 forbidden nodes, 0 forbidden edges, 0 forbidden regions) block every route from 0 to 2"
+        );
+    }
 }
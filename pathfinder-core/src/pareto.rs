@@ -0,0 +1,503 @@
+use crate::{check_edges, euclidean_distance};
+use shared_types::router::Progress;
+use shared_types::{Edge, ParetoPath, Point};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Tolerance when comparing costs for dominance
+const DOMINANCE_EPSILON: f64 = 1e-10;
+
+/// Number of progress reports over the span of the front
+const PROGRESS_STEPS: usize = 100;
+
+/// Most labels a search may create. Fronts can grow exponentially with the graph,
+/// so this holds the search to the work `CallLimits` estimated for it: the edge
+/// relaxations of one plain search per path asked for, at most one per point
+fn max_labels(points: usize, edges: usize, max_paths: Option<usize>) -> usize {
+    let paths = max_paths.unwrap_or(points).min(points).max(1);
+    paths.saturating_mul(points + 2 * edges)
+}
+
+/// A partial path ending at `node`, linked to the label it was extended from
+struct Label {
+    distance: f64,
+    secondary_cost: f64,
+    node: usize,
+    parent: Option<usize>,
+}
+
+impl Label {
+    /// Weak dominance: no worse on both criteria (also discards exact ties)
+    fn dominates(&self, distance: f64, secondary_cost: f64) -> bool {
+        self.distance <= distance + DOMINANCE_EPSILON
+            && self.secondary_cost <= secondary_cost + DOMINANCE_EPSILON
+    }
+}
+
+/// Heap entry ordered lexicographically by (distance, secondary cost), smallest first
+#[derive(PartialEq)]
+struct Candidate {
+    distance: f64,
+    secondary_cost: f64,
+    label: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.secondary_cost.total_cmp(&self.secondary_cost))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn label_path(labels: &[Label], mut index: usize) -> Vec<usize> {
+    let mut path = vec![labels[index].node];
+    while let Some(parent) = labels[index].parent {
+        path.push(labels[parent].node);
+        index = parent;
+    }
+    path.reverse();
+    path
+}
+
+/// Length of the path with the lowest secondary cost, the shortest among ties, or
+/// `None` if `end_idx` cannot be reached. It closes the front: no Pareto-optimal
+/// path is longer
+fn least_secondary_length(
+    adjacency: &[Vec<(usize, f64, f64)>],
+    start_idx: usize,
+    end_idx: usize,
+) -> Option<f64> {
+    // Candidates keyed by secondary cost first, so their fields are swapped here
+    let mut best = vec![(f64::INFINITY, f64::INFINITY); adjacency.len()];
+    best[start_idx] = (0.0, 0.0);
+    let mut heap = BinaryHeap::from([Candidate {
+        distance: 0.0,
+        secondary_cost: 0.0,
+        label: start_idx,
+    }]);
+
+    while let Some(Candidate {
+        distance: secondary_cost,
+        secondary_cost: distance,
+        label: node,
+    }) = heap.pop()
+    {
+        if node == end_idx {
+            return Some(distance);
+        }
+        if (secondary_cost, distance) > best[node] {
+            continue;
+        }
+        for &(next, length, cost) in &adjacency[node] {
+            let key = (secondary_cost + cost, distance + length);
+            if key < best[next] {
+                best[next] = key;
+                heap.push(Candidate {
+                    distance: key.0,
+                    secondary_cost: key.1,
+                    label: next,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Core business logic: every Pareto-optimal path over length and a secondary cost
+/// Uses multi-criteria label setting. Labels leave the queue in lexicographic
+/// order, so each one reaching `end_idx` is final and is passed to `on_path`
+/// immediately, shortest first. Returns the number of paths found.
+/// `on_progress` measures the settled length against the longest path on the front.
+/// `is_cancelled` is checked before each label is settled; once it returns true the
+/// search stops with the paths found so far
+#[allow(clippy::too_many_arguments)]
+pub fn find_pareto_paths(
+    points: &[Point],
+    edges: &[Edge],
+    secondary_costs: &[f64],
+    start_idx: usize,
+    end_idx: usize,
+    max_paths: Option<usize>,
+    mut on_progress: impl FnMut(Progress),
+    mut on_path: impl FnMut(ParetoPath),
+    is_cancelled: impl Fn() -> bool,
+) -> Result<usize, String> {
+    if max_paths == Some(0) {
+        return Err("Max paths must be at least 1".to_string());
+    }
+    if secondary_costs.len() != edges.len() {
+        return Err(format!(
+            "Expected {} secondary costs, one per edge, but got {}",
+            edges.len(),
+            secondary_costs.len()
+        ));
+    }
+    if let Some(i) = secondary_costs
+        .iter()
+        .position(|c| !c.is_finite() || *c < 0.0)
+    {
+        return Err(format!(
+            "Secondary cost of edge {} must be a non-negative number",
+            i
+        ));
+    }
+    check_edges(edges, points.len())?;
+    for (name, index) in [("Start", start_idx), ("End", end_idx)] {
+        if index >= points.len() {
+            return Err(format!("{} index {} is out of range", name, index));
+        }
+    }
+
+    let mut adjacency: Vec<Vec<(usize, f64, f64)>> = vec![Vec::new(); points.len()];
+    for (edge, &cost) in edges.iter().zip(secondary_costs) {
+        let length = euclidean_distance(&points[edge.from], &points[edge.to]);
+        adjacency[edge.from].push((edge.to, length, cost));
+        adjacency[edge.to].push((edge.from, length, cost));
+    }
+    let Some(front_length) = least_secondary_length(&adjacency, start_idx, end_idx) else {
+        return Err("No path found".to_string());
+    };
+
+    let mut labels = vec![Label {
+        distance: 0.0,
+        secondary_cost: 0.0,
+        node: start_idx,
+        parent: None,
+    }];
+    // Labels made permanent at each node; the ones at `end_idx` are the results
+    let mut settled: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+    let mut heap = BinaryHeap::from([Candidate {
+        distance: 0.0,
+        secondary_cost: 0.0,
+        label: 0,
+    }]);
+    let label_budget = max_labels(points.len(), edges.len(), max_paths);
+    let mut found = 0;
+    let mut reported_step = 0;
+
+    while let Some(Candidate {
+        distance,
+        secondary_cost,
+        label,
+    }) = heap.pop()
+    {
+        if is_cancelled() {
+            return Ok(found);
+        }
+        let step = (distance / front_length * PROGRESS_STEPS as f64) as usize;
+        if step > reported_step {
+            reported_step = step;
+            on_progress(Progress::new(distance / front_length, "pareto front"));
+        }
+        let node = labels[label].node;
+        let dominated = |at: usize| {
+            settled[at]
+                .iter()
+                .any(|&l| labels[l].dominates(distance, secondary_cost))
+        };
+        if dominated(node) || dominated(end_idx) {
+            continue;
+        }
+        settled[node].push(label);
+
+        if node == end_idx {
+            on_path(ParetoPath {
+                path: label_path(&labels, label),
+                distance,
+                secondary_cost,
+            });
+            found += 1;
+            if max_paths.is_some_and(|max| found >= max) {
+                break;
+            }
+            continue;
+        }
+
+        for &(next, length, cost) in &adjacency[node] {
+            let next_distance = distance + length;
+            let next_secondary = secondary_cost + cost;
+            let pruned = [next, end_idx].iter().any(|&at| {
+                settled[at]
+                    .iter()
+                    .any(|&l| labels[l].dominates(next_distance, next_secondary))
+            });
+            if pruned {
+                continue;
+            }
+            if labels.len() >= label_budget {
+                return Err(format!(
+                    "Pareto front is too large: the search needs more than {} labels",
+                    label_budget
+                ));
+            }
+
+            labels.push(Label {
+                distance: next_distance,
+                secondary_cost: next_secondary,
+                node: next,
+                parent: Some(label),
+            });
+            heap.push(Candidate {
+                distance: next_distance,
+                secondary_cost: next_secondary,
+                label: labels.len() - 1,
+            });
+        }
+    }
+
+    if found == 0 {
+        return Err("No path found".to_string());
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Short risky route 0-1-3, long safe route 0-2-3, and a dominated 0-3 detour
+    fn two_routes() -> (Vec<Point>, Vec<Edge>, Vec<f64>) {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 2.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 0.0,
+                z: None,
+            },
+        ];
+        let edges = vec![
+            Edge { from: 0, to: 1 },
+            Edge { from: 1, to: 3 },
+            Edge { from: 0, to: 2 },
+            Edge { from: 2, to: 3 },
+            Edge { from: 0, to: 3 },
+        ];
+        let secondary = vec![5.0, 5.0, 1.0, 1.0, 20.0];
+        (points, edges, secondary)
+    }
+
+    #[test]
+    fn test_pareto_front_streams_shortest_first() {
+        let (points, edges, secondary) = two_routes();
+        let mut paths = Vec::new();
+
+        let found = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            None,
+            |_| {},
+            |p| paths.push(p),
+            || false,
+        )
+        .unwrap();
+
+        assert_eq!(found, 2);
+        // 0-3 directly is as short as 0-1-3 but has a higher secondary cost
+        assert_eq!(paths[0].path, vec![0, 1, 3]);
+        assert!((paths[0].secondary_cost - 10.0).abs() < 1e-10);
+        assert_eq!(paths[1].path, vec![0, 2, 3]);
+        assert!((paths[1].secondary_cost - 2.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_pareto_progress_reaches_the_longest_path_on_the_front() {
+        let (points, edges, secondary) = two_routes();
+        let mut reports = Vec::new();
+
+        find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            None,
+            |p| reports.push(p.fraction),
+            |_| {},
+            || false,
+        )
+        .unwrap();
+
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    /// `count` diamonds in a row, each a short costly branch and a long free one,
+    /// so every choice of branches is Pareto-optimal: 2^count paths from 0 to `count`
+    fn diamond_chain(count: usize) -> (Vec<Point>, Vec<Edge>, Vec<f64>) {
+        let point = |x: usize, y: f64| Point {
+            x: x as f64,
+            y,
+            z: None,
+        };
+        let mut points: Vec<Point> = (0..=count).map(|i| point(2 * i, 0.0)).collect();
+        let mut edges = Vec::new();
+        let mut secondary = Vec::new();
+        for i in 0..count {
+            let height = 0.1 * (i + 1) as f64;
+            let detour = 2.0 * (1.0 + height * height).sqrt() - 2.0;
+            for (y, cost) in [(0.0, detour / 2.0), (height, 0.0)] {
+                points.push(point(2 * i + 1, y));
+                let mid = points.len() - 1;
+                edges.extend([
+                    Edge { from: i, to: mid },
+                    Edge {
+                        from: mid,
+                        to: i + 1,
+                    },
+                ]);
+                secondary.extend([cost, cost]);
+            }
+        }
+        (points, edges, secondary)
+    }
+
+    #[test]
+    fn test_pareto_stops_when_the_front_outgrows_its_budget() {
+        let (points, edges, secondary) = diamond_chain(16);
+        let result = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            16,
+            None,
+            |_| {},
+            |_| {},
+            || false,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "Pareto front is too large: the search needs more than 8673 labels"
+        );
+
+        let (points, edges, secondary) = diamond_chain(4);
+        let found = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            4,
+            None,
+            |_| {},
+            |_| {},
+            || false,
+        );
+        assert_eq!(found, Ok(16));
+    }
+
+    #[test]
+    fn test_pareto_stops_when_cancelled() {
+        let (points, edges, secondary) = two_routes();
+        let mut paths = 0;
+        let found = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            None,
+            |_| {},
+            |_| paths += 1,
+            || true,
+        );
+        assert_eq!(found, Ok(0));
+        assert_eq!(paths, 0);
+    }
+
+    #[test]
+    fn test_pareto_rejects_zero_max_paths() {
+        let (points, edges, secondary) = two_routes();
+        let result = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            Some(0),
+            |_| {},
+            |_| {},
+            || false,
+        );
+        assert_eq!(result.unwrap_err(), "Max paths must be at least 1");
+    }
+
+    #[test]
+    fn test_pareto_respects_max_paths() {
+        let (points, edges, secondary) = two_routes();
+        let found = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            Some(1),
+            |_| {},
+            |_| {},
+            || false,
+        )
+        .unwrap();
+        assert_eq!(found, 1);
+    }
+
+    #[test]
+    fn test_pareto_rejects_indices_out_of_range() {
+        let (points, mut edges, mut secondary) = two_routes();
+        let result = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            4,
+            None,
+            |_| {},
+            |_| {},
+            || false,
+        );
+        assert_eq!(result.unwrap_err(), "End index 4 is out of range");
+
+        edges.push(Edge { from: 3, to: 9 });
+        secondary.push(1.0);
+        let result = find_pareto_paths(
+            &points,
+            &edges,
+            &secondary,
+            0,
+            3,
+            None,
+            |_| {},
+            |_| {},
+            || false,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "Edge 3-9 refers to a point that does not exist"
+        );
+    }
+}
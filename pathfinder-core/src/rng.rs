@@ -0,0 +1,41 @@
+/// Small deterministic PRNG (SplitMix64) so seeded results are reproducible
+/// across native and WASM builds without pulling in an external crate
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)` built from the top 53 bits
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform value in `0..bound`; `bound` must be non-zero
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// `count` distinct values from `0..n`, via a partial Fisher-Yates shuffle
+    pub(crate) fn sample_indices(&mut self, n: usize, count: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..n).collect();
+        let count = count.min(n);
+        for i in 0..count {
+            let j = i + self.next_below(n - i);
+            indices.swap(i, j);
+        }
+        indices.truncate(count);
+        indices
+    }
+}
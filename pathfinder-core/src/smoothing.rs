@@ -0,0 +1,334 @@
+use crate::constraints::segment_intersects_region;
+use crate::euclidean_distance;
+use shared_types::{PathGeometry, PathPostProcessing, Point, Region, Smoothing};
+
+/// Upper bound on Chaikin iterations; the point count doubles with each one
+pub const MAX_CHAIKIN_ITERATIONS: usize = 10;
+
+/// Upper bound on Catmull-Rom samples between two waypoints
+pub const MAX_SAMPLES_PER_SEGMENT: usize = 100;
+
+fn clear(obstacles: &[Region], a: &Point, b: &Point) -> bool {
+    !obstacles
+        .iter()
+        .any(|region| segment_intersects_region(region, a, b))
+}
+
+/// Weighted mix of two elevations, kept only when both points have one
+fn mix_z(a: &Point, wa: f64, b: &Point, wb: f64) -> Option<f64> {
+    Some(wa * a.z? + wb * b.z?)
+}
+
+/// Distance from `p` to the segment `a`-`b`
+fn distance_to_segment(p: &Point, a: &Point, b: &Point) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_sq = dx * dx + dy * dy;
+    if length_sq == 0.0 {
+        return euclidean_distance(p, a);
+    }
+    let t = (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_sq).clamp(0.0, 1.0);
+    euclidean_distance(
+        p,
+        &Point {
+            x: a.x + t * dx,
+            y: a.y + t * dy,
+            z: None,
+        },
+    )
+}
+
+/// Pure function to compute the length of a polyline
+pub fn polyline_length(points: &[Point]) -> f64 {
+    points
+        .windows(2)
+        .map(|pair| euclidean_distance(&pair[0], &pair[1]))
+        .sum()
+}
+
+/// Pure function to simplify a path with Ramer-Douglas-Peucker
+/// Keeps a node whenever dropping it would move the line more than `tolerance`
+/// or make a straight stretch cross an obstacle. Returns node indices, in order
+pub fn simplify(
+    points: &[Point],
+    path: &[usize],
+    tolerance: f64,
+    obstacles: &[Region],
+) -> Vec<usize> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+    let mut keep = vec![false; path.len()];
+    keep[0] = true;
+    keep[path.len() - 1] = true;
+
+    let mut pending = vec![(0, path.len() - 1)];
+    while let Some((first, last)) = pending.pop() {
+        if last - first < 2 {
+            continue;
+        }
+        let (a, b) = (&points[path[first]], &points[path[last]]);
+        let (farthest, offset) = (first + 1..last)
+            .map(|i| (i, distance_to_segment(&points[path[i]], a, b)))
+            .fold((first + 1, f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        if offset > tolerance || !clear(obstacles, a, b) {
+            keep[farthest] = true;
+            pending.push((first, farthest));
+            pending.push((farthest, last));
+        }
+    }
+
+    path.iter()
+        .zip(keep)
+        .filter(|&(_, kept)| kept)
+        .map(|(&node, _)| node)
+        .collect()
+}
+
+/// Pure function to shortcut a path by line of sight
+/// From each kept node, jumps to the farthest later node that a straight line
+/// reaches without touching an obstacle. Returns node indices, in order
+pub fn shortcut(points: &[Point], path: &[usize], obstacles: &[Region]) -> Vec<usize> {
+    let Some(&first) = path.first() else {
+        return Vec::new();
+    };
+    let mut kept = vec![first];
+    let mut current = 0;
+    while current + 1 < path.len() {
+        let from = &points[path[current]];
+        // Falls back to the next node, which the path already reaches by an edge
+        current = (current + 2..path.len())
+            .rev()
+            .find(|&j| clear(obstacles, from, &points[path[j]]))
+            .unwrap_or(current + 1);
+        kept.push(path[current]);
+    }
+    kept
+}
+
+/// Pure function to smooth a polyline by Chaikin corner cutting
+/// Each iteration replaces every segment with points at its quarter and
+/// three-quarter marks; the endpoints stay fixed
+pub fn chaikin(points: &[Point], iterations: usize) -> Vec<Point> {
+    let mut current = points.to_vec();
+    for _ in 0..iterations {
+        if current.len() < 3 {
+            break;
+        }
+        let mut next = Vec::with_capacity(current.len() * 2);
+        next.push(current[0].clone());
+        for pair in current.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            next.push(Point {
+                x: 0.75 * a.x + 0.25 * b.x,
+                y: 0.75 * a.y + 0.25 * b.y,
+                z: mix_z(a, 0.75, b, 0.25),
+            });
+            next.push(Point {
+                x: 0.25 * a.x + 0.75 * b.x,
+                y: 0.25 * a.y + 0.75 * b.y,
+                z: mix_z(a, 0.25, b, 0.75),
+            });
+        }
+        // The first and last cut points are replaced by the fixed endpoints
+        next.remove(1);
+        next.pop();
+        next.push(current[current.len() - 1].clone());
+        current = next;
+    }
+    current
+}
+
+/// Pure function to interpolate a centripetal Catmull-Rom spline through `points`
+/// Adds `samples_per_segment - 1` points between consecutive waypoints. The
+/// centripetal parameterisation avoids the loops and cusps of the uniform one
+pub fn catmull_rom(points: &[Point], samples_per_segment: usize) -> Vec<Point> {
+    if points.len() < 3 || samples_per_segment < 2 {
+        return points.to_vec();
+    }
+    // Mirror the ends so the first and last segments have neighbours
+    let mirror = |p: &Point, q: &Point| Point {
+        x: 2.0 * p.x - q.x,
+        y: 2.0 * p.y - q.y,
+        z: mix_z(p, 2.0, q, -1.0),
+    };
+    let n = points.len();
+    let mut control = Vec::with_capacity(n + 2);
+    control.push(mirror(&points[0], &points[1]));
+    control.extend(points.iter().cloned());
+    control.push(mirror(&points[n - 1], &points[n - 2]));
+
+    let mut curve = vec![points[0].clone()];
+    for window in control.windows(4) {
+        let [p0, p1, p2, p3] = [&window[0], &window[1], &window[2], &window[3]];
+        // Knot spacing is the square root of the chord length; repeated points get a tiny gap
+        let knot = |a: &Point, b: &Point| euclidean_distance(a, b).sqrt().max(1e-12);
+        let t1 = knot(p0, p1);
+        let t2 = t1 + knot(p1, p2);
+        let t3 = t2 + knot(p2, p3);
+
+        for step in 1..samples_per_segment {
+            let t = t1 + (t2 - t1) * step as f64 / samples_per_segment as f64;
+            let lerp = |a: &Point, b: &Point, ta: f64, tb: f64| Point {
+                x: ((tb - t) * a.x + (t - ta) * b.x) / (tb - ta),
+                y: ((tb - t) * a.y + (t - ta) * b.y) / (tb - ta),
+                z: mix_z(a, (tb - t) / (tb - ta), b, (t - ta) / (tb - ta)),
+            };
+            let a1 = lerp(p0, p1, 0.0, t1);
+            let a2 = lerp(p1, p2, t1, t2);
+            let a3 = lerp(p2, p3, t2, t3);
+            let b1 = lerp(&a1, &a2, 0.0, t2);
+            let b2 = lerp(&a2, &a3, t1, t3);
+            curve.push(lerp(&b1, &b2, t1, t2));
+        }
+        curve.push(p2.clone());
+    }
+    curve
+}
+
+/// Core business logic: post-process a path into drawable geometry
+/// Shortcutting runs first, then simplification, then smoothing. Neither adds a
+/// straight stretch that crosses `obstacles`, but smoothed curves may cut into them
+pub fn post_process(
+    points: &[Point],
+    path: &[usize],
+    options: &PathPostProcessing,
+    obstacles: &[Region],
+) -> Result<PathGeometry, String> {
+    let mut waypoints = path.to_vec();
+
+    if options.shortcut.unwrap_or(false) {
+        waypoints = shortcut(points, &waypoints, obstacles);
+    }
+    if let Some(tolerance) = options.simplify_tolerance {
+        if !tolerance.is_finite() || tolerance < 0.0 {
+            return Err("Simplify tolerance must be a non-negative number".to_string());
+        }
+        waypoints = simplify(points, &waypoints, tolerance, obstacles);
+    }
+
+    let positions: Vec<Point> = waypoints.iter().map(|&i| points[i].clone()).collect();
+    let curve = match options.smoothing {
+        None => positions,
+        Some(Smoothing::Chaikin { iterations }) => {
+            if iterations > MAX_CHAIKIN_ITERATIONS {
+                return Err(format!(
+                    "Chaikin smoothing allows at most {} iterations",
+                    MAX_CHAIKIN_ITERATIONS
+                ));
+            }
+            chaikin(&positions, iterations)
+        }
+        Some(Smoothing::CatmullRom {
+            samples_per_segment,
+        }) => {
+            if !(1..=MAX_SAMPLES_PER_SEGMENT).contains(&samples_per_segment) {
+                return Err(format!(
+                    "Catmull-Rom smoothing needs between 1 and {} samples per segment",
+                    MAX_SAMPLES_PER_SEGMENT
+                ));
+            }
+            catmull_rom(&positions, samples_per_segment)
+        }
+    };
+
+    Ok(PathGeometry {
+        waypoints,
+        length: polyline_length(&curve),
+        points: curve,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point { x, y, z: None }
+    }
+
+    /// A staircase from (0, 0) to (4, 4), like a path across a grid
+    fn staircase() -> (Vec<Point>, Vec<usize>) {
+        let points: Vec<Point> = (0..9)
+            .map(|i| p(((i + 1) / 2) as f64, (i / 2) as f64))
+            .collect();
+        let path = (0..9).collect();
+        (points, path)
+    }
+
+    #[test]
+    fn test_simplify_respects_tolerance_and_obstacles() {
+        let (points, path) = staircase();
+        assert_eq!(simplify(&points, &path, 1.0, &[]), vec![0, 8]);
+        assert_eq!(simplify(&points, &path, 0.0, &[]), path);
+
+        // A box on the diagonal, clear of the staircase, forces the line to bend
+        let obstacles = [Region::Rect {
+            min: p(1.4, 1.4),
+            max: p(1.6, 1.6),
+        }];
+        let kept = simplify(&points, &path, 1.0, &obstacles);
+        assert!(kept.len() > 2);
+        for pair in kept.windows(2) {
+            assert!(clear(&obstacles, &points[pair[0]], &points[pair[1]]));
+        }
+    }
+
+    #[test]
+    fn test_shortcut_jumps_to_farthest_visible_node() {
+        // Detour around a wall between (1, -1) and (1, 1)
+        let points = vec![
+            p(0.0, 0.0),
+            p(0.0, 2.0),
+            p(2.0, 2.0),
+            p(2.0, 0.0),
+            p(3.0, 0.0),
+        ];
+        let wall = Region::Rect {
+            min: p(0.9, -1.0),
+            max: p(1.1, 1.0),
+        };
+        let path = vec![0, 1, 2, 3, 4];
+
+        assert_eq!(shortcut(&points, &path, &[]), vec![0, 4]);
+        assert_eq!(shortcut(&points, &path, &[wall]), vec![0, 1, 4]);
+    }
+
+    #[test]
+    fn test_chaikin_and_catmull_rom_keep_endpoints() {
+        let corner = vec![p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)];
+
+        let cut = chaikin(&corner, 2);
+        assert_eq!(cut.len(), 6);
+        assert_eq!((cut[0].x, cut[0].y), (0.0, 0.0));
+        assert_eq!((cut[5].x, cut[5].y), (1.0, 1.0));
+        assert!(polyline_length(&cut) < 2.0);
+
+        let spline = catmull_rom(&corner, 4);
+        assert_eq!(spline.len(), 9);
+        // The spline passes through every waypoint
+        for (i, waypoint) in corner.iter().enumerate() {
+            assert!(euclidean_distance(&spline[i * 4], waypoint) < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_post_process_rejects_runaway_smoothing() {
+        let (points, path) = staircase();
+        let options = PathPostProcessing {
+            shortcut: None,
+            simplify_tolerance: Some(0.1),
+            obstacles: None,
+            smoothing: Some(Smoothing::Chaikin { iterations: 40 }),
+        };
+        assert_eq!(
+            post_process(&points, &path, &options, &[]).unwrap_err(),
+            "Chaikin smoothing allows at most 10 iterations"
+        );
+    }
+}
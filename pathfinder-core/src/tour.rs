@@ -0,0 +1,360 @@
+use crate::{build_graph, reconstruct_path};
+use petgraph::algo::dijkstra;
+use petgraph::graph::{Graph, NodeIndex};
+use shared_types::router::Progress;
+use shared_types::{Edge, Point, TourResult};
+use std::collections::HashMap;
+
+/// Minimum gain for a move to count as an improvement, guards against float noise
+const IMPROVEMENT_EPSILON: f64 = 1e-9;
+
+/// Shortest-path distances between every pair of stops, plus the Dijkstra
+/// results needed to expand a tour leg back into a full point path
+struct StopDistances {
+    matrix: Vec<Vec<f64>>,
+    searches: Vec<HashMap<NodeIndex, f64>>,
+}
+
+fn stop_distances(
+    graph: &Graph<(), f64, petgraph::Undirected>,
+    nodes: &[NodeIndex],
+    stops: &[usize],
+    on_progress: &mut impl FnMut(Progress),
+) -> Result<StopDistances, String> {
+    let mut matrix = Vec::with_capacity(stops.len());
+    let mut searches = Vec::with_capacity(stops.len());
+
+    for (i, &from) in stops.iter().enumerate() {
+        let result = dijkstra(graph, nodes[from], None, |e| *e.weight());
+        let row = stops
+            .iter()
+            .map(|&to| {
+                result
+                    .get(&nodes[to])
+                    .copied()
+                    .ok_or_else(|| format!("Stop {} is unreachable from stop {}", to, from))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        matrix.push(row);
+        searches.push(result);
+        on_progress(Progress::counted("distances", i + 1, stops.len()));
+    }
+
+    Ok(StopDistances { matrix, searches })
+}
+
+/// Pure function to compute the length of a closed tour over stop positions
+pub fn tour_length(matrix: &[Vec<f64>], order: &[usize]) -> f64 {
+    (0..order.len())
+        .map(|i| matrix[order[i]][order[(i + 1) % order.len()]])
+        .sum()
+}
+
+/// Pure function to build a tour by always visiting the closest unvisited stop
+/// The tour starts at stop position 0
+pub fn nearest_neighbour_tour(matrix: &[Vec<f64>]) -> Vec<usize> {
+    if matrix.is_empty() {
+        return Vec::new();
+    }
+
+    let mut visited = vec![false; matrix.len()];
+    let mut order = vec![0];
+    visited[0] = true;
+
+    while order.len() < matrix.len() {
+        let current = *order.last().unwrap();
+        let next = (0..matrix.len())
+            .filter(|&i| !visited[i])
+            .min_by(|&a, &b| matrix[current][a].total_cmp(&matrix[current][b]))
+            .unwrap();
+        visited[next] = true;
+        order.push(next);
+    }
+
+    order
+}
+
+/// One pass of 2-opt: reverses the first segment whose reversal shortens the tour
+/// Returns true if the tour was improved
+pub fn two_opt_pass(matrix: &[Vec<f64>], order: &mut [usize]) -> bool {
+    let n = order.len();
+    if n < 4 {
+        return false;
+    }
+
+    // Position 0 stays fixed so the tour keeps starting at the first stop
+    for i in 1..n - 1 {
+        for j in i + 1..n {
+            let a = order[i - 1];
+            let b = order[i];
+            let c = order[j];
+            let d = order[(j + 1) % n];
+
+            let delta = matrix[a][c] + matrix[b][d] - matrix[a][b] - matrix[c][d];
+            if delta < -IMPROVEMENT_EPSILON {
+                order[i..=j].reverse();
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// One pass of or-opt: moves the first segment of up to three stops whose
+/// relocation shortens the tour. Returns true if the tour was improved
+pub fn or_opt_pass(matrix: &[Vec<f64>], order: &mut Vec<usize>) -> bool {
+    let n = order.len();
+    if n < 4 {
+        return false;
+    }
+
+    for segment_len in 1..=3.min(n - 2) {
+        for i in 1..=n - segment_len {
+            let first = order[i];
+            let last = order[i + segment_len - 1];
+            let prev = order[i - 1];
+            let next = order[(i + segment_len) % n];
+
+            let removal_gain = matrix[prev][first] + matrix[last][next] - matrix[prev][next];
+
+            let mut remaining: Vec<usize> = order[..i].to_vec();
+            remaining.extend_from_slice(&order[i + segment_len..]);
+
+            for k in 0..remaining.len() {
+                let p = remaining[k];
+                let q = remaining[(k + 1) % remaining.len()];
+                let insertion_cost = matrix[p][first] + matrix[last][q] - matrix[p][q];
+
+                if insertion_cost < removal_gain - IMPROVEMENT_EPSILON {
+                    let segment = order[i..i + segment_len].to_vec();
+                    remaining.splice(k + 1..k + 1, segment);
+                    *order = remaining;
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn to_tour_result(
+    graph: &Graph<(), f64, petgraph::Undirected>,
+    nodes: &[NodeIndex],
+    stops: &[usize],
+    distances: &StopDistances,
+    order: &[usize],
+    stage: &str,
+    iteration: usize,
+) -> TourResult {
+    let mut path = vec![stops[order[0]]];
+
+    for i in 0..order.len() {
+        let from = order[i];
+        let to = order[(i + 1) % order.len()];
+        let leg = reconstruct_path(
+            graph,
+            &distances.searches[from],
+            nodes[stops[from]],
+            nodes[stops[to]],
+        );
+        path.extend(leg.iter().skip(1).map(|n| n.index()));
+    }
+
+    TourResult {
+        tour: order.iter().map(|&i| stops[i]).collect(),
+        path,
+        distance: tour_length(&distances.matrix, order),
+        stage: stage.to_string(),
+        iteration,
+    }
+}
+
+/// Core business logic: approximate a round trip over `stops`
+/// Builds a nearest-neighbour tour, then improves it with 2-opt (and or-opt
+/// when enabled) until no move helps. `on_progress` reports the distance matrix
+/// computation; `on_improvement` is called with the initial tour and again after
+/// every improving move. `is_cancelled` is checked between improvement passes,
+/// which stop early once it returns true; the best tour so far is returned
+pub fn solve_tour(
+    points: &[Point],
+    edges: &[Edge],
+    stops: &[usize],
+    or_opt: bool,
+    mut on_progress: impl FnMut(Progress),
+    mut on_improvement: impl FnMut(TourResult),
+    is_cancelled: impl Fn() -> bool,
+) -> Result<TourResult, String> {
+    if stops.is_empty() {
+        return Err("At least one stop is required".to_string());
+    }
+    if let Some(&invalid) = stops.iter().find(|&&stop| stop >= points.len()) {
+        return Err(format!("Stop index {} is out of bounds", invalid));
+    }
+
+    let (graph, nodes) = build_graph(points, edges);
+    let distances = stop_distances(&graph, &nodes, stops, &mut on_progress)?;
+
+    let mut order = nearest_neighbour_tour(&distances.matrix);
+    let mut iteration = 0;
+    let mut best = to_tour_result(
+        &graph,
+        &nodes,
+        stops,
+        &distances,
+        &order,
+        "nearest_neighbour",
+        iteration,
+    );
+    on_improvement(best.clone());
+
+    while !is_cancelled() {
+        let stage = if two_opt_pass(&distances.matrix, &mut order) {
+            "two_opt"
+        } else if or_opt && or_opt_pass(&distances.matrix, &mut order) {
+            "or_opt"
+        } else {
+            break;
+        };
+
+        iteration += 1;
+        best = to_tour_result(&graph, &nodes, stops, &distances, &order, stage, iteration);
+        on_improvement(best.clone());
+    }
+
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four corners of a unit square connected as a complete graph
+    fn square() -> (Vec<Point>, Vec<Edge>) {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 0.0,
+                y: 1.0,
+                z: None,
+            },
+        ];
+        let mut edges = Vec::new();
+        for from in 0..points.len() {
+            for to in from + 1..points.len() {
+                edges.push(Edge { from, to });
+            }
+        }
+        (points, edges)
+    }
+
+    #[test]
+    fn test_two_opt_removes_crossing() {
+        let (points, _) = square();
+        let matrix: Vec<Vec<f64>> = points
+            .iter()
+            .map(|a| {
+                points
+                    .iter()
+                    .map(|b| crate::euclidean_distance(a, b))
+                    .collect()
+            })
+            .collect();
+
+        // 0 -> 2 -> 1 -> 3 crosses itself through the diagonals
+        let mut order = vec![0, 2, 1, 3];
+        assert!(two_opt_pass(&matrix, &mut order));
+        assert!((tour_length(&matrix, &order) - 4.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_solve_tour_streams_improvements() {
+        let (points, edges) = square();
+        let mut emitted = Vec::new();
+
+        let mut reported = Vec::new();
+
+        let result = solve_tour(
+            &points,
+            &edges,
+            &[0, 2, 1, 3],
+            true,
+            |progress| reported.push(progress),
+            |tour| emitted.push(tour),
+            || false,
+        )
+        .unwrap();
+
+        assert!((result.distance - 4.0).abs() < 1e-10);
+        assert_eq!(result.tour[0], 0);
+        assert_eq!(result.path.first(), result.path.last());
+        assert_eq!(emitted[0].stage, "nearest_neighbour");
+        assert_eq!(reported.len(), 4);
+        assert_eq!(reported.last().unwrap().fraction, 1.0);
+        assert!(emitted
+            .windows(2)
+            .all(|pair| pair[1].distance < pair[0].distance));
+    }
+
+    #[test]
+    fn test_solve_tour_unreachable_stop() {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 5.0,
+                y: 5.0,
+                z: None,
+            },
+        ];
+        let edges = vec![Edge { from: 0, to: 1 }];
+
+        let result = solve_tour(&points, &edges, &[0, 1, 2], false, |_| {}, |_| {}, || false);
+        assert_eq!(result.unwrap_err(), "Stop 2 is unreachable from stop 0");
+    }
+
+    #[test]
+    fn test_solve_tour_stops_when_cancelled() {
+        let (points, edges) = square();
+        let mut emitted = Vec::new();
+
+        // The crossing start tour would be improved, but the caller gave up
+        let result = solve_tour(
+            &points,
+            &edges,
+            &[0, 2, 1, 3],
+            true,
+            |_| {},
+            |tour| emitted.push(tour),
+            || true,
+        )
+        .unwrap();
+
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(result.stage, "nearest_neighbour");
+        assert_eq!(result.iteration, 0);
+    }
+}
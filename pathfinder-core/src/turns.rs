@@ -0,0 +1,421 @@
+use crate::elevation::elevation_change;
+use crate::{compute_shortest_path, euclidean_distance, PathConstraints};
+use shared_types::{Edge, PathResult, Point, TurnPenalty, TurnRestriction};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Turns at least this sharp count as U-turns for `TurnPenalty::u_turn_cost`
+const U_TURN_DEGREES: f64 = 179.0;
+
+/// Pure function to turn a serialized `TurnPenalty` into a penalty on the turn angle
+pub fn turn_penalty_fn(penalty: &TurnPenalty) -> Result<impl Fn(f64) -> f64, String> {
+    let per_degree = penalty.per_degree;
+    let u_turn_cost = penalty.u_turn_cost.unwrap_or(0.0);
+    if !per_degree.is_finite() || per_degree < 0.0 {
+        return Err("Turn penalty per degree must be a non-negative number".to_string());
+    }
+    if !u_turn_cost.is_finite() || u_turn_cost < 0.0 {
+        return Err("U-turn cost must be a non-negative number".to_string());
+    }
+    Ok(move |degrees| {
+        let extra = if degrees >= U_TURN_DEGREES {
+            u_turn_cost
+        } else {
+            0.0
+        };
+        per_degree * degrees + extra
+    })
+}
+
+/// Pure function to compute the turn angle at `via` in degrees
+/// 0 is straight ahead and 180 is turning back the way you came
+pub fn turn_angle(from: &Point, via: &Point, to: &Point) -> f64 {
+    let (ax, ay) = (via.x - from.x, via.y - from.y);
+    let (bx, by) = (to.x - via.x, to.y - via.y);
+    let norms = (ax * ax + ay * ay).sqrt() * (bx * bx + by * by).sqrt();
+    if norms == 0.0 {
+        return 0.0;
+    }
+    ((ax * bx + ay * by) / norms)
+        .clamp(-1.0, 1.0)
+        .acos()
+        .to_degrees()
+}
+
+/// Min-heap entry over directed edge traversals
+#[derive(PartialEq)]
+struct State {
+    cost: f64,
+    traversal: usize,
+}
+
+impl Eq for State {}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Traversal `2 * i` walks edge `i` from -> to, `2 * i + 1` walks it to -> from
+fn tail(edges: &[Edge], traversal: usize) -> usize {
+    let edge = &edges[traversal / 2];
+    if traversal.is_multiple_of(2) {
+        edge.from
+    } else {
+        edge.to
+    }
+}
+
+fn head(edges: &[Edge], traversal: usize) -> usize {
+    let edge = &edges[traversal / 2];
+    if traversal.is_multiple_of(2) {
+        edge.to
+    } else {
+        edge.from
+    }
+}
+
+fn validate_restrictions(edges: &[Edge], banned: &[TurnRestriction]) -> Result<(), String> {
+    for (i, turn) in banned.iter().enumerate() {
+        let touches = |edge: usize| {
+            edges
+                .get(edge)
+                .is_some_and(|e| e.from == turn.via_node || e.to == turn.via_node)
+        };
+        if !touches(turn.from_edge) || !touches(turn.to_edge) {
+            return Err(format!(
+                "Banned turn {} does not connect edges {} and {} at point {}",
+                i, turn.from_edge, turn.to_edge, turn.via_node
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Core business logic: edge-based shortest path with turn costs and bans
+/// Runs Dijkstra over directed edge traversals (the line graph), so the cost of
+/// entering an edge can depend on the edge it came from. `turn_cost` maps a turn
+/// angle in degrees to a penalty; `PathResult::turn_cost` reports its total.
+/// `slope_penalty` weighs elevation change as in `elevation::edge_cost`
+#[allow(clippy::too_many_arguments)]
+pub fn compute_turn_aware_path(
+    points: &[Point],
+    edges: &[Edge],
+    start_idx: usize,
+    end_idx: usize,
+    constraints: &PathConstraints,
+    slope_penalty: f64,
+    turn_cost: impl Fn(f64) -> f64,
+    banned: &[TurnRestriction],
+) -> Result<PathResult, String> {
+    validate_restrictions(edges, banned)?;
+    let allowed = constraints.allowed_edge_mask(points, edges)?;
+    constraints.check_endpoints(points, start_idx, end_idx)?;
+
+    if start_idx == end_idx {
+        return Ok(PathResult {
+            path: vec![start_idx],
+            distance: 0.0,
+            turn_cost: Some(0.0),
+            geometry: None,
+        });
+    }
+
+    let banned: HashSet<(usize, usize, usize)> = banned
+        .iter()
+        .map(|t| (t.from_edge, t.via_node, t.to_edge))
+        .collect();
+    let lengths: Vec<f64> = edges
+        .iter()
+        .map(|e| euclidean_distance(&points[e.from], &points[e.to]))
+        .collect();
+    let climbs: Vec<f64> = edges
+        .iter()
+        .map(|e| slope_penalty * elevation_change(&points[e.from], &points[e.to]).abs())
+        .collect();
+
+    // Traversals leaving each point
+    let mut leaving: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+    for (i, edge) in edges.iter().enumerate().filter(|&(i, _)| allowed[i]) {
+        leaving[edge.from].push(2 * i);
+        leaving[edge.to].push(2 * i + 1);
+    }
+
+    let mut cost = vec![f64::INFINITY; edges.len() * 2];
+    let mut previous: Vec<Option<usize>> = vec![None; edges.len() * 2];
+    let mut heap = BinaryHeap::new();
+
+    for &traversal in &leaving[start_idx] {
+        let first = lengths[traversal / 2] + climbs[traversal / 2];
+        if first < cost[traversal] {
+            cost[traversal] = first;
+            heap.push(State {
+                cost: first,
+                traversal,
+            });
+        }
+    }
+
+    let mut arrival = None;
+    while let Some(State {
+        cost: current,
+        traversal,
+    }) = heap.pop()
+    {
+        if current > cost[traversal] {
+            continue;
+        }
+        let via = head(edges, traversal);
+        if via == end_idx {
+            arrival = Some(traversal);
+            break;
+        }
+
+        let from = &points[tail(edges, traversal)];
+        for &next in &leaving[via] {
+            if banned.contains(&(traversal / 2, via, next / 2)) {
+                continue;
+            }
+            let angle = turn_angle(from, &points[via], &points[head(edges, next)]);
+            let candidate = current + lengths[next / 2] + climbs[next / 2] + turn_cost(angle);
+            if candidate < cost[next] {
+                cost[next] = candidate;
+                previous[next] = Some(traversal);
+                heap.push(State {
+                    cost: candidate,
+                    traversal: next,
+                });
+            }
+        }
+    }
+
+    let Some(last) = arrival else {
+        if compute_shortest_path(points, edges, start_idx, end_idx).is_ok() {
+            return Err(format!(
+                "No path found: turn restrictions and constraints ({}) block every route from {} to {}",
+                constraints.describe(),
+                start_idx,
+                end_idx
+            ));
+        }
+        return Err("No path found".to_string());
+    };
+
+    let mut traversals = vec![last];
+    while let Some(prev) = previous[*traversals.last().unwrap()] {
+        traversals.push(prev);
+    }
+    traversals.reverse();
+
+    let mut path = vec![start_idx];
+    path.extend(traversals.iter().map(|&t| head(edges, t)));
+    let distance: f64 = traversals.iter().map(|&t| lengths[t / 2]).sum();
+    let climb: f64 = traversals.iter().map(|&t| climbs[t / 2]).sum();
+
+    Ok(PathResult {
+        path,
+        distance,
+        turn_cost: Some(cost[last] - distance - climb),
+        geometry: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two routes from 0 to 3: a staircase 0-1-2-3 with two right-angle turns,
+    /// and a slightly longer route 0-1-4-3 with a single turn
+    fn staircase() -> (Vec<Point>, Vec<Edge>) {
+        let points = vec![
+            Point {
+                x: 0.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 0.0,
+                z: None,
+            },
+            Point {
+                x: 1.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: 1.0,
+                z: None,
+            },
+            Point {
+                x: 2.0,
+                y: -0.05,
+                z: None,
+            },
+        ];
+        let edges = vec![
+            Edge { from: 0, to: 1 },
+            Edge { from: 1, to: 2 },
+            Edge { from: 2, to: 3 },
+            Edge { from: 1, to: 4 },
+            Edge { from: 4, to: 3 },
+        ];
+        (points, edges)
+    }
+
+    #[test]
+    fn test_turn_angle() {
+        let a = Point {
+            x: 0.0,
+            y: 0.0,
+            z: None,
+        };
+        let b = Point {
+            x: 1.0,
+            y: 0.0,
+            z: None,
+        };
+        assert!(
+            turn_angle(
+                &a,
+                &b,
+                &Point {
+                    x: 2.0,
+                    y: 0.0,
+                    z: None
+                }
+            )
+            .abs()
+                < 1e-10
+        );
+        assert!(
+            (turn_angle(
+                &a,
+                &b,
+                &Point {
+                    x: 1.0,
+                    y: 1.0,
+                    z: None
+                }
+            ) - 90.0)
+                .abs()
+                < 1e-10
+        );
+        assert!((turn_angle(&a, &b, &a) - 180.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_banned_turn_forces_detour() {
+        let (points, edges) = staircase();
+        let banned = vec![TurnRestriction {
+            from_edge: 0,
+            via_node: 1,
+            to_edge: 1,
+        }];
+
+        let result = compute_turn_aware_path(
+            &points,
+            &edges,
+            0,
+            3,
+            &PathConstraints::default(),
+            0.0,
+            |_| 0.0,
+            &banned,
+        )
+        .unwrap();
+        assert_eq!(result.path, vec![0, 1, 4, 3]);
+    }
+
+    #[test]
+    fn test_turn_penalty_prefers_fewer_turns() {
+        let (points, edges) = staircase();
+
+        let plain = compute_turn_aware_path(
+            &points,
+            &edges,
+            0,
+            3,
+            &PathConstraints::default(),
+            0.0,
+            |_| 0.0,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(plain.path, vec![0, 1, 2, 3]);
+        assert_eq!(plain.turn_cost, Some(0.0));
+
+        let penalised = compute_turn_aware_path(
+            &points,
+            &edges,
+            0,
+            3,
+            &PathConstraints::default(),
+            0.0,
+            turn_penalty_fn(&TurnPenalty {
+                per_degree: 0.01,
+                u_turn_cost: None,
+            })
+            .unwrap(),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(penalised.path, vec![0, 1, 4, 3]);
+        assert!(penalised.turn_cost.unwrap() > 0.9);
+    }
+
+    #[test]
+    fn test_rejects_restriction_not_at_shared_point() {
+        let (points, edges) = staircase();
+        let banned = vec![TurnRestriction {
+            from_edge: 0,
+            via_node: 3,
+            to_edge: 2,
+        }];
+
+        let result = compute_turn_aware_path(
+            &points,
+            &edges,
+            0,
+            3,
+            &PathConstraints::default(),
+            0.0,
+            |_| 0.0,
+            &banned,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "Banned turn 0 does not connect edges 0 and 2 at point 3"
+        );
+    }
+
+    #[test]
+    fn test_rejects_negative_or_non_finite_turn_penalties() {
+        let penalty = |per_degree, u_turn_cost| {
+            turn_penalty_fn(&TurnPenalty {
+                per_degree,
+                u_turn_cost,
+            })
+            .map(|_| ())
+        };
+        assert_eq!(penalty(0.5, Some(10.0)), Ok(()));
+        assert_eq!(
+            penalty(-0.5, None).unwrap_err(),
+            "Turn penalty per degree must be a non-negative number"
+        );
+        assert!(penalty(f64::NAN, None).is_err());
+        assert_eq!(
+            penalty(0.5, Some(f64::INFINITY)).unwrap_err(),
+            "U-turn cost must be a non-negative number"
+        );
+        assert!(penalty(0.5, Some(-1.0)).is_err());
+    }
+}
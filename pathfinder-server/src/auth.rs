@@ -0,0 +1,253 @@
+use crate::config::ServerConfig;
+use hmac::{Hmac, Mac};
+use hyper::header::AUTHORIZATION;
+use hyper::{HeaderMap, Uri};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Checks the bearer tokens clients present, naming the user each one belongs to
+/// The server asks once per WebSocket handshake and once per HTTP request
+pub trait Authenticator: Send + Sync {
+    /// The user `token` belongs to, or why it is refused
+    fn authenticate(&self, token: &str) -> Result<String, String>;
+}
+
+/// Builds the authenticator `config` names, if any
+pub fn load(config: &ServerConfig) -> Result<Option<Arc<dyn Authenticator>>, String> {
+    if let Some(path) = &config.auth_token_file {
+        return Ok(Some(Arc::new(TokenFile::load(path)?)));
+    }
+    if let Some(path) = &config.auth_hmac_secret_file {
+        return Ok(Some(Arc::new(HmacTokens::load(path)?)));
+    }
+    Ok(None)
+}
+
+/// Finds the request's token and has `authenticator` check it
+/// The token comes from an `Authorization: Bearer` header or, for browsers that
+/// cannot set headers on a WebSocket, a `token` query parameter
+pub fn authenticate(
+    authenticator: &dyn Authenticator,
+    headers: &HeaderMap,
+    uri: &Uri,
+) -> Result<String, String> {
+    let token = bearer_token(headers, uri).ok_or("Missing bearer token")?;
+    authenticator.authenticate(&token)
+}
+
+fn bearer_token(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    if let Some(value) = headers.get(AUTHORIZATION) {
+        let (scheme, token) = value.to_str().ok()?.split_once(' ')?;
+        return scheme
+            .eq_ignore_ascii_case("bearer")
+            .then(|| token.trim().to_string());
+    }
+    uri.query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .and_then(percent_decode)
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            b'+' => {
+                bytes.push(b' ');
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Fixed tokens read from a file, one `<user> <token>` pair per line
+/// Blank lines and lines starting with `#` are skipped
+pub struct TokenFile {
+    users: HashMap<String, String>,
+}
+
+impl TokenFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read token file {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid token file {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut users = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [user, token] = fields[..] else {
+                return Err(format!("line {}: expected `<user> <token>`", i + 1));
+            };
+            if users.insert(token.to_string(), user.to_string()).is_some() {
+                return Err(format!("line {}: token is already in use", i + 1));
+            }
+        }
+        Ok(Self { users })
+    }
+}
+
+impl Authenticator for TokenFile {
+    fn authenticate(&self, token: &str) -> Result<String, String> {
+        self.users
+            .get(token)
+            .cloned()
+            .ok_or_else(|| "Unknown token".to_string())
+    }
+}
+
+/// Tokens signed with a shared secret, so any number can be issued without a
+/// server change: `<user>.<expires>.<signature>`, where `expires` is a Unix
+/// timestamp and `signature` the hex HMAC-SHA256 of `<user>.<expires>`
+pub struct HmacTokens {
+    secret: Vec<u8>,
+}
+
+impl HmacTokens {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret }
+    }
+
+    /// Reads the secret from a file; a trailing newline is not part of it
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read HMAC secret file {}: {}", path.display(), e))?;
+        let secret = text.trim_end_matches(['\r', '\n']);
+        if secret.is_empty() {
+            return Err(format!("HMAC secret file {} is empty", path.display()));
+        }
+        Ok(Self::new(secret.as_bytes().to_vec()))
+    }
+
+    /// Issues a token for `user` that is good until the Unix time `expires`
+    pub fn sign(&self, user: &str, expires: u64) -> String {
+        let payload = format!("{}.{}", user, expires);
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+impl Authenticator for HmacTokens {
+    fn authenticate(&self, token: &str) -> Result<String, String> {
+        let malformed = || "Malformed token".to_string();
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+        let (user, expires) = payload.rsplit_once('.').ok_or_else(malformed)?;
+        let signature = hex::decode(signature).map_err(|_| malformed())?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| "Invalid token signature".to_string())?;
+
+        let expires: u64 = expires.parse().map_err(|_| malformed())?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if expires <= now {
+            return Err("Token expired".to_string());
+        }
+        if user.is_empty() {
+            return Err(malformed());
+        }
+        Ok(user.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_token(header: Option<&str>, uri: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        if let Some(header) = header {
+            headers.insert(AUTHORIZATION, header.parse().unwrap());
+        }
+        bearer_token(&headers, &uri.parse().unwrap())
+    }
+
+    #[test]
+    fn test_finds_bearer_token_in_header_or_query() {
+        assert_eq!(
+            request_token(Some("Bearer abc"), "/?token=ignored"),
+            Some("abc".to_string())
+        );
+        assert_eq!(request_token(Some("Basic abc"), "/"), None);
+        assert_eq!(
+            request_token(None, "/ws?x=1&token=a%2Eb.c"),
+            Some("a.b.c".to_string())
+        );
+        assert_eq!(request_token(None, "/ws?x=1"), None);
+    }
+
+    #[test]
+    fn test_token_file() {
+        let tokens = TokenFile::parse("# ops\nalice s3cret\n\nbob hunter2\n").unwrap();
+        assert_eq!(tokens.authenticate("hunter2"), Ok("bob".to_string()));
+        assert_eq!(
+            tokens.authenticate("alice"),
+            Err("Unknown token".to_string())
+        );
+
+        assert_eq!(
+            TokenFile::parse("alice s3cret\nbob\n").err().unwrap(),
+            "line 2: expected `<user> <token>`"
+        );
+        assert_eq!(
+            TokenFile::parse("alice s3cret\nbob s3cret\n")
+                .err()
+                .unwrap(),
+            "line 2: token is already in use"
+        );
+    }
+
+    #[test]
+    fn test_hmac_tokens() {
+        let tokens = HmacTokens::new(b"secret".to_vec());
+        let token = tokens.sign("carol.ops", u64::MAX);
+        assert_eq!(tokens.authenticate(&token), Ok("carol.ops".to_string()));
+
+        let forged = HmacTokens::new(b"guess".to_vec()).sign("carol.ops", u64::MAX);
+        assert_eq!(
+            tokens.authenticate(&forged),
+            Err("Invalid token signature".to_string())
+        );
+        let tampered = token.replacen("carol", "mallory", 1);
+        assert_eq!(
+            tokens.authenticate(&tampered),
+            Err("Invalid token signature".to_string())
+        );
+        assert_eq!(
+            tokens.authenticate(&tokens.sign("carol", 1)),
+            Err("Token expired".to_string())
+        );
+        assert_eq!(
+            tokens.authenticate("carol"),
+            Err("Malformed token".to_string())
+        );
+    }
+}
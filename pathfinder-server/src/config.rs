@@ -0,0 +1,531 @@
+use crate::origin;
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use shared_types::router::CallLimits;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Smallest accepted `max_message_size`; anything lower cannot carry a real request
+pub const MIN_MESSAGE_SIZE: usize = 1024;
+
+/// Where session data (cached paths, stored graphs) lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// In-memory storage per connection, dropped when it closes
+    Memory,
+    /// No storage: nothing is cached or kept between calls
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Command-line flags; each one can also be set through its environment variable
+/// Precedence is flag, then environment variable, then config file, then default
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "pathfinder-server",
+    version,
+    about = "WebSocket and HTTP server for the pathfinder protocol"
+)]
+pub struct Cli {
+    /// TOML config file
+    #[arg(short, long, env = "PATHFINDER_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on
+    #[arg(long, env = "PATHFINDER_BIND")]
+    pub bind: Option<IpAddr>,
+    /// Port to listen on; 0 picks a free one
+    #[arg(short, long, env = "PATHFINDER_PORT")]
+    pub port: Option<u16>,
+    /// Connections served at once; further ones are turned away
+    #[arg(long, env = "PATHFINDER_MAX_CONNECTIONS")]
+    pub max_connections: Option<usize>,
+    /// Largest incoming WebSocket message or HTTP request body, in bytes
+    #[arg(long, env = "PATHFINDER_MAX_MESSAGE_SIZE")]
+    pub max_message_size: Option<usize>,
+    /// Largest single WebSocket frame, in bytes; messages may span several frames
+    #[arg(long, env = "PATHFINDER_MAX_FRAME_SIZE")]
+    pub max_frame_size: Option<usize>,
+    /// Most points a call's graph may have; 0 means no limit
+    #[arg(long, env = "PATHFINDER_MAX_POINTS")]
+    pub max_points: Option<usize>,
+    /// Most edges a call's graph may have; 0 means no limit
+    #[arg(long, env = "PATHFINDER_MAX_EDGES")]
+    pub max_edges: Option<usize>,
+    /// Most work a call may be estimated to need, in rough steps; 0 means no limit
+    #[arg(long, env = "PATHFINDER_MAX_WORK")]
+    pub max_work: Option<u64>,
+    #[arg(long, value_enum, env = "PATHFINDER_LOG_LEVEL")]
+    pub log_level: Option<LogLevel>,
+    #[arg(long, value_enum, env = "PATHFINDER_STORAGE")]
+    pub storage: Option<StorageBackend>,
+    /// Seconds in-flight calls get to finish on shutdown before they are aborted
+    #[arg(long, env = "PATHFINDER_DRAIN_TIMEOUT_SECS")]
+    pub drain_timeout_secs: Option<u64>,
+    /// Seconds between keepalive pings; 0 turns them off
+    #[arg(long, env = "PATHFINDER_PING_INTERVAL_SECS")]
+    pub ping_interval_secs: Option<u64>,
+    /// Pings in a row a client may leave unanswered before it is dropped
+    #[arg(long, env = "PATHFINDER_MAX_MISSED_PONGS")]
+    pub max_missed_pongs: Option<u32>,
+    /// Seconds without requests or running calls before a connection is closed; 0 turns this off
+    #[arg(long, env = "PATHFINDER_IDLE_TIMEOUT_SECS")]
+    pub idle_timeout_secs: Option<u64>,
+    /// Seconds a new connection gets to send its request head, and before that to
+    /// finish the TLS handshake, before it is dropped
+    #[arg(long, env = "PATHFINDER_HANDSHAKE_TIMEOUT_SECS")]
+    pub handshake_timeout_secs: Option<u64>,
+    /// File of `<user> <token>` lines; clients must present one of the tokens
+    #[arg(long, env = "PATHFINDER_AUTH_TOKEN_FILE")]
+    pub auth_token_file: Option<PathBuf>,
+    /// File holding the secret that signs `<user>.<expires>.<signature>` tokens
+    #[arg(long, env = "PATHFINDER_AUTH_HMAC_SECRET_FILE")]
+    pub auth_hmac_secret_file: Option<PathBuf>,
+    /// Calls a connection may have running at once; 0 means no limit
+    #[arg(long, env = "PATHFINDER_MAX_IN_FLIGHT")]
+    pub max_in_flight: Option<usize>,
+    /// Calls per second each connection may start; 0 means no limit
+    #[arg(long, env = "PATHFINDER_CONNECTION_RATE")]
+    pub connection_rate: Option<f64>,
+    /// Calls a connection may start at once before `connection_rate` applies
+    #[arg(long, env = "PATHFINDER_CONNECTION_BURST")]
+    pub connection_burst: Option<u32>,
+    /// Calls per second each authenticated user may start; 0 means no limit
+    #[arg(long, env = "PATHFINDER_USER_RATE")]
+    pub user_rate: Option<f64>,
+    /// Calls a user may start at once before `user_rate` applies
+    #[arg(long, env = "PATHFINDER_USER_BURST")]
+    pub user_burst: Option<u32>,
+    /// Comma-separated browser origins allowed to connect, e.g. `https://maps.example.com`;
+    /// with none, every origin may
+    #[arg(long, env = "PATHFINDER_ALLOWED_ORIGINS", value_delimiter = ',')]
+    pub allowed_origins: Option<Vec<String>>,
+    /// PEM certificate chain, leaf first; with `tls_key_file`, clients connect over `wss://` and `https://`
+    #[arg(long, env = "PATHFINDER_TLS_CERT_FILE")]
+    pub tls_cert_file: Option<PathBuf>,
+    /// PEM private key for `tls_cert_file`
+    #[arg(long, env = "PATHFINDER_TLS_KEY_FILE")]
+    pub tls_key_file: Option<PathBuf>,
+    /// Seconds between checks of the TLS files for a renewed certificate; 0 turns this off
+    #[arg(long, env = "PATHFINDER_TLS_RELOAD_SECS")]
+    pub tls_reload_secs: Option<u64>,
+}
+
+/// Server settings, as read from the config file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub max_connections: usize,
+    pub max_message_size: usize,
+    pub max_frame_size: usize,
+    pub max_points: usize,
+    pub max_edges: usize,
+    pub max_work: u64,
+    pub log_level: LogLevel,
+    pub storage: StorageBackend,
+    pub drain_timeout_secs: u64,
+    pub ping_interval_secs: u64,
+    pub max_missed_pongs: u32,
+    pub idle_timeout_secs: u64,
+    pub handshake_timeout_secs: u64,
+    pub auth_token_file: Option<PathBuf>,
+    pub auth_hmac_secret_file: Option<PathBuf>,
+    pub max_in_flight: usize,
+    pub connection_rate: f64,
+    pub connection_burst: u32,
+    pub user_rate: f64,
+    pub user_burst: u32,
+    pub allowed_origins: Vec<String>,
+    pub tls_cert_file: Option<PathBuf>,
+    pub tls_key_file: Option<PathBuf>,
+    pub tls_reload_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let limits = CallLimits::default();
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 10810,
+            max_connections: 1024,
+            max_message_size: 64 << 20,
+            max_frame_size: 16 << 20,
+            max_points: limits.max_points.unwrap_or_default(),
+            max_edges: limits.max_edges.unwrap_or_default(),
+            max_work: limits.max_work.unwrap_or_default(),
+            log_level: LogLevel::Info,
+            storage: StorageBackend::Memory,
+            drain_timeout_secs: 10,
+            ping_interval_secs: 30,
+            max_missed_pongs: 2,
+            idle_timeout_secs: 300,
+            handshake_timeout_secs: 10,
+            auth_token_file: None,
+            auth_hmac_secret_file: None,
+            max_in_flight: 64,
+            connection_rate: 0.0,
+            connection_burst: 10,
+            user_rate: 0.0,
+            user_burst: 10,
+            allowed_origins: Vec::new(),
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_reload_secs: 60,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Builds the config from the file named by `cli`, if any, with the flags on top
+    pub fn load(cli: &Cli) -> Result<Self, String> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    fn apply(&mut self, cli: &Cli) {
+        if let Some(bind) = cli.bind {
+            self.bind = bind;
+        }
+        if let Some(port) = cli.port {
+            self.port = port;
+        }
+        if let Some(max_connections) = cli.max_connections {
+            self.max_connections = max_connections;
+        }
+        if let Some(max_message_size) = cli.max_message_size {
+            self.max_message_size = max_message_size;
+        }
+        if let Some(max_frame_size) = cli.max_frame_size {
+            self.max_frame_size = max_frame_size;
+        }
+        if let Some(max_points) = cli.max_points {
+            self.max_points = max_points;
+        }
+        if let Some(max_edges) = cli.max_edges {
+            self.max_edges = max_edges;
+        }
+        if let Some(max_work) = cli.max_work {
+            self.max_work = max_work;
+        }
+        if let Some(log_level) = cli.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(storage) = cli.storage {
+            self.storage = storage;
+        }
+        if let Some(drain_timeout_secs) = cli.drain_timeout_secs {
+            self.drain_timeout_secs = drain_timeout_secs;
+        }
+        if let Some(ping_interval_secs) = cli.ping_interval_secs {
+            self.ping_interval_secs = ping_interval_secs;
+        }
+        if let Some(max_missed_pongs) = cli.max_missed_pongs {
+            self.max_missed_pongs = max_missed_pongs;
+        }
+        if let Some(idle_timeout_secs) = cli.idle_timeout_secs {
+            self.idle_timeout_secs = idle_timeout_secs;
+        }
+        if let Some(handshake_timeout_secs) = cli.handshake_timeout_secs {
+            self.handshake_timeout_secs = handshake_timeout_secs;
+        }
+        if let Some(path) = &cli.auth_token_file {
+            self.auth_token_file = Some(path.clone());
+        }
+        if let Some(path) = &cli.auth_hmac_secret_file {
+            self.auth_hmac_secret_file = Some(path.clone());
+        }
+        if let Some(max_in_flight) = cli.max_in_flight {
+            self.max_in_flight = max_in_flight;
+        }
+        if let Some(connection_rate) = cli.connection_rate {
+            self.connection_rate = connection_rate;
+        }
+        if let Some(connection_burst) = cli.connection_burst {
+            self.connection_burst = connection_burst;
+        }
+        if let Some(user_rate) = cli.user_rate {
+            self.user_rate = user_rate;
+        }
+        if let Some(user_burst) = cli.user_burst {
+            self.user_burst = user_burst;
+        }
+        if let Some(allowed_origins) = &cli.allowed_origins {
+            self.allowed_origins = allowed_origins.clone();
+        }
+        if let Some(path) = &cli.tls_cert_file {
+            self.tls_cert_file = Some(path.clone());
+        }
+        if let Some(path) = &cli.tls_key_file {
+            self.tls_key_file = Some(path.clone());
+        }
+        if let Some(tls_reload_secs) = cli.tls_reload_secs {
+            self.tls_reload_secs = tls_reload_secs;
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_connections == 0 {
+            return Err("max_connections must be at least 1".to_string());
+        }
+        if self.max_message_size < MIN_MESSAGE_SIZE {
+            return Err(format!(
+                "max_message_size must be at least {} bytes, got {}",
+                MIN_MESSAGE_SIZE, self.max_message_size
+            ));
+        }
+        if self.max_frame_size < MIN_MESSAGE_SIZE {
+            return Err(format!(
+                "max_frame_size must be at least {} bytes, got {}",
+                MIN_MESSAGE_SIZE, self.max_frame_size
+            ));
+        }
+        if self.handshake_timeout_secs == 0 {
+            return Err("handshake_timeout_secs must be at least 1".to_string());
+        }
+        if self.ping_interval_secs > 0 && self.max_missed_pongs == 0 {
+            return Err("max_missed_pongs must be at least 1 when pings are on".to_string());
+        }
+        if self.auth_token_file.is_some() && self.auth_hmac_secret_file.is_some() {
+            return Err("Set only one of auth_token_file and auth_hmac_secret_file".to_string());
+        }
+        for (name, rate, burst) in [
+            ("connection", self.connection_rate, self.connection_burst),
+            ("user", self.user_rate, self.user_burst),
+        ] {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(format!("{}_rate must be 0 or more, got {}", name, rate));
+            }
+            if rate > 0.0 && burst == 0 {
+                return Err(format!(
+                    "{}_burst must be at least 1 when {}_rate is set",
+                    name, name
+                ));
+            }
+        }
+        if self.tls_cert_file.is_some() != self.tls_key_file.is_some() {
+            return Err("Set both tls_cert_file and tls_key_file, or neither".to_string());
+        }
+        for allowed in &self.allowed_origins {
+            origin::validate(allowed)?;
+        }
+        Ok(())
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    /// A frame can never be larger than the message it carries
+    pub fn frame_size(&self) -> usize {
+        self.max_frame_size.min(self.max_message_size)
+    }
+
+    pub fn call_limits(&self) -> CallLimits {
+        CallLimits {
+            max_points: (self.max_points > 0).then_some(self.max_points),
+            max_edges: (self.max_edges > 0).then_some(self.max_edges),
+            max_work: (self.max_work > 0).then_some(self.max_work),
+        }
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+
+    pub fn ping_interval(&self) -> Option<Duration> {
+        (self.ping_interval_secs > 0).then(|| Duration::from_secs(self.ping_interval_secs))
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs))
+    }
+
+    pub fn handshake_timeout(&self) -> Duration {
+        Duration::from_secs(self.handshake_timeout_secs)
+    }
+
+    pub fn tls_reload_interval(&self) -> Option<Duration> {
+        (self.tls_reload_secs > 0).then(|| Duration::from_secs(self.tls_reload_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, text: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pathfinder-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let path = write_config(
+            "override",
+            "port = 9000\nmax_connections = 8\nstorage = \"none\"\n",
+        );
+        let cli = Cli::try_parse_from([
+            "pathfinder-server",
+            "--config",
+            path.to_str().unwrap(),
+            "--port",
+            "9100",
+            "--bind",
+            "0.0.0.0",
+        ])
+        .unwrap();
+
+        let config = ServerConfig::load(&cli).unwrap();
+        assert_eq!(config.addr(), "0.0.0.0:9100".parse().unwrap());
+        assert_eq!(config.max_connections, 8);
+        assert_eq!(config.storage, StorageBackend::None);
+        assert_eq!(
+            config.max_message_size,
+            ServerConfig::default().max_message_size
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_unknown_keys_and_bad_limits() {
+        let path = write_config("unknown", "port = 9000\nmax_conections = 8\n");
+        let cli = Cli {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let error = ServerConfig::load(&cli).unwrap_err();
+        assert!(error.starts_with("Invalid config file"), "{}", error);
+        assert!(
+            error.contains("unknown field `max_conections`"),
+            "{}",
+            error
+        );
+        std::fs::remove_file(path).unwrap();
+
+        let cli = Cli {
+            max_connections: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "max_connections must be at least 1"
+        );
+
+        let cli = Cli {
+            max_message_size: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "max_message_size must be at least 1024 bytes, got 10"
+        );
+
+        let cli = Cli {
+            max_frame_size: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "max_frame_size must be at least 1024 bytes, got 10"
+        );
+
+        let cli = Cli {
+            tls_cert_file: Some(PathBuf::from("server.crt")),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "Set both tls_cert_file and tls_key_file, or neither"
+        );
+
+        let cli = Cli::try_parse_from([
+            "pathfinder-server",
+            "--allowed-origins",
+            "https://maps.example.com,maps.example.com",
+        ])
+        .unwrap();
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "allowed_origins: `maps.example.com` is not an origin like https://example.com"
+        );
+
+        let cli = Cli {
+            user_rate: Some(2.0),
+            user_burst: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "user_burst must be at least 1 when user_rate is set"
+        );
+
+        let cli = Cli {
+            max_missed_pongs: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "max_missed_pongs must be at least 1 when pings are on"
+        );
+
+        let cli = Cli {
+            handshake_timeout_secs: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            ServerConfig::load(&cli).unwrap_err(),
+            "handshake_timeout_secs must be at least 1"
+        );
+    }
+
+    #[test]
+    fn test_missing_config_file_is_reported() {
+        let cli = Cli {
+            config: Some(PathBuf::from("/nonexistent/pathfinder.toml")),
+            ..Default::default()
+        };
+        let error = ServerConfig::load(&cli).unwrap_err();
+        assert!(
+            error.starts_with("Failed to read config file /nonexistent/pathfinder.toml"),
+            "{}",
+            error
+        );
+    }
+}
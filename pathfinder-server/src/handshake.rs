@@ -0,0 +1,119 @@
+use crate::auth::{self, Authenticator};
+use crate::origin::Origins;
+use log::warn;
+use shared_types::codec::Encoding;
+use tokio_tungstenite::tungstenite::handshake::server::{
+    Callback, ErrorResponse, Request, Response,
+};
+use tokio_tungstenite::tungstenite::http::header::{SEC_WEBSOCKET_PROTOCOL, WWW_AUTHENTICATE};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+
+/// Inspects the upgrade request during the WebSocket handshake
+/// Outcomes are written through the borrowed fields, since tungstenite consumes the callback
+pub struct Handshake<'a> {
+    /// Refuses upgrades from browsers on sites that are not allowed
+    pub origins: &'a Origins,
+    /// Refuses the upgrade unless the client presents a token it accepts
+    pub authenticator: Option<&'a dyn Authenticator>,
+    pub encoding: &'a mut Encoding,
+    pub user_id: &'a mut Option<String>,
+}
+
+impl Callback for Handshake<'_> {
+    fn on_request(
+        self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        if let Err(reason) = self.origins.check(request.headers()) {
+            warn!("Refusing WebSocket upgrade: {}", reason);
+            return Err(forbidden(reason));
+        }
+        if let Some(authenticator) = self.authenticator {
+            match auth::authenticate(authenticator, request.headers(), request.uri()) {
+                Ok(user_id) => *self.user_id = Some(user_id),
+                Err(reason) => {
+                    warn!("Refusing WebSocket upgrade: {}", reason);
+                    return Err(unauthorized(reason));
+                }
+            }
+        }
+        *self.encoding = negotiate_encoding(request, &mut response);
+        Ok(response)
+    }
+}
+
+/// `403 Forbidden` with the reason as the body
+fn forbidden(reason: String) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason));
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    response
+}
+
+/// `401 Unauthorized` with the reason as the body
+fn unauthorized(reason: String) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+/// Picks the first subprotocol the client offers that names an encoding
+/// With none offered, or none we know, the connection speaks JSON and the
+/// response names no subprotocol, as RFC 6455 asks
+pub fn negotiate_encoding(request: &Request, response: &mut Response) -> Encoding {
+    let offered = request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim);
+
+    let Some(encoding) = offered.into_iter().find_map(Encoding::from_subprotocol) else {
+        return Encoding::Json;
+    };
+    response.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(encoding.subprotocol()),
+    );
+    encoding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiate(offered: &[&str]) -> (Encoding, Option<String>) {
+        let mut request = Request::builder();
+        for value in offered {
+            request = request.header(SEC_WEBSOCKET_PROTOCOL, *value);
+        }
+        let mut response = Response::default();
+        let encoding = negotiate_encoding(&request.body(()).unwrap(), &mut response);
+        let chosen = response
+            .headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .map(|v| v.to_str().unwrap().to_string());
+        (encoding, chosen)
+    }
+
+    #[test]
+    fn test_negotiates_first_known_subprotocol() {
+        assert_eq!(negotiate(&[]), (Encoding::Json, None));
+        assert_eq!(negotiate(&["graphql-ws"]), (Encoding::Json, None));
+        assert_eq!(
+            negotiate(&["graphql-ws, pathfinder.msgpack", "pathfinder.json"]),
+            (
+                Encoding::MessagePack,
+                Some("pathfinder.msgpack".to_string())
+            )
+        );
+        assert_eq!(
+            negotiate(&["pathfinder.json"]),
+            (Encoding::Json, Some("pathfinder.json".to_string()))
+        );
+    }
+}
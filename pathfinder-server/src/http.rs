@@ -0,0 +1,516 @@
+use crate::auth::{self, Authenticator};
+use crate::config::StorageBackend;
+use crate::origin::Origins;
+use crate::rate_limit::ConnectionLimits;
+use crate::shutdown::{self, ConnectionSummary};
+use crate::transport::{self, HttpSender};
+use crate::{open_session, spawn_call, Server, Session, SHUTDOWN_REASON};
+use bytes::Bytes;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ALLOW,
+    CACHE_CONTROL, CONTENT_TYPE, RETRY_AFTER, VARY, WWW_AUTHENTICATE,
+};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{debug, error, warn};
+use shared_types::codec::Encoding;
+use shared_types::router::{CallLimits, Request, RequestEnum, ResponseEnum, WireResponse};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+/// Most bytes read while looking for the end of the first request head
+const MAX_HEAD: usize = 16 * 1024;
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+/// Reads from `stream` until the first request head is complete, the connection
+/// ends or `MAX_HEAD` bytes have arrived, returning everything read
+pub async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(1024);
+    let mut chunk = [0; 1024];
+    while head.len() < MAX_HEAD && !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&chunk[..read]);
+    }
+    Ok(head)
+}
+
+/// Whether `head` is a complete request asking for a WebSocket upgrade
+/// Anything else, including heads that do not parse, is left to the HTTP server to answer
+pub fn is_websocket_upgrade(head: &[u8]) -> bool {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut request = httparse::Request::new(&mut headers);
+    if !matches!(request.parse(head), Ok(httparse::Status::Complete(_))) {
+        return false;
+    }
+    request.headers.iter().any(|header| {
+        header.name.eq_ignore_ascii_case("upgrade")
+            && std::str::from_utf8(header.value).is_ok_and(|value| {
+                value
+                    .split(',')
+                    .any(|protocol| protocol.trim().eq_ignore_ascii_case("websocket"))
+            })
+    })
+}
+
+/// A stream that hands out bytes already read from it before reading any more
+/// Lets the WebSocket or HTTP server see the head `read_head` consumed
+pub struct Replay<S> {
+    head: Vec<u8>,
+    position: usize,
+    stream: S,
+}
+
+impl<S> Replay<S> {
+    pub fn new(head: Vec<u8>, stream: S) -> Self {
+        Self {
+            head,
+            position: 0,
+            stream,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Replay<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let rest = &this.head[this.position..];
+        if rest.is_empty() {
+            return Pin::new(&mut this.stream).poll_read(cx, buf);
+        }
+        let n = rest.len().min(buf.remaining());
+        buf.put_slice(&rest[..n]);
+        this.position += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Replay<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// What the requests on one HTTP connection share
+struct Connection {
+    addr: SocketAddr,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    origins: Origins,
+    storage: StorageBackend,
+    call_limits: CallLimits,
+    /// One session per user, opened by that user's first request
+    sessions: Mutex<HashMap<Option<String>, Arc<dyn Session>>>,
+    limits: Mutex<ConnectionLimits>,
+    /// Calls started on this connection whose responses are still being relayed;
+    /// a `/rpc/stream` call counts until its last event, not until its headers
+    in_flight: AtomicUsize,
+    max_message_size: usize,
+    shutdown: watch::Receiver<Option<Instant>>,
+    summary: Mutex<ConnectionSummary>,
+}
+
+/// Serves HTTP/1.1 requests on `stream` until the client leaves or the server shuts down
+/// `POST /rpc` answers with every response to the call as a JSON array;
+/// `POST /rpc/stream` sends each one as a server-sent event as it happens.
+/// Browsers on allowed origins get CORS headers, and their preflight
+/// `OPTIONS` requests are answered without authentication.
+/// On shutdown no further requests are read, and calls still running at the
+/// deadline end with `Aborted`
+pub async fn serve<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    stream: S,
+    addr: SocketAddr,
+    server: &Server,
+    mut shutdown: watch::Receiver<Option<Instant>>,
+) -> ConnectionSummary {
+    let connection = Arc::new(Connection {
+        addr,
+        authenticator: server.authenticator.clone(),
+        origins: server.origins.clone(),
+        storage: server.config.storage,
+        call_limits: server.config.call_limits(),
+        sessions: Mutex::default(),
+        limits: Mutex::new(ConnectionLimits::new(
+            &server.config,
+            Arc::clone(&server.users),
+        )),
+        in_flight: AtomicUsize::new(0),
+        max_message_size: server.config.max_message_size,
+        shutdown: shutdown.clone(),
+        summary: Mutex::default(),
+    });
+    let shared = Arc::clone(&connection);
+    let service = service_fn(move |request| route(request, Arc::clone(&shared)));
+    let http = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+    tokio::pin!(http);
+
+    let served = tokio::select! {
+        served = http.as_mut() => served,
+        _ = shutdown::requested(&mut shutdown) => {
+            http.as_mut().graceful_shutdown();
+            http.await
+        }
+    };
+    if let Err(e) = served {
+        error!("HTTP error: {}", e);
+    }
+    let summary = *connection.summary.lock().unwrap();
+    summary
+}
+
+/// Refuses requests from origins that are not allowed and lets browsers on
+/// the others read the response
+async fn route(
+    request: hyper::Request<Incoming>,
+    connection: Arc<Connection>,
+) -> Result<Response<Body>, Infallible> {
+    let origin = match connection.origins.check(request.headers()) {
+        Ok(origin) => origin.cloned(),
+        Err(reason) => {
+            warn!("Refusing HTTP request from {}: {}", connection.addr, reason);
+            return Ok(plain(StatusCode::FORBIDDEN, reason));
+        }
+    };
+    let mut response = handle(request, connection).await?;
+    if let Some(origin) = origin {
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+        headers.insert(
+            ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static("Retry-After"),
+        );
+    }
+    Ok(response)
+}
+
+async fn handle(
+    request: hyper::Request<Incoming>,
+    connection: Arc<Connection>,
+) -> Result<Response<Body>, Infallible> {
+    let stream = match request.uri().path() {
+        "/rpc" => false,
+        "/rpc/stream" => true,
+        _ => return Ok(plain(StatusCode::NOT_FOUND, "Not found".to_string())),
+    };
+    if request.method() == Method::OPTIONS {
+        return Ok(preflight());
+    }
+    if request.method() != Method::POST {
+        let mut response = plain(StatusCode::METHOD_NOT_ALLOWED, "Use POST".to_string());
+        response
+            .headers_mut()
+            .insert(ALLOW, HeaderValue::from_static("POST, OPTIONS"));
+        return Ok(response);
+    }
+    let user_id = match &connection.authenticator {
+        Some(authenticator) => {
+            match auth::authenticate(authenticator.as_ref(), request.headers(), request.uri()) {
+                Ok(user_id) => Some(user_id),
+                Err(reason) => {
+                    warn!("Refusing HTTP request from {}: {}", connection.addr, reason);
+                    let mut response = plain(StatusCode::UNAUTHORIZED, reason);
+                    response
+                        .headers_mut()
+                        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                    return Ok(response);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let limit = connection.max_message_size;
+    let body = match Limited::new(request.into_body(), limit).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return Ok(plain(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request larger than {} bytes", limit),
+            ));
+        }
+        Err(e) => {
+            return Ok(plain(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read request: {}", e),
+            ));
+        }
+    };
+    let request = match Encoding::Json.decode_request(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Failed to parse request: {}", e);
+            return Ok(plain(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid request: {}", e),
+            ));
+        }
+    };
+
+    // Held until the call's responses have all been relayed
+    let mut call = None;
+    if let RequestEnum::Call(id, _) = &request.0 {
+        let admitted = connection.limits.lock().unwrap().admit(
+            user_id.as_deref(),
+            connection.in_flight.load(Ordering::Relaxed),
+        );
+        if let Err(throttled) = admitted {
+            debug!("Refusing call {} from {:?}: {}", id, user_id, throttled);
+            // The same body `/rpc` answers with, holding the one `RateLimited` frame
+            let refusal = WireResponse(*id, ResponseEnum::RateLimited(throttled.to_response()));
+            let body = format!(
+                "[{}]",
+                String::from_utf8_lossy(&transport::encode(Encoding::Json, &refusal))
+            );
+            let mut response = with_body(StatusCode::TOO_MANY_REQUESTS, "application/json", body);
+            if let Some(wait) = throttled.retry_after() {
+                let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, seconds.max(1).into());
+            }
+            return Ok(response);
+        }
+        call = Some(InFlightCall::new(Arc::clone(&connection)));
+    }
+
+    let (id, responses) = start(&connection, user_id, request);
+    if stream {
+        return Ok(events(connection, id, responses, call));
+    }
+
+    let mut frames = Vec::new();
+    relay(&connection, id, responses, |response| {
+        frames.push(transport::encode(Encoding::Json, response));
+    })
+    .await;
+    let body = format!(
+        "[{}]",
+        frames
+            .iter()
+            .map(|frame| String::from_utf8_lossy(frame))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    Ok(with_body(StatusCode::OK, "application/json", body))
+}
+
+/// Runs the request off the connection task, as calls block while they compute
+fn start(
+    connection: &Connection,
+    user_id: Option<String>,
+    request: Request,
+) -> (usize, mpsc::UnboundedReceiver<WireResponse>) {
+    let id = match &request.0 {
+        RequestEnum::Call(id, _) | RequestEnum::Abort(id, _) => *id,
+    };
+    let (responses, received) = mpsc::unbounded_channel();
+    let session = Arc::clone(
+        connection
+            .sessions
+            .lock()
+            .unwrap()
+            .entry(user_id.clone())
+            .or_insert_with(|| {
+                let session_id = format!("http-{}", connection.addr);
+                open_session(
+                    connection.storage,
+                    connection.call_limits,
+                    session_id,
+                    user_id,
+                )
+            }),
+    );
+    spawn_call(session, request, HttpSender::new(responses));
+    (id, received)
+}
+
+/// Counts a call in `Connection::in_flight` until dropped
+struct InFlightCall(Arc<Connection>);
+
+impl InFlightCall {
+    fn new(connection: Arc<Connection>) -> Self {
+        connection.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(connection)
+    }
+}
+
+impl Drop for InFlightCall {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Streams the call's responses as server-sent events, one JSON `WireResponse` per event
+fn events(
+    connection: Arc<Connection>,
+    id: usize,
+    responses: mpsc::UnboundedReceiver<WireResponse>,
+    call: Option<InFlightCall>,
+) -> Response<Body> {
+    let (events, queue) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        relay(&connection, id, responses, |response| {
+            let mut event = b"data: ".to_vec();
+            event.extend(transport::encode(Encoding::Json, response));
+            event.extend_from_slice(b"\n\n");
+            // The client may have gone; the call still runs to its end
+            let _ = events.send(Ok(Frame::data(Bytes::from(event))));
+        })
+        .await;
+        // Before `events` goes and ends the body, so the next request on the
+        // connection no longer counts this call
+        drop(call);
+    });
+
+    let stream = futures_util::stream::unfold(queue, |mut queue| async move {
+        queue.recv().await.map(|event| (event, queue))
+    });
+    let mut response = Response::new(StreamBody::new(stream).boxed_unsync());
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+/// Passes the call's responses to `emit` up to and including its terminal frame
+/// Once shutdown starts the call has until the deadline, after which it ends with `Aborted`
+async fn relay(
+    connection: &Connection,
+    id: usize,
+    mut responses: mpsc::UnboundedReceiver<WireResponse>,
+    mut emit: impl FnMut(&WireResponse),
+) {
+    let mut shutdown = connection.shutdown.clone();
+    let mut deadline = None;
+    loop {
+        let response = tokio::select! {
+            response = responses.recv() => response.unwrap_or_else(|| {
+                WireResponse(id, ResponseEnum::Error("Call ended without a result".to_string()))
+            }),
+            at = shutdown::requested(&mut shutdown), if deadline.is_none() => {
+                deadline = Some(at);
+                continue;
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                connection.summary.lock().unwrap().aborted += 1;
+                emit(&WireResponse(id, ResponseEnum::Aborted(SHUTDOWN_REASON.to_string())));
+                return;
+            }
+        };
+        emit(&response);
+        if transport::is_terminal(&response.1) {
+            if deadline.is_some() {
+                connection.summary.lock().unwrap().drained += 1;
+            }
+            return;
+        }
+    }
+}
+
+/// `204 No Content` naming what a cross-origin `POST` may carry
+/// Browsers send no credentials with a preflight, so it is never authenticated
+fn preflight() -> Response<Body> {
+    let mut response = Response::new(Full::default().boxed_unsync());
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    let headers = response.headers_mut();
+    headers.insert(ALLOW, HeaderValue::from_static("POST, OPTIONS"));
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("POST"),
+    );
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("authorization, content-type"),
+    );
+    headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static("600"));
+    response
+}
+
+fn plain(status: StatusCode, text: String) -> Response<Body> {
+    with_body(status, "text/plain; charset=utf-8", text)
+}
+
+fn with_body(status: StatusCode, content_type: &'static str, body: String) -> Response<Body> {
+    let mut response = Response::new(Full::from(body).boxed_unsync());
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_websocket_upgrades() {
+        let upgrade =
+            b"GET / HTTP/1.1\r\nHost: x\r\nConnection: Upgrade\r\nUpgrade: WebSocket\r\n\r\n";
+        assert!(is_websocket_upgrade(upgrade));
+
+        let post = b"POST /rpc HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\n{}";
+        assert!(!is_websocket_upgrade(post));
+        assert!(!is_websocket_upgrade(
+            b"GET / HTTP/1.1\r\nUpgrade: websocket\r\n"
+        ));
+        assert!(!is_websocket_upgrade(b"\x16\x03\x01 not http"));
+    }
+
+    #[tokio::test]
+    async fn test_replay_hands_back_the_head_first() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let writer = tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            client
+                .write_all(b"POST / HTTP/1.1\r\n\r\nbody")
+                .await
+                .unwrap();
+            client
+        });
+        let mut server = server;
+        let head = read_head(&mut server).await.unwrap();
+        drop(writer.await.unwrap());
+
+        let mut replayed = Vec::new();
+        Replay::new(head, server)
+            .read_to_end(&mut replayed)
+            .await
+            .unwrap();
+        assert_eq!(replayed, b"POST / HTTP/1.1\r\n\r\nbody");
+    }
+}
@@ -1,14 +1,1279 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+pub mod auth;
+pub mod config;
+mod handshake;
+mod http;
+pub mod origin;
+pub mod rate_limit;
+pub mod shutdown;
+mod tls;
+mod transport;
+
+use auth::Authenticator;
+use config::{ServerConfig, StorageBackend};
+use futures_util::StreamExt;
+use handshake::Handshake;
+use http::Replay;
+use log::{debug, error, info, warn};
+use origin::Origins;
+use pathfinder_core::PathfinderHandler;
+use rate_limit::{ConnectionLimits, UserBuckets};
+use shared_types::codec::Encoding;
+use shared_types::receiver::Receiver;
+use shared_types::router::{
+    CallLimits, Request, RequestEnum, ResponseEnum, WireResponse, WireResponseSender,
+};
+use shared_types::storage::{InMemoryStorage, NoStorage, Storage};
+use shutdown::{ConnectionSummary, ShutdownSummary};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tls::Tls;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{Instant, Interval};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_hdr_async_with_config, WebSocketStream};
+use transport::{InFlight, WebSocketSender};
+
+/// Reason sent with the close frame and with calls aborted on shutdown
+const SHUTDOWN_REASON: &str = "Server shutting down";
+
+/// Error sent for a call whose handler panicked; the details only go to the log
+const INTERNAL_ERROR: &str = "Internal error";
+
+/// Reason sent with the close frame when `idle_timeout` passes
+const IDLE_REASON: &str = "Idle timeout";
+
+/// How long a client that sent a close frame has to take the reply
+const CLOSE_REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The server's settings along with what was loaded from the files they name
+pub struct Server {
+    config: ServerConfig,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    origins: Origins,
+    tls: Option<Arc<Tls>>,
+    users: Arc<UserBuckets>,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Result<Self, String> {
+        Ok(Self {
+            authenticator: auth::load(&config)?,
+            origins: Origins::new(&config.allowed_origins),
+            tls: Tls::load(&config)?.map(Arc::new),
+            users: Arc::default(),
+            config,
+        })
+    }
+
+    /// Requires every connection to authenticate with `authenticator`, in place of
+    /// any the config names
+    pub fn with_authenticator(mut self, authenticator: Arc<dyn Authenticator>) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    /// Accepts connections on `listener` until `shutdown` resolves, serving each on its own task
+    /// A connection is a WebSocket session when its first request asks for an upgrade
+    /// and an HTTP connection otherwise; see `http::serve` for the HTTP routes.
+    /// With TLS configured every connection starts with a TLS handshake, and the
+    /// certificate files are checked for renewals every `tls_reload_secs`.
+    /// Connections beyond `max_connections` are closed straight away. On shutdown the
+    /// listener closes, each connection stops reading, gets `drain_timeout` for its
+    /// in-flight calls, answers the rest with `Aborted` and then sends a close frame
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()>,
+    ) -> ShutdownSummary {
+        serve(Arc::new(self), listener, shutdown).await
+    }
+}
+
+async fn serve(
+    server: Arc<Server>,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> ShutdownSummary {
+    let config = &server.config;
+    let slots = Arc::new(Semaphore::new(config.max_connections));
+    let (deadline, deadline_rx) = watch::channel(None);
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    let reloader = match (&server.tls, config.tls_reload_interval()) {
+        (Some(tls), Some(interval)) => Some(tokio::spawn(Arc::clone(tls).watch(interval))),
+        _ => None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => {
+                let (stream, addr) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                        break;
+                    }
+                };
+                let Ok(permit) = Arc::clone(&slots).try_acquire_owned() else {
+                    warn!(
+                        "Rejecting connection from {}: {} connections already open",
+                        addr, config.max_connections
+                    );
+                    continue;
+                };
+                let server = Arc::clone(&server);
+                let shutdown = deadline_rx.clone();
+                connections.spawn(async move {
+                    let summary = handle_connection(stream, addr, &server, shutdown).await;
+                    drop(permit);
+                    summary
+                });
+            }
+            // Reap finished connections so the set only holds open ones
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+
+    drop(listener);
+    if let Some(reloader) = reloader {
+        reloader.abort();
+    }
+    info!(
+        "Draining {} connections for up to {}s",
+        connections.len(),
+        config.drain_timeout_secs
+    );
+    deadline.send_replace(Some(Instant::now() + config.drain_timeout()));
+
+    let mut summary = ShutdownSummary::default();
+    while let Some(joined) = connections.join_next().await {
+        match joined {
+            Ok(connection) => summary.add(connection),
+            Err(e) => error!("Connection task failed: {}", e),
+        }
+    }
+    summary
+}
+
+/// A connection's receiver, whichever storage backend it was built with
+pub(crate) trait Session: Send + Sync {
+    fn handle_request(&self, request: Request, sender: Box<dyn WireResponseSender>);
+}
+
+impl<S: Storage + 'static> Session for Receiver<PathfinderHandler<S>, S> {
+    fn handle_request(&self, request: Request, sender: Box<dyn WireResponseSender>) {
+        Receiver::handle_request(self, request, sender)
+    }
+}
+
+/// Runs `request` on the blocking pool, as calls block while they compute
+/// A call whose handler panics is answered with an `Error`, so it does not stay
+/// in flight
+fn spawn_call(
+    session: Arc<dyn Session>,
+    request: Request,
+    sender: impl WireResponseSender + Clone + Send + 'static,
+) {
+    tokio::task::spawn_blocking(move || {
+        let call = match &request.0 {
+            RequestEnum::Call(id, _) => Some(*id),
+            RequestEnum::Abort(..) => None,
+        };
+        let handled = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            session.handle_request(request, Box::new(sender.clone()))
+        }));
+        if let (Err(_), Some(id)) = (handled, call) {
+            error!("Call {} panicked", id);
+            sender.send_response(WireResponse(
+                id,
+                ResponseEnum::Error(INTERNAL_ERROR.to_string()),
+            ));
+        }
+    });
+}
+
+/// `user_id` is the authenticated user, when the server requires authentication
+fn open_session(
+    storage: StorageBackend,
+    limits: CallLimits,
+    session_id: String,
+    user_id: Option<String>,
+) -> Arc<dyn Session> {
+    match storage {
+        StorageBackend::Memory => {
+            // Session storage lives as long as the connection (cached paths, stored graphs)
+            let storage = InMemoryStorage::new();
+            let handler = PathfinderHandler::new(Some(Arc::new(storage.clone())));
+            let receiver = Receiver::new(session_id, handler, Some(storage)).with_limits(limits);
+            Arc::new(match user_id {
+                Some(user_id) => receiver.with_user(user_id),
+                None => receiver,
+            })
+        }
+        StorageBackend::None => {
+            let handler = PathfinderHandler::<NoStorage>::new(None);
+            let receiver = Receiver::new(session_id, handler, None).with_limits(limits);
+            Arc::new(match user_id {
+                Some(user_id) => receiver.with_user(user_id),
+                None => receiver,
+            })
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    server: &Server,
+    mut shutdown: watch::Receiver<Option<Instant>>,
+) -> ConnectionSummary {
+    let Some(tls) = &server.tls else {
+        return handle_stream(stream, addr, server, shutdown).await;
+    };
+    // The TLS handshake and the request head after it get a timeout each
+    let accept = tokio::time::timeout(server.config.handshake_timeout(), tls.accept(stream));
+    let stream = tokio::select! {
+        accepted = accept => match accepted {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                warn!("TLS handshake with {} failed: {}", addr, e);
+                return ConnectionSummary::default();
+            }
+            Err(_) => {
+                warn!("Dropping {}: no TLS handshake within the handshake timeout", addr);
+                return ConnectionSummary::default();
+            }
+        },
+        _ = shutdown::requested(&mut shutdown) => return ConnectionSummary::default(),
+    };
+    handle_stream(stream, addr, server, shutdown).await
+}
+
+/// Serves a connection, once past TLS if the server uses it
+async fn handle_stream<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    mut stream: S,
+    addr: SocketAddr,
+    server: &Server,
+    mut shutdown: watch::Receiver<Option<Instant>>,
+) -> ConnectionSummary {
+    let read_head = tokio::time::timeout(
+        server.config.handshake_timeout(),
+        http::read_head(&mut stream),
+    );
+    let head = tokio::select! {
+        head = read_head => match head {
+            Ok(Ok(head)) => head,
+            Ok(Err(e)) => {
+                error!("Failed to read request from {}: {}", addr, e);
+                return ConnectionSummary::default();
+            }
+            Err(_) => {
+                warn!("Dropping {}: no request within the handshake timeout", addr);
+                return ConnectionSummary::default();
+            }
+        },
+        _ = shutdown::requested(&mut shutdown) => return ConnectionSummary::default(),
+    };
+    let upgrade = http::is_websocket_upgrade(&head);
+    let stream = Replay::new(head, stream);
+    if upgrade {
+        return handle_websocket(stream, addr, server, shutdown).await;
+    }
+
+    info!("New HTTP connection: {}", addr);
+    let summary = http::serve(stream, addr, server, shutdown).await;
+    info!("HTTP connection closed: {}", addr);
+    summary
+}
+
+async fn handle_websocket<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    stream: S,
+    addr: SocketAddr,
+    server: &Server,
+    mut shutdown: watch::Receiver<Option<Instant>>,
+) -> ConnectionSummary {
+    info!("New WebSocket connection: {}", addr);
+    let config = &server.config;
+
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(config.max_message_size),
+        max_frame_size: Some(config.frame_size()),
+        ..Default::default()
+    };
+    let mut encoding = Encoding::Json;
+    let mut user_id = None;
+    let handshake = Handshake {
+        origins: &server.origins,
+        authenticator: server.authenticator.as_deref(),
+        encoding: &mut encoding,
+        user_id: &mut user_id,
+    };
+    let ws_stream = tokio::select! {
+        accepted = accept_hdr_async_with_config(stream, handshake, Some(ws_config)) => match accepted {
+            Ok(ws) => ws,
+            Err(e) => {
+                error!("Error during WebSocket handshake: {}", e);
+                return ConnectionSummary::default();
+            }
+        },
+        _ = shutdown::requested(&mut shutdown) => return ConnectionSummary::default(),
+    };
+
+    let session = open_session(
+        config.storage,
+        config.call_limits(),
+        format!("ws-{}", addr),
+        user_id.clone(),
+    );
+    let limits = ConnectionLimits::new(config, Arc::clone(&server.users));
+    let summary = run_session(
+        ws_stream, session, user_id, limits, encoding, config, shutdown,
+    )
+    .await;
+
+    info!("WebSocket connection closed: {}", addr);
+    summary
+}
+
+/// Why a session stopped reading
+enum Ending {
+    /// The server is shutting down; in-flight calls may run until the deadline
+    Shutdown(Instant),
+    ClosedByPeer,
+    IdleTimeout,
+    /// The peer stopped answering pings or the connection failed
+    Lost,
+}
+
+async fn run_session<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    ws_stream: WebSocketStream<S>,
+    session: Arc<dyn Session>,
+    user_id: Option<String>,
+    mut limits: ConnectionLimits,
+    encoding: Encoding,
+    config: &ServerConfig,
+    mut shutdown: watch::Receiver<Option<Instant>>,
+) -> ConnectionSummary {
+    let (write, mut read) = ws_stream.split();
+    let (frames, writer) = transport::spawn_writer(write);
+    let in_flight = Arc::new(InFlight::default());
+    let ws_sender = WebSocketSender::new(frames.clone(), Arc::clone(&in_flight), encoding);
+
+    let mut pings = config
+        .ping_interval()
+        .map(|period| tokio::time::interval_at(Instant::now() + period, period));
+    let mut missed_pongs = 0;
+    let mut awaiting_pong = false;
+    let idle_timeout = config.idle_timeout();
+    let idle = tokio::time::sleep(idle_timeout.unwrap_or_default());
+    tokio::pin!(idle);
+
+    let ending = loop {
+        tokio::select! {
+            message = read.next() => match message {
+                Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))) => {
+                    if let Some(timeout) = idle_timeout {
+                        idle.as_mut().reset(Instant::now() + timeout);
+                    }
+                    match decode_request(encoding, message) {
+                        Ok(request) => {
+                            if let RequestEnum::Call(id, _) = &request.0 {
+                                if let Err(throttled) = limits.admit(user_id.as_deref(), in_flight.len()) {
+                                    debug!("Refusing call {} from {:?}: {}", id, user_id, throttled);
+                                    ws_sender.reject(*id, throttled.to_response());
+                                    continue;
+                                }
+                                in_flight.start(*id);
+                            }
+                            spawn_call(Arc::clone(&session), request, ws_sender.clone());
+                        }
+                        Err(e) => {
+                            warn!("Failed to parse request: {}", e);
+                        }
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    awaiting_pong = false;
+                    missed_pongs = 0;
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    info!("Client closed the connection: {:?}", frame);
+                    break Ending::ClosedByPeer;
+                }
+                // Pings are answered by tungstenite on the next read or write
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    error!("WebSocket error: {}", e);
+                    break Ending::Lost;
+                }
+                None => break Ending::Lost,
+            },
+            _ = next_ping(&mut pings) => {
+                if awaiting_pong {
+                    missed_pongs += 1;
+                    if missed_pongs >= config.max_missed_pongs {
+                        warn!("Dropping connection after {} unanswered pings", missed_pongs);
+                        break Ending::Lost;
+                    }
+                }
+                awaiting_pong = true;
+                let _ = frames.send(Message::Ping(Vec::new()));
+            }
+            _ = &mut idle, if idle_timeout.is_some() => {
+                let timeout = idle_timeout.unwrap_or_default();
+                if in_flight.len() > 0 {
+                    // A long call is not idleness; look again once another timeout has passed
+                    idle.as_mut().reset(Instant::now() + timeout);
+                    continue;
+                }
+                info!("Closing connection idle for {}s", timeout.as_secs());
+                break Ending::IdleTimeout;
+            }
+            deadline = shutdown::requested(&mut shutdown) => break Ending::Shutdown(deadline),
+        }
+    };
+
+    match ending {
+        Ending::Shutdown(deadline) => {
+            let pending = in_flight.len();
+            let _ = tokio::time::timeout_at(deadline, in_flight.wait_idle()).await;
+            let abandoned = in_flight.abandon_all();
+            for &id in &abandoned {
+                ws_sender.send_response(WireResponse(
+                    id,
+                    ResponseEnum::Aborted(SHUTDOWN_REASON.to_string()),
+                ));
+            }
+            close(frames, writer, SHUTDOWN_REASON).await;
+            ConnectionSummary {
+                drained: pending - abandoned.len(),
+                aborted: abandoned.len(),
+            }
+        }
+        Ending::IdleTimeout => {
+            close(frames, writer, IDLE_REASON).await;
+            ConnectionSummary::default()
+        }
+        Ending::ClosedByPeer => {
+            // Nobody is left to answer; reading once more sends tungstenite's close reply
+            in_flight.abandon_all();
+            let _ = tokio::time::timeout(CLOSE_REPLY_TIMEOUT, read.next()).await;
+            writer.abort();
+            ConnectionSummary::default()
+        }
+        Ending::Lost => {
+            in_flight.abandon_all();
+            writer.abort();
+            ConnectionSummary::default()
+        }
+    }
+}
+
+/// Text frames are always JSON; binary frames need a binary encoding negotiated
+fn decode_request(encoding: Encoding, message: Message) -> Result<Request, String> {
+    match message {
+        Message::Text(text) => Encoding::Json.decode_request(text.as_bytes()),
+        Message::Binary(bytes) if encoding.is_binary() => encoding.decode_request(&bytes),
+        Message::Binary(_) => Err(format!(
+            "binary frame on a connection without the {} subprotocol",
+            Encoding::MessagePack.subprotocol()
+        )),
+        _ => Err("not a data frame".to_string()),
+    }
+}
+
+/// Resolves on the next keepalive tick, or never when pings are off
+async fn next_ping(pings: &mut Option<Interval>) {
+    match pings {
+        Some(pings) => {
+            pings.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Queues a `1001 Going Away` close frame after everything already queued and waits for it to go out
+async fn close(frames: mpsc::UnboundedSender<Message>, writer: JoinHandle<()>, reason: &str) {
+    let _ = frames.send(Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: reason.to_string().into(),
+    })));
+    let _ = writer.await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures_util::SinkExt;
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    /// Betweenness on a `side` x `side` grid; its cost grows with side^4
+    fn centrality_call(id: usize, side: usize) -> Message {
+        let points: Vec<_> = (0..side * side)
+            .map(|i| json!({"x": (i % side) as f64, "y": (i / side) as f64}))
+            .collect();
+        let mut edges = Vec::new();
+        for i in 0..side * side {
+            if i % side + 1 < side {
+                edges.push(json!({"from": i, "to": i + 1}));
+            }
+            if i + side < side * side {
+                edges.push(json!({"from": i, "to": i + side}));
+            }
+        }
+        let call =
+            json!({"Call": [id, {"compute_centrality": {"points": points, "edges": edges}}]});
+        Message::Text(call.to_string())
+    }
+
+    async fn start(
+        config: ServerConfig,
+    ) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<ShutdownSummary>) {
+        start_server(Server::new(config).unwrap()).await
+    }
+
+    async fn start_server(
+        server: Server,
+    ) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<ShutdownSummary>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel();
+        let server = tokio::spawn(server.serve(listener, async {
+            let _ = stopped.await;
+        }));
+        (addr, stop, server)
+    }
+
+    /// Text frames other than progress reports, then the close frame
+    async fn frames_until_close(
+        ws: &mut WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>,
+    ) -> (Vec<String>, CloseFrame<'static>) {
+        let mut texts = Vec::new();
+        while let Some(message) = ws.next().await {
+            match message.unwrap() {
+                Message::Text(text) if !text.contains(r#"{"Progress":"#) => texts.push(text),
+                Message::Close(frame) => return (texts, frame.unwrap()),
+                _ => {}
+            }
+        }
+        panic!("connection ended without a close frame");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_lets_in_flight_calls_finish_then_closes() {
+        let (addr, stop, server) = start(ServerConfig {
+            drain_timeout_secs: 30,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        ws.send(centrality_call(1, 20)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stop.send(()).unwrap();
+
+        let (texts, close) = frames_until_close(&mut ws).await;
+        assert_eq!(texts.len(), 2, "{:?}", texts);
+        assert!(texts[0].starts_with(r#"[1,{"N":"#), "{}", texts[0]);
+        assert!(texts[1].starts_with(r#"[1,{"Complete":"#), "{}", texts[1]);
+        assert_eq!(close.code, CloseCode::Away);
+        assert_eq!(close.reason, SHUTDOWN_REASON);
+
+        let summary = server.await.unwrap();
+        assert_eq!(summary.connections, 1);
+        assert_eq!(summary.aborted, 0);
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn test_shutdown_aborts_calls_past_the_deadline() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let summary = runtime.block_on(async {
+            let (addr, stop, server) = start(ServerConfig {
+                drain_timeout_secs: 0,
+                ..Default::default()
+            })
+            .await;
+            let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+                .await
+                .unwrap();
+
+            // Far too slow to finish; it keeps a blocking thread busy after the abort
+            ws.send(centrality_call(7, 60)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stop.send(()).unwrap();
+
+            let (texts, close) = frames_until_close(&mut ws).await;
+            assert_eq!(texts, vec![r#"[7,{"Aborted":"Server shutting down"}]"#]);
+            assert_eq!(close.code, CloseCode::Away);
+            server.await.unwrap()
+        });
+        // Like the binary, leave the abandoned call behind instead of waiting for it
+        runtime.shutdown_background();
+
+        assert_eq!(
+            summary,
+            ShutdownSummary {
+                connections: 1,
+                drained: 0,
+                aborted: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_close_is_answered() {
+        let (addr, _stop, _server) = start(ServerConfig::default()).await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        ws.close(None).await.unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .unwrap();
+        assert!(matches!(reply, Some(Ok(Message::Close(_)))), "{:?}", reply);
+    }
+
+    struct PanickingSession;
+
+    impl Session for PanickingSession {
+        fn handle_request(&self, _request: Request, _sender: Box<dyn WireResponseSender>) {
+            panic!("handler bug");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_handler_ends_its_call() {
+        let (frames, mut queue) = mpsc::unbounded_channel();
+        let in_flight = Arc::new(InFlight::default());
+        let sender = WebSocketSender::new(frames, Arc::clone(&in_flight), Encoding::Json);
+        let request = decode_request(Encoding::Json, centrality_call(5, 2)).unwrap();
+
+        in_flight.start(5);
+        spawn_call(Arc::new(PanickingSession), request, sender);
+        tokio::time::timeout(Duration::from_secs(5), in_flight.wait_idle())
+            .await
+            .unwrap();
+        assert_eq!(
+            queue.recv().await.unwrap().into_text().unwrap(),
+            r#"[5,{"Error":"Internal error"}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection_is_closed() {
+        let (addr, _stop, _server) = start(ServerConfig {
+            idle_timeout_secs: 1,
+            ping_interval_secs: 0,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        let (texts, close) =
+            tokio::time::timeout(Duration::from_secs(5), frames_until_close(&mut ws))
+                .await
+                .unwrap();
+        assert!(texts.is_empty());
+        assert_eq!(close.code, CloseCode::Away);
+        assert_eq!(close.reason, IDLE_REASON);
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_is_dropped_after_handshake_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (addr, _stop, _server) = start(ServerConfig {
+            handshake_timeout_secs: 1,
+            ..Default::default()
+        })
+        .await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        // Half a request head, then nothing
+        stream.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+        let mut rest = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+            .await
+            .unwrap();
+        assert_eq!(read.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_pings_drop_the_connection() {
+        let (addr, _stop, _server) = start(ServerConfig {
+            ping_interval_secs: 1,
+            max_missed_pongs: 1,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        // Not reading means not answering: the first ping goes unanswered and the
+        // server hangs up at the second tick, without a close frame
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        let mut pings = 0;
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Ping(_))) => pings += 1,
+                Some(Ok(other)) => panic!("unexpected {:?}", other),
+                Some(Err(_)) | None => break,
+            }
+        }
+        assert_eq!(pings, 1);
+    }
+
+    /// Sends one HTTP/1.1 request and reads the whole response
+    async fn http_request(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        http_request_with(addr, method, path, "", body).await
+    }
+
+    /// Like `http_request`, with `headers` (each ending in CRLF) added to the request
+    async fn http_request_with(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &str,
+        body: &str,
+    ) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            headers,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_http_rpc_returns_every_response() {
+        let (addr, _stop, _server) = start(ServerConfig::default()).await;
+        let Message::Text(call) = centrality_call(5, 3) else {
+            unreachable!()
+        };
+
+        let response = http_request(addr, "POST", "/rpc", &call).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("content-type: application/json"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let frames: Vec<WireResponse> = serde_json::from_str(body).unwrap();
+        assert!(frames.iter().all(|WireResponse(id, _)| *id == 5));
+        assert!(frames
+            .iter()
+            .any(|frame| matches!(frame.1, ResponseEnum::N(_))));
+        assert!(matches!(
+            frames.last().unwrap().1,
+            ResponseEnum::Complete(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_rpc_stream_sends_events() {
+        let (addr, _stop, _server) = start(ServerConfig::default()).await;
+        let Message::Text(call) = centrality_call(6, 3) else {
+            unreachable!()
+        };
+
+        let response = http_request(addr, "POST", "/rpc/stream", &call).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("content-type: text/event-stream"));
+        assert!(response.contains(r#"data: [6,{"N":"#), "{}", response);
+        assert!(
+            response.contains(r#"data: [6,{"Complete":"#),
+            "{}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_calls_count_in_flight_until_their_stream_ends() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (addr, _stop, _server) = start(ServerConfig {
+            max_in_flight: 1,
+            ..Default::default()
+        })
+        .await;
+        let Message::Text(call) = centrality_call(6, 3) else {
+            unreachable!()
+        };
+
+        // Pipelined on one connection: the second is only read once the first
+        // stream has ended, by which time the first call no longer counts
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = |connection: &str| {
+            format!(
+                "POST /rpc/stream HTTP/1.1\r\nHost: {}\r\nConnection: {}\r\nContent-Length: {}\r\n\r\n{}",
+                addr,
+                connection,
+                call.len(),
+                call
+            )
+        };
+        let requests = request("keep-alive") + &request("close");
+        stream.write_all(requests.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert_eq!(
+            response.matches("HTTP/1.1 200 OK").count(),
+            2,
+            "{}",
+            response
+        );
+        assert_eq!(response.matches(r#"data: [6,{"Complete":"#).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_http_rejects_bad_requests() {
+        let (addr, _stop, _server) = start(ServerConfig::default()).await;
+
+        let response = http_request(addr, "GET", "/rpc", "").await;
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+        assert!(response.contains("allow: POST"));
+
+        let response = http_request(addr, "POST", "/nowhere", "").await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        let response = http_request(addr, "POST", "/rpc", r#"{"Call": [1]}"#).await;
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(response.contains("Invalid request"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_clients_are_refused() {
+        use tokio_tungstenite::tungstenite::Error;
+
+        let tokens = auth::TokenFile::parse("alice s3cret\n").unwrap();
+        let server = Server::new(ServerConfig::default())
+            .unwrap()
+            .with_authenticator(Arc::new(tokens));
+        let (addr, _stop, _server) = start_server(server).await;
+
+        for url in [
+            format!("ws://{}", addr),
+            format!("ws://{}/?token=wrong", addr),
+        ] {
+            match tokio_tungstenite::connect_async(url).await {
+                Err(Error::Http(response)) => assert_eq!(response.status(), 401),
+                other => panic!("expected 401, got {:?}", other.map(|(_, r)| r)),
+            }
+        }
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/?token=s3cret", addr))
+            .await
+            .unwrap();
+        ws.send(centrality_call(2, 3)).await.unwrap();
+        let reply = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(reply.starts_with("[2,"), "{}", reply);
+
+        let Message::Text(call) = centrality_call(3, 3) else {
+            unreachable!()
+        };
+        let response = http_request(addr, "POST", "/rpc", &call).await;
+        assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+        assert!(
+            response.contains("www-authenticate: Bearer"),
+            "{}",
+            response
+        );
+        assert!(response.ends_with("Missing bearer token"), "{}", response);
+
+        let authorization = "Authorization: Bearer s3cret\r\n";
+        let response = http_request_with(addr, "POST", "/rpc", authorization, &call).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_only_allowed_origins_may_connect() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::http::header::ORIGIN;
+        use tokio_tungstenite::tungstenite::Error;
+
+        let (addr, _stop, _server) = start(ServerConfig {
+            allowed_origins: vec!["https://maps.example.com".to_string()],
+            ..Default::default()
+        })
+        .await;
+        let connect = |origin: Option<&'static str>| {
+            let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+            if let Some(origin) = origin {
+                request
+                    .headers_mut()
+                    .insert(ORIGIN, origin.parse().unwrap());
+            }
+            tokio_tungstenite::connect_async(request)
+        };
+
+        match connect(Some("https://evil.example")).await {
+            Err(Error::Http(response)) => assert_eq!(response.status(), 403),
+            other => panic!("expected 403, got {:?}", other.map(|(_, r)| r)),
+        }
+        connect(Some("https://maps.example.com")).await.unwrap();
+        // Clients that are not browsers send no origin
+        connect(None).await.unwrap();
+
+        let Message::Text(call) = centrality_call(1, 3) else {
+            unreachable!()
+        };
+        let evil = "Origin: https://evil.example\r\n";
+        let response = http_request_with(addr, "POST", "/rpc", evil, &call).await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+        assert!(!response.contains("access-control-allow-origin"));
+
+        let allowed = "Origin: https://maps.example.com\r\n";
+        let preflight = "Origin: https://maps.example.com\r\n\
+                         Access-Control-Request-Method: POST\r\n\
+                         Access-Control-Request-Headers: authorization\r\n";
+        let response = http_request_with(addr, "OPTIONS", "/rpc/stream", preflight, "").await;
+        assert!(response.starts_with("HTTP/1.1 204"), "{}", response);
+        assert!(response.contains("access-control-allow-origin: https://maps.example.com"));
+        assert!(response.contains("access-control-allow-methods: POST"));
+        assert!(response.contains("access-control-allow-headers: authorization, content-type"));
+
+        let response = http_request_with(addr, "POST", "/rpc", allowed, &call).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("access-control-allow-origin: https://maps.example.com"));
+        assert!(response.contains("vary: Origin"));
+    }
+
+    #[tokio::test]
+    async fn test_excess_calls_are_refused_with_retry_after() {
+        let (addr, _stop, _server) = start(ServerConfig {
+            max_in_flight: 1,
+            connection_rate: 0.05,
+            connection_burst: 2,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        // The first call is still running when the second arrives, and the
+        // bucket is empty by the third
+        ws.send(centrality_call(1, 20)).await.unwrap();
+        ws.send(centrality_call(2, 3)).await.unwrap();
+        let refusal = loop {
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            if text.starts_with("[2,") {
+                break text;
+            }
+        };
+        let refusal: serde_json::Value = serde_json::from_str(&refusal).unwrap();
+        assert_eq!(
+            refusal[1]["RateLimited"],
+            json!({
+                "scope": "in-flight",
+                "retry_after_ms": null,
+                "message": "Rate limited (in-flight): at most 1 calls at once per connection"
+            })
+        );
+        loop {
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            if text.starts_with(r#"[1,{"Complete":"#) {
+                break;
+            }
+        }
+        ws.send(centrality_call(3, 3)).await.unwrap();
+        loop {
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            if text.starts_with(r#"[3,{"Complete":"#) {
+                break;
+            }
+        }
+        ws.send(centrality_call(4, 3)).await.unwrap();
+        let refusal = loop {
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            if text.starts_with("[4,") {
+                break text;
+            }
+        };
+        let refusal: serde_json::Value = serde_json::from_str(&refusal).unwrap();
+        assert_eq!(refusal[1]["RateLimited"]["scope"], "connection");
+        assert!(
+            refusal[1]["RateLimited"]["retry_after_ms"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_rate_limit_spans_connections() {
+        let tokens = auth::TokenFile::parse("alice s3cret\nbob hunter2\n").unwrap();
+        let server = Server::new(ServerConfig {
+            user_rate: 0.5,
+            user_burst: 2,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_authenticator(Arc::new(tokens));
+        let (addr, _stop, _server) = start_server(server).await;
+        let Message::Text(call) = centrality_call(5, 3) else {
+            unreachable!()
+        };
+
+        let alice = "Authorization: Bearer s3cret\r\n";
+        for _ in 0..2 {
+            let response = http_request_with(addr, "POST", "/rpc", alice, &call).await;
+            assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        }
+        let response = http_request_with(addr, "POST", "/rpc", alice, &call).await;
+        assert!(response.starts_with("HTTP/1.1 429"), "{}", response);
+        assert!(response.contains("retry-after: 2"), "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let frames: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(frames[0][0], 5);
+        assert_eq!(frames[0][1]["RateLimited"]["scope"], "user");
+
+        let bob = "Authorization: Bearer hunter2\r\n";
+        let response = http_request_with(addr, "POST", "/rpc", bob, &call).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_calls_over_size_limits_are_refused() {
+        let (addr, _stop, _server) = start(ServerConfig {
+            max_points: 10,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        ws.send(centrality_call(1, 4)).await.unwrap();
+        let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert_eq!(
+            text,
+            r#"[1,{"Error":"Graph has 16 points; at most 10 are allowed"}]"#
+        );
+        ws.send(centrality_call(2, 3)).await.unwrap();
+        let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(text.starts_with("[2,"), "{}", text);
+        assert!(!text.contains("Error"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_frames_over_max_frame_size_close_the_connection() {
+        let (addr, _stop, _server) = start(ServerConfig {
+            max_frame_size: 4096,
+            ..Default::default()
+        })
+        .await;
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+
+        // tungstenite refuses the frame before it is read, so the call is never answered
+        ws.send(centrality_call(1, 20)).await.unwrap();
+        while let Some(Ok(message)) = ws.next().await {
+            assert!(
+                matches!(message, Message::Close(_) | Message::Ping(_)),
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    async fn tls_connect(
+        addr: SocketAddr,
+        roots: &[&rcgen::CertifiedKey],
+    ) -> tokio_rustls::client::TlsStream<TcpStream> {
+        use rustls::pki_types::ServerName;
+
+        let mut store = rustls::RootCertStore::empty();
+        for root in roots {
+            store.add(root.cert.der().clone()).unwrap();
+        }
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(store)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap()
+    }
+
+    fn served_certificate(stream: &tokio_rustls::client::TlsStream<TcpStream>) -> Vec<u8> {
+        stream.get_ref().1.peer_certificates().unwrap()[0].to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_tls_serves_wss_and_https_and_picks_up_renewals() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let first = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let second = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("pathfinder-server-{}.crt", std::process::id()));
+        let key_path = dir.join(format!("pathfinder-server-{}.key", std::process::id()));
+        std::fs::write(&cert_path, first.cert.pem()).unwrap();
+        std::fs::write(&key_path, first.key_pair.serialize_pem()).unwrap();
+        let (addr, _stop, _server) = start(ServerConfig {
+            tls_cert_file: Some(cert_path.clone()),
+            tls_key_file: Some(key_path.clone()),
+            tls_reload_secs: 1,
+            ..Default::default()
+        })
+        .await;
+        let roots = [&first, &second];
+
+        let stream = tls_connect(addr, &roots).await;
+        assert_eq!(served_certificate(&stream), first.cert.der().to_vec());
+        let url = format!("wss://localhost:{}", addr.port());
+        let (mut ws, _) = tokio_tungstenite::client_async(url, stream).await.unwrap();
+        ws.send(centrality_call(1, 3)).await.unwrap();
+        let reply = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(reply.starts_with("[1,"), "{}", reply);
+
+        let Message::Text(call) = centrality_call(2, 3) else {
+            unreachable!()
+        };
+        let mut stream = tls_connect(addr, &roots).await;
+        let request = format!(
+            "POST /rpc HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            call.len(),
+            call
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        // Clients that skip TLS get nowhere
+        assert!(tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .is_err());
+
+        std::fs::write(&cert_path, second.cert.pem()).unwrap();
+        std::fs::write(&key_path, second.key_pair.serialize_pem()).unwrap();
+        let renewed = async {
+            loop {
+                let stream = tls_connect(addr, &roots).await;
+                if served_certificate(&stream) == second.cert.der().to_vec() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), renewed)
+            .await
+            .expect("renewed certificate was not picked up");
+
+        // Connections opened before the renewal carry on
+        ws.send(centrality_call(3, 3)).await.unwrap();
+        let reply = loop {
+            let text = ws.next().await.unwrap().unwrap().into_text().unwrap();
+            if !text.starts_with("[1,") {
+                break text;
+            }
+        };
+        assert!(reply.starts_with("[3,"), "{}", reply);
+        std::fs::remove_file(cert_path).unwrap();
+        std::fs::remove_file(key_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_times_out() {
+        use tokio::io::AsyncReadExt;
+
+        let pair = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("pathfinder-silent-{}.crt", std::process::id()));
+        let key_path = dir.join(format!("pathfinder-silent-{}.key", std::process::id()));
+        std::fs::write(&cert_path, pair.cert.pem()).unwrap();
+        std::fs::write(&key_path, pair.key_pair.serialize_pem()).unwrap();
+        let (addr, _stop, _server) = start(ServerConfig {
+            tls_cert_file: Some(cert_path.clone()),
+            tls_key_file: Some(key_path.clone()),
+            handshake_timeout_secs: 1,
+            ..Default::default()
+        })
+        .await;
+
+        // Connects but never sends a ClientHello
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut rest = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+            .await
+            .unwrap();
+        assert_eq!(read.unwrap(), 0);
+        std::fs::remove_file(cert_path).unwrap();
+        std::fs::remove_file(key_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_subprotocol_uses_binary_frames() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+
+        let (addr, _stop, _server) = start(ServerConfig::default()).await;
+        let mut request = format!("ws://{}", addr).into_client_request().unwrap();
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            "pathfinder.msgpack".parse().unwrap(),
+        );
+        let (mut ws, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(
+            response.headers().get(SEC_WEBSOCKET_PROTOCOL).unwrap(),
+            "pathfinder.msgpack"
+        );
+
+        let Message::Text(call) = centrality_call(3, 3) else {
+            unreachable!()
+        };
+        let call = Encoding::Json.decode_request(call.as_bytes()).unwrap();
+        let bytes = Encoding::MessagePack.encode_request(&call.0).unwrap();
+        ws.send(Message::Binary(bytes)).await.unwrap();
+
+        let mut bodies = Vec::new();
+        while let Some(message) = ws.next().await {
+            let Message::Binary(bytes) = message.unwrap() else {
+                panic!("expected a binary frame");
+            };
+            let WireResponse(id, body) = Encoding::MessagePack.decode_response(&bytes).unwrap();
+            assert_eq!(id, 3);
+            let done = matches!(body, ResponseEnum::Complete(_));
+            bodies.push(body);
+            if done {
+                break;
+            }
+        }
+        assert!(bodies.iter().any(|body| matches!(body, ResponseEnum::N(_))));
     }
 }
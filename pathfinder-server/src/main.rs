@@ -1,64 +1,42 @@
-mod transport;
+use clap::Parser;
+use log::{error, info};
+use pathfinder_server::config::{Cli, ServerConfig};
+use pathfinder_server::{shutdown, Server};
+use tokio::net::TcpListener;
 
-use futures_util::StreamExt;
-use pathfinder_core::PathfinderHandler;
-use shared_types::receiver::Receiver;
-use shared_types::router::Request;
-use shared_types::storage::NoStorage;
-use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::accept_async;
-use transport::WebSocketSender;
-
-async fn handle_connection(stream: TcpStream, addr: SocketAddr) {
-    println!("New WebSocket connection: {}", addr);
-
-    let ws_stream = match accept_async(stream).await {
-        Ok(ws) => ws,
+#[tokio::main]
+async fn main() {
+    let server = match ServerConfig::load(&Cli::parse()).and_then(Server::new) {
+        Ok(server) => server,
         Err(e) => {
-            eprintln!("Error during WebSocket handshake: {}", e);
-            return;
+            eprintln!("pathfinder-server: {}", e);
+            std::process::exit(2);
         }
     };
+    let config = server.config();
+    env_logger::Builder::new()
+        .filter_level(config.log_level.into())
+        .init();
 
-    let (write, mut read) = ws_stream.split();
-    let session_id = format!("ws-{}", addr);
-    let handler = PathfinderHandler::<NoStorage>::new(None);
-    let receiver = Receiver::new(session_id, handler, None::<NoStorage>);
-    let ws_sender = WebSocketSender::new(write);
-
-    while let Some(message) = read.next().await {
-        match message {
-            Ok(msg) => {
-                if let tokio_tungstenite::tungstenite::Message::Text(text) = msg {
-                    match serde_json::from_str::<Request>(&text) {
-                        Ok(request) => {
-                            let sender: Box<dyn shared_types::router::WireResponseSender> = Box::new(ws_sender.clone());
-                            receiver.handle_request(request, sender);
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to parse request: {}", e);
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("WebSocket error: {}", e);
-                break;
-            }
+    let listener = match TcpListener::bind(config.addr()).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind {}: {}", config.addr(), e);
+            std::process::exit(1);
         }
-    }
-
-    println!("WebSocket connection closed: {}", addr);
-}
-
-#[tokio::main]
-async fn main() {
-    let addr = "127.0.0.1:10810";
-    let listener = TcpListener::bind(addr).await.expect("Failed to bind");
-    println!("WebSocket server listening on: ws://{}", addr);
-
-    while let Ok((stream, addr)) = listener.accept().await {
-        tokio::spawn(handle_connection(stream, addr));
-    }
+    };
+    let (ws, http) = match config.tls_cert_file {
+        Some(_) => ("wss", "https"),
+        None => ("ws", "http"),
+    };
+    let addr = listener.local_addr().unwrap_or(config.addr());
+    info!(
+        "Server listening on: {0}://{1} and {2}://{1}/rpc",
+        ws, addr, http
+    );
+
+    let summary = server.serve(listener, shutdown::signal()).await;
+    info!("Shutdown complete: {}", summary);
+    // Aborted calls may still be computing on blocking threads; do not wait for them
+    std::process::exit(0);
 }
@@ -0,0 +1,95 @@
+use hyper::header::{HeaderValue, ORIGIN};
+use hyper::HeaderMap;
+
+/// The browser origins allowed to reach the server, from `allowed_origins`
+/// Browsers send `Origin` with every WebSocket upgrade and cross-site request.
+/// Requests without one come from clients that are not browsers and are let
+/// through, as is every origin when the list is empty
+#[derive(Debug, Clone, Default)]
+pub struct Origins {
+    allowed: Vec<String>,
+}
+
+impl Origins {
+    pub fn new(allowed: &[String]) -> Self {
+        Self {
+            allowed: allowed.iter().map(|origin| normalize(origin)).collect(),
+        }
+    }
+
+    /// The request's `Origin` when it is allowed, None when it sent none,
+    /// or why it is refused
+    pub fn check<'a>(&self, headers: &'a HeaderMap) -> Result<Option<&'a HeaderValue>, String> {
+        let Some(origin) = headers.get(ORIGIN) else {
+            return Ok(None);
+        };
+        if self.allowed.is_empty() {
+            return Ok(Some(origin));
+        }
+        let text = origin.to_str().unwrap_or_default();
+        if self.allowed.contains(&normalize(text)) {
+            Ok(Some(origin))
+        } else {
+            Err(format!("Origin {} is not allowed", text))
+        }
+    }
+}
+
+/// Checks that `origin` is a `scheme://host[:port]` a browser could send
+pub fn validate(origin: &str) -> Result<(), String> {
+    let invalid = || {
+        format!(
+            "allowed_origins: `{}` is not an origin like https://example.com",
+            origin
+        )
+    };
+    let (scheme, host) = origin.split_once("://").ok_or_else(invalid)?;
+    let host = host.strip_suffix('/').unwrap_or(host);
+    if scheme.is_empty() || host.is_empty() || host.contains(['/', '?', '#']) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Scheme and host are case-insensitive, and browsers never send a trailing slash
+fn normalize(origin: &str) -> String {
+    origin.trim_end_matches('/').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(origins: &Origins, origin: Option<&str>) -> Result<Option<String>, String> {
+        let mut headers = HeaderMap::new();
+        if let Some(origin) = origin {
+            headers.insert(ORIGIN, origin.parse().unwrap());
+        }
+        origins
+            .check(&headers)
+            .map(|origin| origin.map(|value| value.to_str().unwrap().to_string()))
+    }
+
+    #[test]
+    fn test_allows_listed_origins_and_clients_without_one() {
+        let origins = Origins::new(&["https://Maps.example.com/".to_string()]);
+        assert_eq!(
+            check(&origins, Some("https://maps.example.com")),
+            Ok(Some("https://maps.example.com".to_string()))
+        );
+        assert_eq!(check(&origins, None), Ok(None));
+        assert_eq!(
+            check(&origins, Some("https://evil.example")),
+            Err("Origin https://evil.example is not allowed".to_string())
+        );
+        assert_eq!(
+            check(&origins, Some("http://maps.example.com")),
+            Err("Origin http://maps.example.com is not allowed".to_string())
+        );
+        assert!(check(&Origins::default(), Some("https://evil.example")).is_ok());
+
+        assert_eq!(validate("http://localhost:5173"), Ok(()));
+        assert!(validate("example.com").is_err());
+        assert!(validate("https://example.com/app").is_err());
+    }
+}
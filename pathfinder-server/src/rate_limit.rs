@@ -0,0 +1,236 @@
+use crate::config::ServerConfig;
+use shared_types::router::RateLimited;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Users tracked before buckets that have refilled are dropped
+const MAX_IDLE_USERS: usize = 4096;
+
+/// Holds up to `burst` tokens and refills at `rate` per second; each call takes one
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    pub fn new(rate: f64, burst: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            burst: burst as f64,
+            tokens: burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+    }
+
+    /// Ok when a token is there to take, or how long until one will be
+    fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.burst
+    }
+}
+
+/// Why a call was turned away
+/// Clients get it as a `RateLimited` frame; its `Display` is that frame's message:
+/// `Rate limited (<scope>): ...`, ending in `retry after <n> ms` when a wait is known
+#[derive(Debug, Clone, PartialEq)]
+pub enum Throttled {
+    /// The connection already has `max_in_flight` calls running
+    InFlight(usize),
+    Connection(Duration),
+    User(Duration),
+}
+
+impl Throttled {
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Throttled::InFlight(_) => None,
+            Throttled::Connection(wait) | Throttled::User(wait) => Some(*wait),
+        }
+    }
+
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Throttled::InFlight(_) => "in-flight",
+            Throttled::Connection(_) => "connection",
+            Throttled::User(_) => "user",
+        }
+    }
+
+    /// The frame answering the refused call
+    pub fn to_response(&self) -> RateLimited {
+        RateLimited {
+            scope: self.scope().to_string(),
+            retry_after_ms: self.retry_after().map(millis),
+            message: self.to_string(),
+        }
+    }
+}
+
+/// Rounded up, so retrying after the wait always finds a token
+fn millis(wait: Duration) -> u64 {
+    wait.as_micros().div_ceil(1000) as u64
+}
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Throttled::InFlight(max) => write!(
+                f,
+                "Rate limited (in-flight): at most {} calls at once per connection",
+                max
+            ),
+            Throttled::Connection(wait) | Throttled::User(wait) => write!(
+                f,
+                "Rate limited ({}): retry after {} ms",
+                self.scope(),
+                millis(*wait)
+            ),
+        }
+    }
+}
+
+/// Buckets per `Context.user_id`, shared by every connection of the server
+#[derive(Default)]
+pub struct UserBuckets {
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+/// The limits one connection's calls are held to
+pub struct ConnectionLimits {
+    max_in_flight: usize,
+    connection: Option<TokenBucket>,
+    user_rate: Option<(f64, u32)>,
+    users: Arc<UserBuckets>,
+}
+
+impl ConnectionLimits {
+    pub fn new(config: &ServerConfig, users: Arc<UserBuckets>) -> Self {
+        let now = Instant::now();
+        Self {
+            max_in_flight: config.max_in_flight,
+            connection: (config.connection_rate > 0.0)
+                .then(|| TokenBucket::new(config.connection_rate, config.connection_burst, now)),
+            user_rate: (config.user_rate > 0.0).then_some((config.user_rate, config.user_burst)),
+            users,
+        }
+    }
+
+    /// Lets a call through, taking its tokens, unless a limit turns it away
+    /// `in_flight` is how many calls the connection is already running
+    pub fn admit(&mut self, user_id: Option<&str>, in_flight: usize) -> Result<(), Throttled> {
+        if self.max_in_flight > 0 && in_flight >= self.max_in_flight {
+            return Err(Throttled::InFlight(self.max_in_flight));
+        }
+        let now = Instant::now();
+        if let Some(bucket) = &mut self.connection {
+            bucket.check(now).map_err(Throttled::Connection)?;
+        }
+
+        // Tokens are only taken once every bucket has one, so a refused call costs nothing
+        if let (Some((rate, burst)), Some(user_id)) = (self.user_rate, user_id) {
+            let mut buckets = self.users.buckets.lock().unwrap();
+            if buckets.len() >= MAX_IDLE_USERS && !buckets.contains_key(user_id) {
+                buckets.retain(|_, bucket| {
+                    bucket.refill(now);
+                    !bucket.is_full()
+                });
+            }
+            let bucket = buckets
+                .entry(user_id.to_string())
+                .or_insert_with(|| TokenBucket::new(rate, burst, now));
+            bucket.check(now).map_err(Throttled::User)?;
+            bucket.take();
+        }
+        if let Some(bucket) = &mut self.connection {
+            bucket.take();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_at_rate_up_to_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(4.0, 2, start);
+        for _ in 0..2 {
+            bucket.check(start).unwrap();
+            bucket.take();
+        }
+        assert_eq!(bucket.check(start), Err(Duration::from_millis(250)));
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(bucket.check(later), Ok(()));
+        bucket.check(start + Duration::from_secs(60)).unwrap();
+        assert!(bucket.is_full());
+    }
+
+    #[test]
+    fn test_limits_apply_in_flight_connection_and_user() {
+        let users = Arc::new(UserBuckets::default());
+        let config = ServerConfig {
+            max_in_flight: 2,
+            connection_rate: 1.0,
+            connection_burst: 3,
+            user_rate: 1.0,
+            user_burst: 1,
+            ..Default::default()
+        };
+        let mut first = ConnectionLimits::new(&config, Arc::clone(&users));
+        let mut second = ConnectionLimits::new(&config, users);
+
+        assert_eq!(
+            first.admit(None, 2).unwrap_err().to_string(),
+            "Rate limited (in-flight): at most 2 calls at once per connection"
+        );
+        first.admit(Some("alice"), 0).unwrap();
+        // Alice's one token is spent whichever connection she uses
+        let refused = second.admit(Some("alice"), 0).unwrap_err();
+        assert!(matches!(refused, Throttled::User(_)), "{:?}", refused);
+        assert!(refused
+            .to_string()
+            .starts_with("Rate limited (user): retry after "));
+
+        first.admit(Some("bob"), 0).unwrap();
+        first.admit(None, 0).unwrap();
+        let refused = first.admit(None, 0).unwrap_err();
+        assert!(matches!(refused, Throttled::Connection(_)), "{:?}", refused);
+        assert!(refused.retry_after().unwrap() <= Duration::from_secs(1));
+
+        let frame = Throttled::Connection(Duration::from_micros(1_500_001)).to_response();
+        assert_eq!(frame.scope, "connection");
+        assert_eq!(frame.retry_after_ms, Some(1501));
+        assert_eq!(
+            frame.message,
+            "Rate limited (connection): retry after 1501 ms"
+        );
+        assert_eq!(Throttled::InFlight(2).to_response().retry_after_ms, None);
+    }
+}
@@ -0,0 +1,83 @@
+use log::{error, info};
+use std::fmt;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// What one connection had in flight when the server shut down
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionSummary {
+    /// Calls that finished before the drain deadline
+    pub drained: usize,
+    /// Calls still running at the deadline, answered with `Aborted`
+    pub aborted: usize,
+}
+
+/// Totals over every connection open at shutdown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    pub connections: usize,
+    pub drained: usize,
+    pub aborted: usize,
+}
+
+impl ShutdownSummary {
+    pub fn add(&mut self, connection: ConnectionSummary) {
+        self.connections += 1;
+        self.drained += connection.drained;
+        self.aborted += connection.aborted;
+    }
+}
+
+impl fmt::Display for ShutdownSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "closed {} connections, drained {} in-flight calls, aborted {}",
+            self.connections, self.drained, self.aborted
+        )
+    }
+}
+
+/// Resolves with the drain deadline once shutdown has begun
+pub async fn requested(shutdown: &mut watch::Receiver<Option<Instant>>) -> Instant {
+    loop {
+        if let Some(deadline) = *shutdown.borrow_and_update() {
+            return deadline;
+        }
+        if shutdown.changed().await.is_err() {
+            // The server dropped the sender without shutting down; nothing more will come
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Resolves on SIGINT (Ctrl-C) or, on Unix, SIGTERM
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("Received SIGINT, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
+}
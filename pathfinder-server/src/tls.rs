@@ -0,0 +1,191 @@
+use crate::config::ServerConfig;
+use log::{info, warn};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Terminates TLS with the certificate and key from `tls_cert_file` and `tls_key_file`
+pub struct Tls {
+    acceptor: TlsAcceptor,
+    certificate: Arc<Certificate>,
+}
+
+impl Tls {
+    /// Reads the certificate and key `config` names, if any
+    pub fn load(config: &ServerConfig) -> Result<Option<Self>, String> {
+        let (Some(cert_path), Some(key_path)) = (&config.tls_cert_file, &config.tls_key_file)
+        else {
+            return Ok(None);
+        };
+        let provider = Arc::new(ring::default_provider());
+        let certificate = Arc::new(Certificate::load(cert_path, key_path, &provider)?);
+        let tls_config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Failed to set up TLS: {}", e))?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(&certificate) as Arc<dyn ResolvesServerCert>);
+        Ok(Some(Self {
+            acceptor: TlsAcceptor::from(Arc::new(tls_config)),
+            certificate,
+        }))
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        self.acceptor.accept(stream).await
+    }
+
+    /// Checks the files every `interval` and serves new connections a renewed
+    /// certificate once both files hold a matching pair; connections already
+    /// open keep the one they started with
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            let certificate = Arc::clone(&self.certificate);
+            match tokio::task::spawn_blocking(move || certificate.reload()).await {
+                Ok(Ok(true)) => info!(
+                    "Reloaded TLS certificate from {}",
+                    self.certificate.cert_path.display()
+                ),
+                Ok(Ok(false)) => {}
+                Ok(Err(e)) => warn!("Keeping the current TLS certificate: {}", e),
+                Err(e) => warn!("TLS certificate reload failed: {}", e),
+            }
+        }
+    }
+}
+
+/// The certificate served to new connections
+/// Files are compared by content rather than modification time, so renewals
+/// that swap a symlink or land within the same second are still seen
+#[derive(Debug)]
+struct Certificate {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    /// File contents last tried, loaded or not, so a bad pair is reported once
+    files: Mutex<(Vec<u8>, Vec<u8>)>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl Certificate {
+    fn load(
+        cert_path: &Path,
+        key_path: &Path,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<Self, String> {
+        let (cert, key) = (read(cert_path)?, read(key_path)?);
+        let current = parse(cert_path, &cert, key_path, &key, provider)?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            provider: Arc::clone(provider),
+            files: Mutex::new((cert, key)),
+            current: RwLock::new(Arc::new(current)),
+        })
+    }
+
+    /// Ok(true) when the files changed and the new pair is now served
+    fn reload(&self) -> Result<bool, String> {
+        let (cert, key) = (read(&self.cert_path)?, read(&self.key_path)?);
+        let mut files = self.files.lock().unwrap();
+        if files.0 == cert && files.1 == key {
+            return Ok(false);
+        }
+        let parsed = parse(&self.cert_path, &cert, &self.key_path, &key, &self.provider);
+        *files = (cert, key);
+        *self.current.write().unwrap() = Arc::new(parsed?);
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for Certificate {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.current.read().unwrap()))
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// A PEM certificate chain, leaf first, and the PEM private key it was issued for
+fn parse(
+    cert_path: &Path,
+    cert: &[u8],
+    key_path: &Path,
+    key: &[u8],
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, String> {
+    let invalid = |path: &Path, e: &dyn std::fmt::Display| {
+        format!("Invalid TLS file {}: {}", path.display(), e)
+    };
+    let chain = CertificateDer::pem_slice_iter(cert)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(cert_path, &e))?;
+    if chain.is_empty() {
+        return Err(invalid(cert_path, &"no certificate found"));
+    }
+    let key = PrivateKeyDer::from_pem_slice(key).map_err(|e| invalid(key_path, &e))?;
+    CertifiedKey::from_der(chain, key, provider).map_err(|e| invalid(key_path, &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, text: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pathfinder-tls-{}-{}", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn served(certificate: &Certificate) -> Vec<u8> {
+        certificate.current.read().unwrap().cert[0].to_vec()
+    }
+
+    #[test]
+    fn test_reloads_changed_files_and_keeps_serving_on_errors() {
+        let first = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let second = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = write("reload.crt", &first.cert.pem());
+        let key_path = write("reload.key", &first.key_pair.serialize_pem());
+        let provider = Arc::new(ring::default_provider());
+
+        let certificate = Certificate::load(&cert_path, &key_path, &provider).unwrap();
+        assert_eq!(served(&certificate), first.cert.der().to_vec());
+        assert_eq!(certificate.reload(), Ok(false));
+
+        // Halfway through a renewal the key no longer matches the certificate
+        std::fs::write(&cert_path, second.cert.pem()).unwrap();
+        let error = certificate.reload().unwrap_err();
+        assert!(error.starts_with("Invalid TLS file"), "{}", error);
+        assert_eq!(served(&certificate), first.cert.der().to_vec());
+
+        std::fs::write(&key_path, second.key_pair.serialize_pem()).unwrap();
+        assert_eq!(certificate.reload(), Ok(true));
+        assert_eq!(served(&certificate), second.cert.der().to_vec());
+
+        std::fs::write(&cert_path, "not a certificate").unwrap();
+        assert_eq!(
+            certificate.reload().unwrap_err(),
+            format!(
+                "Invalid TLS file {}: no certificate found",
+                cert_path.display()
+            )
+        );
+        std::fs::remove_file(cert_path).unwrap();
+        std::fs::remove_file(key_path).unwrap();
+    }
+}
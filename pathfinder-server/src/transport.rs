@@ -1,37 +1,249 @@
+use futures_util::stream::SplitSink;
 use futures_util::SinkExt;
-use serde_json;
-use shared_types::router::{WireResponse, WireResponseSender};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use log::error;
+use shared_types::codec::Encoding;
+use shared_types::router::{RateLimited, ResponseEnum, WireResponse, WireResponseSender};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 
+/// Spawns the task that owns the write half of a connection
+/// Frames go out in the order they were queued; the writer stops after a close frame
+pub fn spawn_writer<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    mut sink: SplitSink<WebSocketStream<S>, Message>,
+) -> (mpsc::UnboundedSender<Message>, JoinHandle<()>) {
+    let (frames, mut queue) = mpsc::unbounded_channel::<Message>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = queue.recv().await {
+            let closing = message.is_close();
+            if let Err(e) = sink.send(message).await {
+                error!("Failed to send WebSocket message: {}", e);
+                break;
+            }
+            if closing {
+                break;
+            }
+        }
+    });
+    (frames, writer)
+}
+
+/// Request ids of the calls a connection is still running
+#[derive(Default)]
+pub struct InFlight {
+    ids: Mutex<HashSet<usize>>,
+    idle: Notify,
+}
+
+impl InFlight {
+    pub fn start(&self, id: usize) {
+        self.ids.lock().unwrap().insert(id);
+    }
+
+    /// Runs `send` if `id` is in flight, or regardless when `always`, and ends
+    /// the call when `terminal`. Sending under the lock keeps the frame ahead of
+    /// anything queued once the call has ended, like the shutdown close frame
+    fn respond(&self, id: usize, terminal: bool, always: bool, send: impl FnOnce()) {
+        let mut ids = self.ids.lock().unwrap();
+        if always || ids.contains(&id) {
+            send();
+        }
+        if terminal {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.idle.notify_waiters();
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.lock().unwrap().len()
+    }
+
+    /// Resolves once no call is in flight
+    pub async fn wait_idle(&self) {
+        loop {
+            // Created before the check so a call ending in between still wakes it
+            let idle = self.idle.notified();
+            if self.ids.lock().unwrap().is_empty() {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Stops tracking every call still in flight, returning their ids
+    /// Anything those calls send afterwards is dropped by `WebSocketSender`
+    pub fn abandon_all(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.ids.lock().unwrap().drain().collect();
+        ids.sort_unstable();
+        self.idle.notify_waiters();
+        ids
+    }
+}
+
+/// Queues responses for the connection's writer task, encoded as negotiated
+/// Only calls in flight may respond, so nothing follows a call's terminal frame
+/// (`Complete`, `Error` or `Aborted`). `Aborted` always goes out, as the router
+/// also sends it for ids it does not know
 #[derive(Clone)]
 pub struct WebSocketSender {
-    sender: Arc<Mutex<futures_util::stream::SplitSink<tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>, Message>>>,
+    frames: mpsc::UnboundedSender<Message>,
+    in_flight: Arc<InFlight>,
+    encoding: Encoding,
 }
 
 impl WebSocketSender {
     pub fn new(
-        sender: futures_util::stream::SplitSink<tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>, Message>,
+        frames: mpsc::UnboundedSender<Message>,
+        in_flight: Arc<InFlight>,
+        encoding: Encoding,
     ) -> Self {
         Self {
-            sender: Arc::new(Mutex::new(sender)),
+            frames,
+            in_flight,
+            encoding,
+        }
+    }
+
+    /// Answers a call that was turned away before it started
+    pub fn reject(&self, id: usize, refusal: RateLimited) {
+        let frame = self.frame(&WireResponse(id, ResponseEnum::RateLimited(refusal)));
+        let _ = self.frames.send(frame);
+    }
+
+    fn frame(&self, response: &WireResponse) -> Message {
+        let bytes = encode(self.encoding, response);
+        match self.encoding {
+            Encoding::Json => Message::Text(String::from_utf8(bytes).unwrap_or_default()),
+            Encoding::MessagePack => Message::Binary(bytes),
         }
     }
 }
 
 impl WireResponseSender for WebSocketSender {
     fn send_response(&self, response: WireResponse) {
-        let sender = Arc::clone(&self.sender);
-        tokio::spawn(async move {
-            let json = serde_json::to_string(&response).unwrap_or_else(|e| {
-                format!(r#"[0,{{"Error":"Failed to serialize response: {}"}}]"#, e)
-            });
-            
-            let mut sender = sender.lock().await;
-            if let Err(e) = sender.send(Message::Text(json)).await {
-                eprintln!("Failed to send WebSocket message: {}", e);
-            }
+        let WireResponse(id, body) = &response;
+        let terminal = is_terminal(body);
+        let always = matches!(body, ResponseEnum::Aborted(_));
+
+        let frame = self.frame(&response);
+        self.in_flight.respond(*id, terminal, always, || {
+            // The writer is gone once the connection has closed; the response has nowhere to go
+            let _ = self.frames.send(frame);
         });
     }
 }
+
+/// Encodes `response`, or an `Error` in its place when it cannot be encoded
+pub fn encode(encoding: Encoding, response: &WireResponse) -> Vec<u8> {
+    encoding.encode_response(response).unwrap_or_else(|e| {
+        let error = WireResponse(
+            response.0,
+            ResponseEnum::Error(format!("Failed to serialize response: {}", e)),
+        );
+        // A bare error string always encodes
+        encoding.encode_response(&error).unwrap_or_default()
+    })
+}
+
+/// Whether `body` ends its call: nothing may follow it for the same id
+pub fn is_terminal(body: &ResponseEnum) -> bool {
+    match body {
+        ResponseEnum::Aborted(_)
+        | ResponseEnum::Complete(_)
+        | ResponseEnum::Error(_)
+        | ResponseEnum::RateLimited(_) => true,
+        ResponseEnum::N(_) | ResponseEnum::Progress(_) => false,
+    }
+}
+
+/// Hands responses to the task answering an HTTP request
+/// An HTTP request carries a single call, so the task stops at its terminal frame
+#[derive(Clone)]
+pub struct HttpSender {
+    responses: mpsc::UnboundedSender<WireResponse>,
+}
+
+impl HttpSender {
+    pub fn new(responses: mpsc::UnboundedSender<WireResponse>) -> Self {
+        Self { responses }
+    }
+}
+
+impl WireResponseSender for HttpSender {
+    fn send_response(&self, response: WireResponse) {
+        // The request is gone once its answer was sent or the client left
+        let _ = self.responses.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sender() -> (
+        WebSocketSender,
+        Arc<InFlight>,
+        mpsc::UnboundedReceiver<Message>,
+    ) {
+        let (frames, queue) = mpsc::unbounded_channel();
+        let in_flight = Arc::new(InFlight::default());
+        (
+            WebSocketSender::new(frames, Arc::clone(&in_flight), Encoding::Json),
+            in_flight,
+            queue,
+        )
+    }
+
+    fn text(queue: &mut mpsc::UnboundedReceiver<Message>) -> Vec<String> {
+        std::iter::from_fn(|| queue.try_recv().ok())
+            .map(|message| message.into_text().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_nothing_follows_a_terminal_frame() {
+        let (sender, in_flight, mut queue) = sender();
+        in_flight.start(1);
+        in_flight.start(2);
+
+        sender.send_response(WireResponse(1, ResponseEnum::Complete("done".to_string())));
+        sender.send_response(WireResponse(1, ResponseEnum::Error("late".to_string())));
+        sender.send_response(WireResponse(2, ResponseEnum::Aborted("stop".to_string())));
+        sender.send_response(WireResponse(2, ResponseEnum::Complete("late".to_string())));
+
+        assert_eq!(
+            text(&mut queue),
+            vec![r#"[1,{"Complete":"done"}]"#, r#"[2,{"Aborted":"stop"}]"#]
+        );
+        assert_eq!(in_flight.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle_resolves_when_calls_finish_or_are_abandoned() {
+        let (sender, in_flight, mut queue) = sender();
+        in_flight.start(3);
+        in_flight.start(4);
+
+        let finisher = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            sender.send_response(WireResponse(3, ResponseEnum::Complete(String::new())));
+            sender
+        });
+        let waited = tokio::time::timeout(Duration::from_millis(100), in_flight.wait_idle()).await;
+        assert!(waited.is_err(), "call 4 is still running");
+
+        let sender = finisher.await.unwrap();
+        assert_eq!(in_flight.abandon_all(), vec![4]);
+        in_flight.wait_idle().await;
+
+        sender.send_response(WireResponse(4, ResponseEnum::Complete(String::new())));
+        assert_eq!(text(&mut queue), vec![r#"[3,{"Complete":""}]"#]);
+    }
+}
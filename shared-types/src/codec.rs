@@ -0,0 +1,126 @@
+use crate::router::{Request, RequestEnum, WireResponse};
+
+/// How requests and responses are encoded on the wire
+/// Transports that negotiate (like WebSocket subprotocols) use `subprotocol`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// JSON text, the default when nothing is negotiated
+    #[default]
+    Json,
+    /// MessagePack with structs as maps, so optional fields may be left out
+    MessagePack,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 2] = [Encoding::Json, Encoding::MessagePack];
+
+    pub fn subprotocol(self) -> &'static str {
+        match self {
+            Encoding::Json => "pathfinder.json",
+            Encoding::MessagePack => "pathfinder.msgpack",
+        }
+    }
+
+    pub fn from_subprotocol(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.subprotocol() == name)
+    }
+
+    /// Whether frames are binary rather than text
+    pub fn is_binary(self) -> bool {
+        self == Encoding::MessagePack
+    }
+
+    pub fn encode_response(self, response: &WireResponse) -> Result<Vec<u8>, String> {
+        self.encode(response)
+    }
+
+    pub fn decode_response(self, bytes: &[u8]) -> Result<WireResponse, String> {
+        self.decode(bytes)
+    }
+
+    pub fn encode_request(self, request: &RequestEnum) -> Result<Vec<u8>, String> {
+        self.encode(request)
+    }
+
+    pub fn decode_request(self, bytes: &[u8]) -> Result<Request, String> {
+        self.decode(bytes)
+    }
+
+    fn encode<T: serde::Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Encoding::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Encoding::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::ResponseEnum;
+    use crate::{Edge, Point};
+
+    fn call() -> RequestEnum {
+        let points: Vec<Point> = (0..100)
+            .map(|i| Point {
+                x: i as f64 * 0.123456789,
+                y: i as f64 / 7.0,
+                z: (i % 2 == 0).then_some(1.5),
+            })
+            .collect();
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "Call": [4, {"find_shortest_path": {
+                "points": points,
+                "edges": [Edge { from: 0, to: 99 }],
+                "start_idx": 0,
+                "end_idx": 99,
+            }}]
+        }))
+        .unwrap();
+        request.0
+    }
+
+    #[test]
+    fn test_requests_round_trip_and_msgpack_is_smaller() {
+        let request = call();
+        let json = Encoding::Json.encode_request(&request).unwrap();
+        let packed = Encoding::MessagePack.encode_request(&request).unwrap();
+        assert!(
+            packed.len() < json.len(),
+            "{} >= {}",
+            packed.len(),
+            json.len()
+        );
+
+        // Compare through JSON, since the protocol types have no PartialEq
+        let decoded = Encoding::MessagePack.decode_request(&packed).unwrap();
+        assert_eq!(serde_json::to_vec(&decoded.0).unwrap(), json);
+    }
+
+    #[test]
+    fn test_subprotocols_and_responses() {
+        for encoding in Encoding::ALL {
+            assert_eq!(
+                Encoding::from_subprotocol(encoding.subprotocol()),
+                Some(encoding)
+            );
+            let bytes = encoding
+                .encode_response(&WireResponse(2, ResponseEnum::Complete("done".to_string())))
+                .unwrap();
+            let WireResponse(id, body) = encoding.decode_response(&bytes).unwrap();
+            assert_eq!(id, 2);
+            assert!(matches!(body, ResponseEnum::Complete(notes) if notes == "done"));
+        }
+        assert_eq!(Encoding::from_subprotocol("pathfinder.cbor"), None);
+        assert!(Encoding::MessagePack
+            .decode_request(b"not msgpack")
+            .is_err());
+    }
+}
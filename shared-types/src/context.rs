@@ -1,3 +1,7 @@
+use crate::router::CallLimits;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 /// Context passed to all handler methods containing session information
 #[derive(Debug, Clone)]
 pub struct Context {
@@ -12,6 +16,12 @@ pub struct Context {
     
     /// Timestamp when session was created
     pub created_at: u64,
+
+    /// Set when the client aborts this call
+    cancelled: Arc<AtomicBool>,
+
+    /// Limits of the session, for graphs that grow after the call was checked
+    limits: CallLimits,
 }
 
 impl Context {
@@ -21,6 +31,8 @@ impl Context {
             request_id,
             user_id: None,
             created_at: chrono::Utc::now().timestamp() as u64,
+            cancelled: Arc::default(),
+            limits: CallLimits::unlimited(),
         }
     }
     
@@ -28,4 +40,26 @@ impl Context {
         self.user_id = Some(user_id);
         self
     }
+
+    /// Shares `cancelled` with whoever may abort the call
+    pub fn with_cancellation(mut self, cancelled: Arc<AtomicBool>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: CallLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The session's limits; unlimited unless the receiver set them
+    pub fn limits(&self) -> &CallLimits {
+        &self.limits
+    }
+
+    /// Whether the client has aborted the call; long computations check this
+    /// between steps and stop early
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 }
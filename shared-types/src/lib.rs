@@ -1,15 +1,19 @@
 use shared_types_proc::protocol;
 
+pub mod codec;
 pub mod context;
 pub mod receiver;
 pub mod router;
 pub mod storage;
 
-/// A 2D point with x and y coordinates
+/// A point with x and y coordinates and an optional elevation
 #[protocol("wasm")]
 pub struct Point {
     pub x: f64,
     pub y: f64,
+    /// Elevation; left out of the JSON when absent, so 2D clients see no change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<f64>,
 }
 
 /// An edge connecting two points
@@ -24,6 +28,72 @@ pub struct Edge {
 pub struct PathResult {
     pub path: Vec<usize>,
     pub distance: f64,
+    /// Total turn penalty along the path, set when routing with turn costs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn_cost: Option<f64>,
+    /// Simplified or smoothed geometry, set when post-processing was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<PathGeometry>,
+}
+
+/// Drawable geometry of a path after post-processing
+#[protocol("wasm")]
+pub struct PathGeometry {
+    /// Path nodes kept by shortcutting and simplification, in order
+    pub waypoints: Vec<usize>,
+    /// The smoothed curve when smoothing was requested, otherwise the waypoint positions
+    pub points: Vec<Point>,
+    /// Length of the polyline through `points`
+    pub length: f64,
+}
+
+/// Curve fitted through the waypoints of a path
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum Smoothing {
+    /// Corner cutting; every iteration doubles the number of points
+    Chaikin { iterations: usize },
+    /// Centripetal Catmull-Rom spline, which passes through every waypoint
+    CatmullRom { samples_per_segment: usize },
+}
+
+/// Post-processing applied to a path before it is returned, in field order
+#[protocol("wasm")]
+pub struct PathPostProcessing {
+    /// Skip waypoints wherever a straight line clears every obstacle
+    pub shortcut: Option<bool>,
+    /// Ramer-Douglas-Peucker tolerance: drop waypoints this close to the simplified line
+    pub simplify_tolerance: Option<f64>,
+    /// Areas straight lines may not cross, in addition to `forbidden_regions`
+    pub obstacles: Option<Vec<Region>>,
+    pub smoothing: Option<Smoothing>,
+}
+
+/// An area that paths must not enter
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum Region {
+    /// Axis-aligned rectangle spanned by two opposite corners
+    Rect { min: Point, max: Point },
+    /// Simple polygon with vertices in order; the closing edge is implied
+    Polygon { vertices: Vec<Point> },
+}
+
+/// Cost of turning at an intersection, growing with the turn angle
+#[protocol("wasm")]
+pub struct TurnPenalty {
+    /// Cost per degree of deviation from straight ahead
+    pub per_degree: f64,
+    /// Extra cost for turning back the way you came (a 180 degree turn)
+    pub u_turn_cost: Option<f64>,
+}
+
+/// A forbidden manoeuvre: arriving on `from_edge` at `via_node` and leaving on `to_edge`
+#[protocol("wasm")]
+pub struct TurnRestriction {
+    pub from_edge: usize,
+    pub via_node: usize,
+    pub to_edge: usize,
 }
 
 /// Parameters for finding the shortest path between two points
@@ -34,6 +104,26 @@ pub struct ShortestPathParams {
     pub edges: Vec<Edge>,
     pub start_idx: usize,
     pub end_idx: usize,
+    /// Point indices the path must not pass through
+    pub forbidden_nodes: Option<Vec<usize>>,
+    /// Edge indices the path must not use
+    pub forbidden_edges: Option<Vec<usize>>,
+    /// Regions no point or edge of the path may touch
+    pub forbidden_regions: Option<Vec<Region>>,
+    /// Route over edges instead of nodes and add this cost at every turn
+    pub turn_penalty: Option<TurnPenalty>,
+    /// Turns that may not be taken; setting this also enables edge-based routing
+    pub banned_turns: Option<Vec<TurnRestriction>>,
+    /// Search from both ends at once; same distance, fewer nodes explored on large graphs,
+    /// but ties between equally short paths may be broken differently.
+    /// Ignored by edge-based routing
+    pub bidirectional: Option<bool>,
+    /// Simplify or smooth the path; the result gets a `geometry`
+    pub post_processing: Option<PathPostProcessing>,
+    /// Extra cost per unit of elevation gained or lost, for points with `z`
+    pub slope_penalty: Option<f64>,
+    /// Edges whose grade (elevation change over horizontal length) exceeds this are not used
+    pub max_grade: Option<f64>,
 }
 
 /// Graph statistics and metrics
@@ -53,6 +143,310 @@ pub struct GraphMetricsParams {
     pub edges: Vec<Edge>,
 }
 
+/// An approximate round trip over a set of stops
+#[protocol("wasm")]
+pub struct TourResult {
+    /// Stops (point indices) in visiting order, starting at the first requested stop
+    pub tour: Vec<usize>,
+    /// Full point path following the graph, closed back to the first stop
+    pub path: Vec<usize>,
+    pub distance: f64,
+    /// Heuristic that produced this tour: "nearest_neighbour", "two_opt" or "or_opt"
+    pub stage: String,
+    /// Number of improvements applied since the initial tour
+    pub iteration: usize,
+}
+
+/// Parameters for solving a tour over a set of stops
+#[protocol("wasm")]
+#[codegen(fn = "solve_tour() -> TourResult")]
+pub struct TourParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Point indices to visit; the first stop is the start and end of the tour
+    pub stops: Vec<usize>,
+    /// Also run or-opt segment moves after 2-opt converges
+    pub or_opt: bool,
+}
+
+/// Per-node centrality scores, each indexed like the input points
+#[protocol("wasm")]
+pub struct CentralityResult {
+    /// Shortest-path betweenness (unnormalized, each unordered pair counted once)
+    pub betweenness: Vec<f64>,
+    /// Closeness by edge length, Wasserman-Faust scaled for disconnected graphs
+    pub closeness: Vec<f64>,
+    /// Number of distinct neighbours divided by `n - 1`
+    pub degree: Vec<f64>,
+    /// Number of source nodes searched; equals the node count when exact
+    pub sampled_sources: usize,
+}
+
+/// Parameters for computing node centrality
+#[protocol("wasm")]
+#[codegen(fn = "compute_centrality() -> CentralityResult")]
+pub struct CentralityParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Approximate betweenness and closeness from this many random sources
+    pub sample_size: Option<usize>,
+    /// Seed for choosing sample sources; defaults to 0
+    pub seed: Option<u64>,
+}
+
+/// Maximum flow between two points and the minimum cut that limits it
+#[protocol("wasm")]
+pub struct MaxFlowResult {
+    pub flow_value: f64,
+    /// Flow per edge, indexed like the input edges; negative means `to` -> `from`
+    pub edge_flows: Vec<f64>,
+    /// Indices of saturated edges separating the source side from the sink side
+    pub min_cut: Vec<usize>,
+    /// Points still reachable from the source in the residual graph
+    pub source_side: Vec<usize>,
+}
+
+/// Parameters for computing maximum flow over undirected edges
+#[protocol("wasm")]
+#[codegen(fn = "compute_max_flow() -> MaxFlowResult")]
+pub struct MaxFlowParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Capacity per edge, indexed like `edges`, usable in either direction
+    pub capacities: Vec<f64>,
+    pub source_idx: usize,
+    pub sink_idx: usize,
+}
+
+/// A path that no other path beats on both length and secondary cost
+#[protocol("wasm")]
+pub struct ParetoPath {
+    pub path: Vec<usize>,
+    pub distance: f64,
+    pub secondary_cost: f64,
+}
+
+/// Parameters for finding all Pareto-optimal paths between two points
+#[protocol("wasm")]
+#[codegen(fn = "find_pareto_paths() -> ParetoPath")]
+pub struct ParetoPathParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Non-negative second cost per edge (e.g. risk), indexed like `edges`
+    pub secondary_costs: Vec<f64>,
+    pub start_idx: usize,
+    pub end_idx: usize,
+    /// Stop after this many paths have been streamed
+    pub max_paths: Option<usize>,
+}
+
+/// Version of the graph stored for this session
+#[protocol("wasm")]
+pub struct GraphVersion {
+    /// Increases by one with every accepted batch of edits
+    pub version: u64,
+    pub point_count: usize,
+    pub edge_count: usize,
+}
+
+/// Parameters for storing a graph in the session, replacing any previous one
+#[protocol("wasm")]
+#[codegen(fn = "load_graph() -> GraphVersion")]
+pub struct LoadGraphParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Weight per edge, indexed like `edges`; `None` uses the Euclidean length
+    pub weights: Option<Vec<Option<f64>>>,
+}
+
+/// A change to the stored graph
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum GraphEdit {
+    /// Move a point; incident edges without a weight override change length
+    MovePoint { index: usize, point: Point },
+    /// Append an edge, which gets the next free edge index
+    AddEdge { edge: Edge, weight: Option<f64> },
+    /// Remove an edge; edges after it shift down by one index
+    RemoveEdge { index: usize },
+    /// Override an edge weight, or go back to its Euclidean length with `None`
+    SetEdgeWeight { index: usize, weight: Option<f64> },
+}
+
+/// Endpoints of a cached shortest path
+#[protocol("wasm")]
+pub struct PathKey {
+    pub start_idx: usize,
+    pub end_idx: usize,
+}
+
+/// Outcome of applying edits to the stored graph
+#[protocol("wasm")]
+pub struct GraphEditResult {
+    pub version: u64,
+    /// Cached paths dropped because the edits may have changed them
+    pub invalidated: Vec<PathKey>,
+    /// Cached paths the edits cannot have affected
+    pub retained: usize,
+}
+
+/// Parameters for editing the stored graph; edits apply in order, all or nothing
+#[protocol("wasm")]
+#[codegen(fn = "edit_graph() -> GraphEditResult")]
+pub struct EditGraphParams {
+    pub edits: Vec<GraphEdit>,
+}
+
+/// Shortest path on the stored graph
+#[protocol("wasm")]
+pub struct StoredPathResult {
+    pub path: Vec<usize>,
+    pub distance: f64,
+    /// Graph version the path was computed for
+    pub version: u64,
+    /// Whether the path came from the session cache
+    pub cached: bool,
+}
+
+/// Parameters for a shortest path query on the stored graph
+#[protocol("wasm")]
+#[codegen(fn = "find_stored_path() -> StoredPathResult")]
+pub struct StoredPathParams {
+    pub start_idx: usize,
+    pub end_idx: usize,
+}
+
+/// Summary of the contraction hierarchy stored for this session
+#[protocol("wasm")]
+pub struct HierarchyInfo {
+    pub point_count: usize,
+    pub edge_count: usize,
+    /// Edges added during contraction; fewer means faster queries
+    pub shortcut_count: usize,
+}
+
+/// Parameters for preprocessing a static graph into a contraction hierarchy,
+/// replacing any hierarchy stored for this session
+#[protocol("wasm")]
+#[codegen(fn = "prepare_graph() -> HierarchyInfo")]
+pub struct PrepareGraphParams {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+}
+
+/// Shortest path answered from the session's contraction hierarchy
+#[protocol("wasm")]
+pub struct PreparedPathResult {
+    pub path: Vec<usize>,
+    pub distance: f64,
+}
+
+/// Parameters for a shortest path query on the prepared graph
+#[protocol("wasm")]
+#[codegen(fn = "find_prepared_path() -> PreparedPathResult")]
+pub struct PreparedPathParams {
+    pub start_idx: usize,
+    pub end_idx: usize,
+}
+
+/// A graph file to import, as text
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum GraphSource {
+    /// FeatureCollection, Feature or geometry; LineStrings become edges, Points become points
+    GeoJson { text: String },
+    /// Graphviz DOT where every node has a `pos="x,y"` attribute
+    Dot { text: String },
+    /// Node list (`id,x,y`) and edge list (`from,to`), header rows optional
+    Csv { nodes: String, edges: String },
+}
+
+/// Points and edges parsed from a graph file
+#[protocol("wasm")]
+pub struct ImportedGraph {
+    pub points: Vec<Point>,
+    pub edges: Vec<Edge>,
+    /// Name of each point in the source (node id or feature id), empty if unnamed
+    pub names: Vec<String>,
+    /// Input nodes merged into an earlier point within the tolerance
+    pub merged_points: usize,
+    /// Edges left out because merging made them self-loops or repeats
+    pub dropped_edges: usize,
+}
+
+/// Parameters for importing a graph from text
+#[protocol("wasm")]
+#[codegen(fn = "import_graph() -> ImportedGraph")]
+pub struct ImportGraphParams {
+    pub source: GraphSource,
+    /// Points at most this far apart are merged into one; defaults to 1e-9
+    pub tolerance: Option<f64>,
+}
+
+/// Text formats a graph and path can be exported to
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum ExportFormat {
+    /// FeatureCollection of points, edges, forbidden regions and the path
+    GeoJson,
+    /// Graphviz DOT with pinned positions and the path in red
+    Dot,
+    /// Standalone SVG image
+    Svg,
+}
+
+/// An exported graph as text
+#[protocol("wasm")]
+pub struct ExportedGraph {
+    pub text: String,
+    /// MIME type of `text`, e.g. "image/svg+xml"
+    pub content_type: String,
+}
+
+/// Parameters for exporting a graph, e.g. for debugging or reports
+#[protocol("wasm")]
+#[codegen(fn = "export_graph() -> ExportedGraph")]
+pub struct ExportGraphParams {
+    /// The query, whose forbidden elements are drawn as well
+    pub graph: ShortestPathParams,
+    /// Path to highlight, typically the result of `find_shortest_path`
+    pub path: Option<PathResult>,
+    pub format: ExportFormat,
+}
+
+/// Random graph model for `generate_graph`
+#[protocol("wasm")]
+#[serde(tag = "type")]
+pub enum GraphModel {
+    /// Uniform random points, each joined to its `k` nearest neighbours
+    NearestNeighbours { count: usize, k: usize },
+    /// Evenly spaced lattice, optionally with both diagonals in every cell
+    Grid {
+        rows: usize,
+        cols: usize,
+        diagonals: Option<bool>,
+    },
+    /// Uniform random points with every pair joined independently with `probability`
+    ErdosRenyi { count: usize, probability: f64 },
+    /// Preferential attachment: each new point links to `attachments` existing
+    /// points chosen in proportion to their degree
+    BarabasiAlbert { count: usize, attachments: usize },
+    /// Uniform random points joined whenever they are at most `radius` apart
+    RandomGeometric { count: usize, radius: f64 },
+}
+
+/// Parameters for generating a random graph; equal seeds give equal graphs
+#[protocol("wasm")]
+#[codegen(fn = "generate_graph() -> GraphMetricsParams")]
+pub struct GenerateGraphParams {
+    pub model: GraphModel,
+    /// Defaults to 0
+    pub seed: Option<u64>,
+    /// Points are placed in `[0, width] x [0, height]`; both default to 1
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+}
+
 #[cfg(test)]
 #[cfg(feature = "codegen")]
 mod generate {
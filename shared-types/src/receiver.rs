@@ -1,25 +1,57 @@
 use crate::context::Context;
-use crate::router::{CallHandler, Request, WireResponseSender};
+use crate::router::{
+    CallHandler, CallLimits, Request, RequestEnum, ResponseEnum, WireResponse, WireResponseSender,
+};
 use crate::storage::Storage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
 /// Session receiver that handles requests for a single session/connection
 /// This can be instantiated in WASM, over WebSockets, or via HTTP
 pub struct Receiver<H: CallHandler, S: Storage> {
     session_id: String,
+    user_id: Option<String>,
+    limits: CallLimits,
     handler: H,
     storage: Option<S>,
+    /// Cancellation flags of the calls running now, set by an `Abort` for their id
+    running: Mutex<HashMap<usize, Arc<AtomicBool>>>,
 }
 
 impl<H: CallHandler, S: Storage> Receiver<H, S> {
     pub fn new(session_id: String, handler: H, storage: Option<S>) -> Self {
         Self {
             session_id,
+            user_id: None,
+            limits: CallLimits::default(),
             handler,
             storage,
+            running: Mutex::default(),
         }
     }
 
+    /// Marks every request on this session as made by `user_id`
+    pub fn with_user(mut self, user_id: String) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    /// Replaces the default `CallLimits`; calls over them are answered with `Error`
+    pub fn with_limits(mut self, limits: CallLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn handle_request(&self, request: Request, sender: Box<dyn WireResponseSender>) {
+        // Calls over the limits are refused before the handler does any work
+        if let RequestEnum::Call(id, call) = &request.0 {
+            if let Err(e) = self.limits.check(call) {
+                sender.send_response(WireResponse(*id, ResponseEnum::Error(e)));
+                return;
+            }
+        }
+
         // Extract request ID from the request
         let request_id = match &request.0 {
             crate::router::RequestEnum::Call(id, _) => *id,
@@ -27,13 +59,111 @@ impl<H: CallHandler, S: Storage> Receiver<H, S> {
         };
 
         // Create context for this request
-        let ctx = Context::new(self.session_id.clone(), request_id);
+        let mut ctx = Context::new(self.session_id.clone(), request_id).with_limits(self.limits);
+        if let Some(user_id) = &self.user_id {
+            ctx = ctx.with_user(user_id.clone());
+        }
 
-        // Handle the request
-        crate::router::handle_request(request, &ctx, &self.handler, sender);
+        match &request.0 {
+            RequestEnum::Call(..) => {
+                let cancelled = Arc::new(AtomicBool::new(false));
+                self.running
+                    .lock()
+                    .unwrap()
+                    .insert(request_id, Arc::clone(&cancelled));
+                let _running = RunningCall {
+                    running: &self.running,
+                    request_id,
+                };
+                let ctx = ctx.with_cancellation(cancelled);
+                crate::router::handle_request(request, &ctx, &self.handler, sender);
+            }
+            RequestEnum::Abort(..) => {
+                // The call stops at its next check; the router answers with `Aborted`
+                if let Some(cancelled) = self.running.lock().unwrap().get(&request_id) {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+                crate::router::handle_request(request, &ctx, &self.handler, sender);
+            }
+        }
     }
 
     pub fn storage(&self) -> Option<&S> {
         self.storage.as_ref()
     }
 }
+
+/// Removes a call's cancellation flag when it ends, even if its handler panics
+struct RunningCall<'a> {
+    running: &'a Mutex<HashMap<usize, Arc<AtomicBool>>>,
+    request_id: usize,
+}
+
+impl Drop for RunningCall<'_> {
+    fn drop(&mut self) {
+        self.running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::ObserverImpl;
+    use crate::storage::NoStorage;
+    use crate::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    struct PanickingHandler;
+
+    macro_rules! panicking_calls {
+        ($($call:ident($params:ty) -> $result:ty),* $(,)?) => {
+            $(fn $call(&self, _ctx: &Context, _params: $params, _tx: ObserverImpl<$result>) {
+                panic!("handler bug");
+            })*
+        };
+    }
+
+    impl CallHandler for PanickingHandler {
+        panicking_calls! {
+            find_shortest_path(ShortestPathParams) -> PathResult,
+            compute_graph_metrics(GraphMetricsParams) -> GraphMetrics,
+            solve_tour(TourParams) -> TourResult,
+            compute_centrality(CentralityParams) -> CentralityResult,
+            compute_max_flow(MaxFlowParams) -> MaxFlowResult,
+            find_pareto_paths(ParetoPathParams) -> ParetoPath,
+            load_graph(LoadGraphParams) -> GraphVersion,
+            edit_graph(EditGraphParams) -> GraphEditResult,
+            find_stored_path(StoredPathParams) -> StoredPathResult,
+            import_graph(ImportGraphParams) -> ImportedGraph,
+            export_graph(ExportGraphParams) -> ExportedGraph,
+            generate_graph(GenerateGraphParams) -> GraphMetricsParams,
+            prepare_graph(PrepareGraphParams) -> HierarchyInfo,
+            find_prepared_path(PreparedPathParams) -> PreparedPathResult,
+        }
+    }
+
+    struct NoSender;
+
+    impl WireResponseSender for NoSender {
+        fn send_response(&self, _wire_response: WireResponse) {}
+    }
+
+    #[test]
+    fn test_panicking_call_is_no_longer_running() {
+        let receiver = Receiver::new("session".to_string(), PanickingHandler, None::<NoStorage>);
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "Call": [7, {"compute_graph_metrics": {"points": [], "edges": []}}]
+        }))
+        .unwrap();
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            receiver.handle_request(request, Box::new(NoSender))
+        }));
+
+        assert!(result.is_err());
+        assert!(receiver.running.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+use super::router_gen::CallGen;
+use crate::{GenerateGraphParams, GraphModel, GraphSource};
+
+/// Bounds on the calls a session accepts, checked before any work starts
+/// Work is an estimate in rough elementary steps (a heap operation, an edge
+/// relaxation), so `max_work` compares calls of different kinds on one scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallLimits {
+    pub max_points: Option<usize>,
+    pub max_edges: Option<usize>,
+    pub max_work: Option<u64>,
+}
+
+impl Default for CallLimits {
+    /// Generous enough for interactive use, small enough that one call cannot
+    /// exhaust a shared server's memory or keep a thread busy for hours
+    fn default() -> Self {
+        Self {
+            max_points: Some(1_000_000),
+            max_edges: Some(5_000_000),
+            max_work: Some(10_000_000_000),
+        }
+    }
+}
+
+impl CallLimits {
+    /// No limits, for a session that only its own user can slow down (e.g. WASM)
+    pub fn unlimited() -> Self {
+        Self {
+            max_points: None,
+            max_edges: None,
+            max_work: None,
+        }
+    }
+
+    /// Err naming the first limit `call` exceeds
+    pub(crate) fn check(&self, call: &CallGen) -> Result<(), String> {
+        let size = call.size();
+        self.check_graph(size.points, size.edges)?;
+        if self.max_work.is_some_and(|max| size.work > max) {
+            return Err(format!(
+                "Call needs an estimated {} steps of work; at most {} are allowed",
+                size.work,
+                self.max_work.unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Err when a graph of this size is over `max_points` or `max_edges`
+    /// Also for graphs that grow after the call was checked, like edited stored graphs
+    pub fn check_graph(&self, points: u64, edges: u64) -> Result<(), String> {
+        if let Some(max) = self.max_points.filter(|&max| points > max as u64) {
+            return Err(format!(
+                "Graph has {} points; at most {} are allowed",
+                points, max
+            ));
+        }
+        if let Some(max) = self.max_edges.filter(|&max| edges > max as u64) {
+            return Err(format!(
+                "Graph has {} edges; at most {} are allowed",
+                edges, max
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Points and edges a call carries (or generates) and the work it is estimated to need
+#[derive(Debug, Default, PartialEq)]
+struct CallSize {
+    points: u64,
+    edges: u64,
+    work: u64,
+}
+
+impl CallSize {
+    fn graph(points: usize, edges: usize, work: f64) -> Self {
+        Self {
+            points: points as u64,
+            edges: edges as u64,
+            // Saturates rather than wrapping for absurd inputs
+            work: work.min(u64::MAX as f64) as u64,
+        }
+    }
+}
+
+/// One Dijkstra-like search: every point and edge, with a heap operation each
+fn search(points: usize, edges: usize) -> f64 {
+    (points as f64 + edges as f64) * (points as f64 + 2.0).log2()
+}
+
+impl CallGen {
+    fn size(&self) -> CallSize {
+        match self {
+            CallGen::find_shortest_path(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                // Edge-based routing searches directed edges, each with its own turns
+                let edge_based = p.turn_penalty.is_some() || p.banned_turns.is_some();
+                let work = if edge_based {
+                    let turns = 2.0 * e as f64 * (2.0 * e as f64 / v.max(1) as f64);
+                    search(2 * e, 0) + turns
+                } else {
+                    search(v, e)
+                };
+                CallSize::graph(v, e, work)
+            }
+            CallGen::compute_graph_metrics(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                CallSize::graph(v, e, v as f64 + e as f64)
+            }
+            CallGen::solve_tour(p) => {
+                let (v, e, stops) = (p.points.len(), p.edges.len(), p.stops.len() as f64);
+                // A search per stop for the distance table, then cubic local search
+                CallSize::graph(v, e, stops * search(v, e) + stops.powi(3))
+            }
+            CallGen::compute_centrality(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                let sources = p.sample_size.unwrap_or(v).min(v);
+                CallSize::graph(v, e, sources as f64 * search(v, e))
+            }
+            CallGen::compute_max_flow(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                // Dinic's bound is V^2 E; real graphs need a few phases, each V E at most
+                CallSize::graph(v, e, v as f64 * e as f64)
+            }
+            CallGen::find_pareto_paths(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                // Each Pareto path found can leave a label at every point; the search
+                // itself stops once its labels outgrow this estimate
+                let paths = p.max_paths.unwrap_or(v).min(v).max(1);
+                CallSize::graph(v, e, paths as f64 * search(v, e))
+            }
+            CallGen::load_graph(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                CallSize::graph(v, e, v as f64 + e as f64)
+            }
+            CallGen::prepare_graph(p) => {
+                let (v, e) = (p.points.len(), p.edges.len());
+                // Each contraction runs a bounded witness search around the point
+                CallSize::graph(v, e, 10.0 * search(v, e))
+            }
+            CallGen::export_graph(p) => {
+                let (v, e) = (p.graph.points.len(), p.graph.edges.len());
+                CallSize::graph(v, e, v as f64 + e as f64)
+            }
+            // The handler checks the stored graph's size once the edits are applied
+            CallGen::edit_graph(p) => CallSize::graph(0, 0, p.edits.len() as f64),
+            CallGen::import_graph(p) => {
+                let bytes = match &p.source {
+                    GraphSource::GeoJson { text } | GraphSource::Dot { text } => text.len(),
+                    GraphSource::Csv { nodes, edges } => nodes.len() + edges.len(),
+                };
+                // The graph's size is only known once parsed, so the parser checks it
+                // then; each point is merged with its neighbours through a grid, so
+                // work follows the text length
+                CallSize::graph(0, 0, bytes as f64)
+            }
+            CallGen::generate_graph(p) => generated_size(p),
+            // Stored graphs were checked when loaded, and queries on them are single searches
+            CallGen::find_stored_path(_) | CallGen::find_prepared_path(_) => CallSize::default(),
+        }
+    }
+}
+
+/// Expected points and edges of a generated graph, and the work to build it
+fn generated_size(params: &GenerateGraphParams) -> CallSize {
+    match params.model {
+        GraphModel::NearestNeighbours { count, k } => {
+            // Every point measures the distance to every other
+            let edges = count.saturating_mul(k);
+            CallSize::graph(count, edges, count as f64 * count as f64)
+        }
+        GraphModel::Grid {
+            rows,
+            cols,
+            diagonals,
+        } => {
+            let points = rows.saturating_mul(cols);
+            let per_point = if diagonals == Some(true) { 4 } else { 2 };
+            let edges = points.saturating_mul(per_point);
+            CallSize::graph(points, edges, points as f64 + edges as f64)
+        }
+        GraphModel::ErdosRenyi { count, probability } => {
+            let pairs = count as f64 * count as f64 / 2.0;
+            let edges = (pairs * probability.clamp(0.0, 1.0)) as usize;
+            CallSize::graph(count, edges, pairs)
+        }
+        GraphModel::BarabasiAlbert { count, attachments } => {
+            let edges = count.saturating_mul(attachments);
+            CallSize::graph(count, edges, edges as f64 * (count.max(2) as f64).log2())
+        }
+        GraphModel::RandomGeometric { count, radius } => {
+            // Points are compared with those in the 3 x 3 cells of side `radius` around them
+            let area = params.width.unwrap_or(1.0) * params.height.unwrap_or(1.0);
+            let cell_share = (radius * radius / area).clamp(0.0, 1.0);
+            let pairs = count as f64 * count as f64 / 2.0;
+            let edges = pairs * (std::f64::consts::PI * cell_share).min(1.0);
+            let work = count as f64 + 18.0 * pairs * cell_share;
+            CallSize::graph(count, edges as usize, work)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(value: serde_json::Value) -> CallGen {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn path_call(points: usize) -> CallGen {
+        let points: Vec<_> = (0..points).map(|i| json!({"x": i, "y": 0})).collect();
+        let edges: Vec<_> = (1..points.len())
+            .map(|i| json!({"from": i - 1, "to": i}))
+            .collect();
+        call(json!({"find_shortest_path": {
+            "points": points, "edges": edges, "start_idx": 0, "end_idx": 1
+        }}))
+    }
+
+    #[test]
+    fn test_rejects_calls_over_limits() {
+        let limits = CallLimits {
+            max_points: Some(100),
+            max_edges: Some(50),
+            max_work: Some(1_000_000),
+        };
+        assert_eq!(limits.check(&path_call(40)), Ok(()));
+        assert_eq!(
+            limits.check(&path_call(101)).unwrap_err(),
+            "Graph has 101 points; at most 100 are allowed"
+        );
+        assert_eq!(
+            limits.check(&path_call(60)).unwrap_err(),
+            "Graph has 59 edges; at most 50 are allowed"
+        );
+        assert_eq!(CallLimits::unlimited().check(&path_call(101)), Ok(()));
+    }
+
+    #[test]
+    fn test_generated_graphs_count_towards_limits() {
+        let huge_grid = call(json!({"generate_graph": {
+            "model": {"type": "Grid", "rows": 100_000, "cols": 100_000, "diagonals": null},
+            "seed": null, "width": null, "height": null
+        }}));
+        assert_eq!(
+            CallLimits::default().check(&huge_grid).unwrap_err(),
+            "Graph has 10000000000 points; at most 1000000 are allowed"
+        );
+
+        // Few points, but every one compared with every other
+        let dense = call(json!({"generate_graph": {
+            "model": {"type": "NearestNeighbours", "count": 200_000, "k": 2},
+            "seed": null, "width": null, "height": null
+        }}));
+        let error = CallLimits::default().check(&dense).unwrap_err();
+        assert!(
+            error.starts_with("Call needs an estimated 40000000000 steps"),
+            "{}",
+            error
+        );
+    }
+}
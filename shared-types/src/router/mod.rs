@@ -2,10 +2,15 @@ use crate::context::Context;
 use shared_types_proc::protocol;
 use std::marker::PhantomData;
 
+mod limits;
 mod router_gen;
+pub use limits::CallLimits;
 pub use router_gen::CallHandler;
 
+/// Calls carry whole graphs inline; a request is decoded once and handed straight
+/// to the handler, so boxing the large variant would buy nothing
 #[protocol("router")]
+#[allow(clippy::large_enum_variant)]
 pub enum RequestEnum {
     Abort(usize, String),
     Call(usize, router_gen::CallGen),
@@ -20,6 +25,63 @@ pub enum ResponseEnum {
     Error(String),
     Complete(String),
     N(router_gen::ResponseNextGen),
+    Progress(Progress),
+    /// The server turned the call away under its rate limits; ends the call
+    RateLimited(RateLimited),
+}
+
+/// A call refused by a server's rate limits, in place of its responses
+#[protocol("router")]
+pub struct RateLimited {
+    /// Which limit applied: "in-flight", "connection" or "user"
+    pub scope: String,
+    /// How long until the call would be accepted, when the server knows
+    pub retry_after_ms: Option<u64>,
+    /// Human-readable reason, for clients that only show it
+    pub message: String,
+}
+
+/// Non-terminal progress report for long-running calls
+#[protocol("router")]
+pub struct Progress {
+    /// Estimated completion between 0.0 and 1.0
+    pub fraction: f64,
+    /// Human-readable label for the current stage
+    pub stage: String,
+    /// Units of work finished in the current stage, if countable
+    pub completed: Option<usize>,
+    /// Units of work in the current stage, if countable
+    pub total: Option<usize>,
+}
+
+impl Progress {
+    /// Progress at `fraction`, clamped to 0.0..=1.0; a non-finite estimate reads as 0.0
+    pub fn new(fraction: f64, stage: impl Into<String>) -> Self {
+        Self {
+            fraction: if fraction.is_finite() {
+                fraction.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+            stage: stage.into(),
+            completed: None,
+            total: None,
+        }
+    }
+
+    /// Progress through a countable stage, with the fraction derived from the counters
+    pub fn counted(stage: impl Into<String>, completed: usize, total: usize) -> Self {
+        let fraction = if total > 0 {
+            completed as f64 / total as f64
+        } else {
+            1.0
+        };
+        Self {
+            completed: Some(completed),
+            total: Some(total),
+            ..Self::new(fraction, stage)
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -103,6 +165,11 @@ impl<T: ToResponseNextGen> Emitter<T> {
             ResponseEnum::N(value.to_response_next_gen()),
         ));
     }
+
+    pub fn progress(&self, progress: Progress) {
+        self.sender
+            .send_response(WireResponse(self.request_id, ResponseEnum::Progress(progress)));
+    }
 }
 
 impl<T: ToResponseNextGen> Completer<T> {
@@ -127,6 +194,11 @@ impl<T: ToResponseNextGen> ObserverImpl<T> {
         ));
     }
 
+    pub fn progress(&self, progress: Progress) {
+        self.sender
+            .send_response(WireResponse(self.request_id, ResponseEnum::Progress(progress)));
+    }
+
     pub fn error(self, error: String) {
         self.sender
             .send_response(WireResponse(self.request_id, ResponseEnum::Error(error)));
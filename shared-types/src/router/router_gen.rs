@@ -16,6 +16,78 @@ pub trait CallHandler {
         params: GraphMetricsParams,
         tx: ObserverImpl<GraphMetrics>,
     );
+    fn solve_tour(
+        &self,
+        ctx: &Context,
+        params: TourParams,
+        tx: ObserverImpl<TourResult>,
+    );
+    fn compute_centrality(
+        &self,
+        ctx: &Context,
+        params: CentralityParams,
+        tx: ObserverImpl<CentralityResult>,
+    );
+    fn compute_max_flow(
+        &self,
+        ctx: &Context,
+        params: MaxFlowParams,
+        tx: ObserverImpl<MaxFlowResult>,
+    );
+    fn find_pareto_paths(
+        &self,
+        ctx: &Context,
+        params: ParetoPathParams,
+        tx: ObserverImpl<ParetoPath>,
+    );
+    fn load_graph(
+        &self,
+        ctx: &Context,
+        params: LoadGraphParams,
+        tx: ObserverImpl<GraphVersion>,
+    );
+    fn edit_graph(
+        &self,
+        ctx: &Context,
+        params: EditGraphParams,
+        tx: ObserverImpl<GraphEditResult>,
+    );
+    fn find_stored_path(
+        &self,
+        ctx: &Context,
+        params: StoredPathParams,
+        tx: ObserverImpl<StoredPathResult>,
+    );
+    fn import_graph(
+        &self,
+        ctx: &Context,
+        params: ImportGraphParams,
+        tx: ObserverImpl<ImportedGraph>,
+    );
+    fn export_graph(
+        &self,
+        ctx: &Context,
+        params: ExportGraphParams,
+        tx: ObserverImpl<ExportedGraph>,
+    );
+    fn generate_graph(
+        &self,
+        ctx: &Context,
+        params: GenerateGraphParams,
+        tx: ObserverImpl<GraphMetricsParams>,
+    );
+    fn prepare_graph(
+        &self,
+        ctx: &Context,
+        params: PrepareGraphParams,
+        tx: ObserverImpl<HierarchyInfo>,
+    );
+    fn find_prepared_path(
+        &self,
+        ctx: &Context,
+        params: PreparedPathParams,
+        tx: ObserverImpl<PreparedPathResult>,
+    );
 }
 
 #[allow(non_camel_case_types)]
@@ -23,6 +95,18 @@ pub trait CallHandler {
 pub enum CallGen {
     find_shortest_path(ShortestPathParams),
     compute_graph_metrics(GraphMetricsParams),
+    solve_tour(TourParams),
+    compute_centrality(CentralityParams),
+    compute_max_flow(MaxFlowParams),
+    find_pareto_paths(ParetoPathParams),
+    load_graph(LoadGraphParams),
+    edit_graph(EditGraphParams),
+    find_stored_path(StoredPathParams),
+    import_graph(ImportGraphParams),
+    export_graph(ExportGraphParams),
+    generate_graph(GenerateGraphParams),
+    prepare_graph(PrepareGraphParams),
+    find_prepared_path(PreparedPathParams),
 }
 
 #[allow(non_camel_case_types)]
@@ -30,6 +114,18 @@ pub enum CallGen {
 pub enum ResponseNextGen {
     find_shortest_path(PathResult),
     compute_graph_metrics(GraphMetrics),
+    solve_tour(TourResult),
+    compute_centrality(CentralityResult),
+    compute_max_flow(MaxFlowResult),
+    find_pareto_paths(ParetoPath),
+    load_graph(GraphVersion),
+    edit_graph(GraphEditResult),
+    find_stored_path(StoredPathResult),
+    import_graph(ImportedGraph),
+    export_graph(ExportedGraph),
+    generate_graph(GraphMetricsParams),
+    prepare_graph(HierarchyInfo),
+    find_prepared_path(PreparedPathResult),
 }
 
 pub(crate) fn gen_call(
@@ -50,6 +146,66 @@ pub(crate) fn gen_call(
             params,
             ObserverImpl::new(id, sender),
         ),
+        CallGen::solve_tour(params) => handler.solve_tour(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::compute_centrality(params) => handler.compute_centrality(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::compute_max_flow(params) => handler.compute_max_flow(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::find_pareto_paths(params) => handler.find_pareto_paths(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::load_graph(params) => handler.load_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::edit_graph(params) => handler.edit_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::find_stored_path(params) => handler.find_stored_path(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::import_graph(params) => handler.import_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::export_graph(params) => handler.export_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::generate_graph(params) => handler.generate_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::prepare_graph(params) => handler.prepare_graph(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
+        CallGen::find_prepared_path(params) => handler.find_prepared_path(
+            ctx,
+            params,
+            ObserverImpl::new(id, sender),
+        ),
     }
 }
 
@@ -65,3 +221,75 @@ impl super::ToResponseNextGen for GraphMetrics {
         ResponseNextGen::compute_graph_metrics(self)
     }
 }
+
+impl super::ToResponseNextGen for TourResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::solve_tour(self)
+    }
+}
+
+impl super::ToResponseNextGen for CentralityResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::compute_centrality(self)
+    }
+}
+
+impl super::ToResponseNextGen for MaxFlowResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::compute_max_flow(self)
+    }
+}
+
+impl super::ToResponseNextGen for ParetoPath {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::find_pareto_paths(self)
+    }
+}
+
+impl super::ToResponseNextGen for GraphVersion {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::load_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for GraphEditResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::edit_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for StoredPathResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::find_stored_path(self)
+    }
+}
+
+impl super::ToResponseNextGen for ImportedGraph {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::import_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for ExportedGraph {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::export_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for GraphMetricsParams {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::generate_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for HierarchyInfo {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::prepare_graph(self)
+    }
+}
+
+impl super::ToResponseNextGen for PreparedPathResult {
+    fn to_response_next_gen(self) -> ResponseNextGen {
+        ResponseNextGen::find_prepared_path(self)
+    }
+}
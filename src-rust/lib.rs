@@ -1,7 +1,10 @@
+use once_cell::sync::Lazy;
 use pathfinder_core::PathfinderHandler;
+use shared_types::codec::Encoding;
 use shared_types::receiver::Receiver;
-use shared_types::router::{Request, WireResponse, WireResponseSender};
+use shared_types::router::{CallLimits, Request, WireResponse, WireResponseSender};
 use shared_types::storage::InMemoryStorage;
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 
 /// WASM-specific transport that calls JavaScript callback
@@ -18,6 +21,32 @@ impl WireResponseSender for WasmTransport {
     }
 }
 
+/// Binary counterpart of `WasmTransport`: responses reach the callback as
+/// MessagePack-encoded `Uint8Array`s
+struct WasmBinaryTransport {
+    callback: js_sys::Function,
+}
+
+impl WireResponseSender for WasmBinaryTransport {
+    fn send_response(&self, wire_response: WireResponse) {
+        let this = JsValue::NULL;
+        if let Ok(bytes) = Encoding::MessagePack.encode_response(&wire_response) {
+            let _ = self.callback.call1(&this, &js_sys::Uint8Array::from(bytes.as_slice()));
+        }
+    }
+}
+
+/// Receiver shared by every request, so session storage (cached paths and
+/// stored graphs) persists for the lifetime of the module. Calls run on the
+/// user's own machine, so the server's call limits do not apply
+static RECEIVER: Lazy<Receiver<PathfinderHandler<InMemoryStorage>, InMemoryStorage>> =
+    Lazy::new(|| {
+        let storage = InMemoryStorage::new();
+        let handler = PathfinderHandler::new(Some(Arc::new(storage.clone())));
+        Receiver::new("wasm-session".to_string(), handler, Some(storage))
+            .with_limits(CallLimits::unlimited())
+    });
+
 /// Main entry point for router-based requests
 #[wasm_bindgen]
 pub fn send_request(request_js: JsValue, response_callback: js_sys::Function) -> Result<(), JsValue> {
@@ -28,14 +57,42 @@ pub fn send_request(request_js: JsValue, response_callback: js_sys::Function) ->
         callback: response_callback,
     });
 
-    // Create handler with optional in-memory storage
-    let handler = PathfinderHandler::new(Some(std::sync::Arc::new(InMemoryStorage::new())));
+    // Handle the request
+    RECEIVER.handle_request(request, transport);
+
+    Ok(())
+}
 
-    // Create receiver for this session
-    let receiver = Receiver::new("wasm-session".to_string(), handler, Some(InMemoryStorage::new()));
+/// Entry point for MessagePack-encoded requests, answered in the same encoding
+#[wasm_bindgen]
+pub fn send_request_bytes(request: &[u8], response_callback: js_sys::Function) -> Result<(), JsValue> {
+    let request = Encoding::MessagePack
+        .decode_request(request)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse request: {}", e)))?;
 
-    // Handle the request
-    receiver.handle_request(request, transport);
+    let transport = Box::new(WasmBinaryTransport {
+        callback: response_callback,
+    });
+    RECEIVER.handle_request(request, transport);
 
     Ok(())
 }
+
+/// Encodes a request as MessagePack, for transports that speak `pathfinder.msgpack`
+#[wasm_bindgen]
+pub fn encode_request(request_js: JsValue) -> Result<Vec<u8>, JsValue> {
+    let request: Request = serde_wasm_bindgen::from_value(request_js)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse request: {}", e)))?;
+    Encoding::MessagePack
+        .encode_request(&request.0)
+        .map_err(|e| JsValue::from_str(&e))
+}
+
+/// Decodes a MessagePack-encoded response into the same shape `send_request` passes its callback
+#[wasm_bindgen]
+pub fn decode_response(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let response = Encoding::MessagePack
+        .decode_response(bytes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse response: {}", e)))?;
+    serde_wasm_bindgen::to_value(&response).map_err(|e| JsValue::from_str(&e.to_string()))
+}
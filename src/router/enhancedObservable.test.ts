@@ -203,6 +203,69 @@ describe("enhanceObservable", () => {
     });
   });
 
+  describe("onProgress", () => {
+    it("reports progress frames before the first emission", async () => {
+      const obs: Observable<number> = {
+        subscribe: (observer) => {
+          observer.progress?.({ fraction: 0.5, stage: "distances" });
+          observer.next?.(1);
+          observer.progress?.({ fraction: 1, stage: "distances" });
+          observer.complete?.("done");
+        },
+      };
+
+      const enhanced = enhanceObservable(obs);
+      const onProgress = vi.fn();
+      const result = await enhanced.first({ onProgress });
+
+      expect(result).toBe(1);
+      expect(onProgress).toHaveBeenCalledTimes(1);
+      expect(onProgress).toHaveBeenCalledWith({
+        fraction: 0.5,
+        stage: "distances",
+      });
+    });
+
+    it("reports every progress frame while collecting all()", async () => {
+      const obs: Observable<number> = {
+        subscribe: (observer) => {
+          observer.progress?.({ fraction: 0.25, stage: "a", completed: 1, total: 4 });
+          observer.next?.(1);
+          observer.progress?.({ fraction: 1, stage: "a", completed: 4, total: 4 });
+          observer.complete?.("done");
+        },
+      };
+
+      const enhanced = enhanceObservable(obs);
+      const onProgress = vi.fn();
+      const result = await enhanced.all({ onProgress });
+
+      expect(result).toEqual([1]);
+      expect(onProgress).toHaveBeenCalledTimes(2);
+    });
+
+    it("stops reporting progress after each() is aborted", () => {
+      let emitProgress: () => void = () => {};
+
+      const obs: Observable<number> = {
+        subscribe: (observer) => {
+          emitProgress = () =>
+            observer.progress?.({ fraction: 0.1, stage: "work" });
+        },
+      };
+
+      const enhanced = enhanceObservable(obs);
+      const onProgress = vi.fn();
+      const controller = enhanced.each(() => {}, { onProgress });
+
+      emitProgress();
+      controller.abort();
+      emitProgress();
+
+      expect(onProgress).toHaveBeenCalledTimes(1);
+    });
+  });
+
   describe("subscribe() still works", () => {
     it("can still use traditional subscribe pattern", () => {
       const obs: Observable<number> = {
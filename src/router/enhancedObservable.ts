@@ -1,4 +1,14 @@
-import type { Observable, EnhancedObservable } from "./types";
+import { RateLimitedError } from "./types";
+import type {
+  Observable,
+  EnhancedObservable,
+  EnhancedObservableOptions,
+  RateLimited,
+} from "./types";
+
+function callError(error: string, rateLimited?: RateLimited): Error {
+  return rateLimited ? new RateLimitedError(rateLimited) : new Error(error);
+}
 
 export function enhanceObservable<T>(
   observable: Observable<T>,
@@ -6,7 +16,7 @@ export function enhanceObservable<T>(
   return {
     subscribe: observable.subscribe.bind(observable),
 
-    first(options?: { signal?: AbortSignal }): Promise<T> {
+    first(options?: EnhancedObservableOptions): Promise<T> {
       return new Promise((resolve, reject) => {
         let resolved = false;
         const cleanup = () => {
@@ -22,15 +32,20 @@ export function enhanceObservable<T>(
         options?.signal?.addEventListener("abort", onAbort);
 
         observable.subscribe({
+          progress: (progress) => {
+            if (!resolved) {
+              options?.onProgress?.(progress);
+            }
+          },
           next: (value) => {
             if (!resolved) {
               cleanup();
               resolve(value);
             }
           },
-          error: (error) => {
+          error: (error, rateLimited) => {
             cleanup();
-            reject(new Error(error));
+            reject(callError(error, rateLimited));
           },
           complete: () => {
             if (!resolved) {
@@ -42,7 +57,7 @@ export function enhanceObservable<T>(
       });
     },
 
-    all(options?: { signal?: AbortSignal }): Promise<T[]> {
+    all(options?: EnhancedObservableOptions): Promise<T[]> {
       return new Promise((resolve, reject) => {
         const values: T[] = [];
         let completed = false;
@@ -60,14 +75,19 @@ export function enhanceObservable<T>(
         options?.signal?.addEventListener("abort", onAbort);
 
         observable.subscribe({
+          progress: (progress) => {
+            if (!completed) {
+              options?.onProgress?.(progress);
+            }
+          },
           next: (value) => {
             if (!completed) {
               values.push(value);
             }
           },
-          error: (error) => {
+          error: (error, rateLimited) => {
             cleanup();
-            reject(new Error(error));
+            reject(callError(error, rateLimited));
           },
           complete: () => {
             cleanup();
@@ -79,7 +99,7 @@ export function enhanceObservable<T>(
 
     each(
       fn: (value: T) => void,
-      options?: { signal?: AbortSignal },
+      options?: EnhancedObservableOptions,
     ): AbortController {
       const controller = new AbortController();
       let stopped = false;
@@ -99,6 +119,11 @@ export function enhanceObservable<T>(
       controller.signal.addEventListener("abort", cleanup);
 
       observable.subscribe({
+        progress: (progress) => {
+          if (!stopped) {
+            options?.onProgress?.(progress);
+          }
+        },
         next: (value) => {
           if (!stopped) {
             fn(value);
@@ -1,10 +1,14 @@
 export { createRouter } from "./router";
-export { createWasmAdaptor } from "./wasmAdaptor";
+export { createWasmAdaptor, wasmMsgpackCodec } from "./wasmAdaptor";
 export { createWebSocketAdaptor } from "./websocketAdaptor";
+export { MSGPACK_SUBPROTOCOL, parseRateLimited, RateLimitedError } from "./types";
 export type {
   Adaptor,
+  BinaryCodec,
+  RateLimited,
   Router,
   Observable,
+  Progress,
   WireResponse,
   RequestEnum,
   ResponseEnum,
@@ -0,0 +1,76 @@
+import { describe, it, expect } from "vitest";
+import { createRouter } from "./router";
+import { RateLimitedError } from "./types";
+import type { Adaptor, RateLimited, WireResponse } from "./types";
+
+const refusal: RateLimited = {
+  scope: "connection",
+  retry_after_ms: 1500,
+  message: "Rate limited (connection): retry after 1500 ms",
+};
+
+/** Adaptor that answers every call with `responses`, the way the WebSocket adaptor forwards frames */
+function replying(...responses: WireResponse[]): Adaptor {
+  return {
+    init: async () => {},
+    sendRequest: () => ({
+      subscribe: (observer) => {
+        for (const response of responses) {
+          observer.next?.(response);
+        }
+      },
+    }),
+  };
+}
+
+describe("refused calls", () => {
+  it("reject first() with the parsed refusal", async () => {
+    const router = createRouter({ adaptor: replying([1, { RateLimited: refusal }]) });
+
+    const error = await router.find_shortest_path({} as any).first().catch((e) => e);
+
+    expect(error).toBeInstanceOf(RateLimitedError);
+    expect(error.message).toBe(refusal.message);
+    expect(error.rateLimited).toEqual(refusal);
+  });
+
+  it("reject all() after earlier progress", async () => {
+    const router = createRouter({
+      adaptor: replying(
+        [1, { Progress: { fraction: 0.5, stage: "distances" } }],
+        [1, { RateLimited: refusal }],
+      ),
+    });
+
+    await expect(router.compute_centrality({} as any).all()).rejects.toThrow(
+      RateLimitedError,
+    );
+  });
+
+  it("pass the refusal to subscribers", async () => {
+    const router = createRouter({ adaptor: replying([1, { RateLimited: refusal }]) });
+
+    const refused = await new Promise<RateLimited | undefined>((resolve) => {
+      router.find_shortest_path({} as any).subscribe({
+        error: (_error, rateLimited) => resolve(rateLimited),
+      });
+    });
+
+    expect(refused?.scope).toBe("connection");
+    expect(refused?.retry_after_ms).toBe(1500);
+  });
+});
+
+describe("terminal frames forwarded through next", () => {
+  it("settle calls like the adaptor's own error and complete", async () => {
+    const failing = createRouter({ adaptor: replying([1, { Error: "No path found" }]) });
+    await expect(failing.find_shortest_path({} as any).first()).rejects.toThrow(
+      "No path found",
+    );
+
+    const finishing = createRouter({
+      adaptor: replying([1, { Complete: "Found 0 Pareto-optimal paths" }]),
+    });
+    await expect(finishing.find_pareto_paths({} as any).all()).resolves.toEqual([]);
+  });
+});
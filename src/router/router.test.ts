@@ -80,6 +80,53 @@ describe("Router with WASM Adaptor", () => {
     router.dispose();
   });
 
+  test("find shortest path - msgpack encoding", async () => {
+    const router = createRouter({
+      adaptor: createWasmAdaptor({ wasmInit, encoding: "msgpack" }),
+    });
+
+    const params: ShortestPathParams = {
+      points: [
+        { x: 0.0, y: 0.0 },
+        { x: 3.0, y: 0.0 },
+        { x: 0.0, y: 4.0 },
+      ],
+      edges: [
+        { from: 0, to: 1 },
+        { from: 1, to: 2 },
+        { from: 0, to: 2 },
+      ],
+      start_idx: 0,
+      end_idx: 2,
+    };
+
+    const results: PathResult[] = [];
+    let errorMsg: string | undefined;
+    let completeMsg: string | undefined;
+
+    await new Promise<void>((resolve) => {
+      router.find_shortest_path(params).subscribe({
+        next: (result) => results.push(result),
+        error: (error) => {
+          errorMsg = error;
+          resolve();
+        },
+        complete: (notes) => {
+          completeMsg = notes;
+          resolve();
+        },
+      });
+    });
+
+    expect(errorMsg).toBeUndefined();
+    expect(completeMsg).toBe("Path found successfully");
+    expect(results).toHaveLength(1);
+    expect(results[0].path).toEqual([0, 2]);
+    expect(results[0].distance).toBeCloseTo(4.0, 5);
+
+    router.dispose();
+  });
+
   test("find shortest path - no path exists", async () => {
     const router = createRouter({
       adaptor: createWasmAdaptor({ wasmInit }),
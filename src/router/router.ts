@@ -1,3 +1,4 @@
+import { parseRateLimited } from "./types";
 import type { Adaptor, Router, Observable } from "./types";
 import { enhanceObservable } from "./enhancedObservable";
 
@@ -42,10 +43,22 @@ export function createRouter(options: CreateRouterOptions): Router {
                 };
 
                 adaptor.sendRequest(request).subscribe({
+                  // Adaptors may forward terminal frames here too (the WebSocket one does)
                   next: (response) => {
                     const [, responseEnum] = response;
+                    const rateLimited = parseRateLimited(responseEnum);
                     if ("N" in responseEnum && methodName in responseEnum.N) {
                       observer.next?.((responseEnum.N as any)[methodName]);
+                    } else if ("Progress" in responseEnum) {
+                      observer.progress?.(responseEnum.Progress);
+                    } else if ("Error" in responseEnum) {
+                      observer.error?.(responseEnum.Error);
+                    } else if ("Aborted" in responseEnum) {
+                      observer.error?.(responseEnum.Aborted);
+                    } else if ("Complete" in responseEnum) {
+                      observer.complete?.(responseEnum.Complete);
+                    } else if (rateLimited) {
+                      observer.error?.(rateLimited.message, rateLimited);
                     }
                   },
                   ...(observer.error && { error: observer.error }),
@@ -3,6 +3,9 @@ import type {
   ResponseNextGen,
   Observable,
   EnhancedObservable,
+  EnhancedObservableOptions,
+  Progress,
+  RateLimited,
   Router,
 } from "../../dist-types/router.gen";
 
@@ -16,12 +19,36 @@ export type ResponseEnum =
   | { Aborted: string }
   | { Error: string }
   | { Complete: string }
-  | { N: ResponseNextGen };
+  | { N: ResponseNextGen }
+  | { Progress: Progress }
+  | { RateLimited: RateLimited };
 
 export type RequestEnum =
   | { Abort: [number, string] }
   | { Call: [number, CallGen] };
 
+/** Subprotocol naming the MessagePack wire encoding */
+export const MSGPACK_SUBPROTOCOL = "pathfinder.msgpack";
+
+/** The rate-limit refusal a response carries, if it is one */
+export function parseRateLimited(response: ResponseEnum): RateLimited | undefined {
+  return "RateLimited" in response ? response.RateLimited : undefined;
+}
+
+/** What `first()` and `all()` reject with when the server refused the call under its rate limits */
+export class RateLimitedError extends Error {
+  constructor(readonly rateLimited: RateLimited) {
+    super(rateLimited.message);
+    this.name = "RateLimitedError";
+  }
+}
+
+/** Encodes requests and decodes responses for binary (MessagePack) transports */
+export interface BinaryCodec {
+  encode(request: RequestEnum): Uint8Array;
+  decode(bytes: Uint8Array): WireResponse;
+}
+
 /** Transport adaptor interface - implementations handle sending/receiving */
 export interface Adaptor {
   /** Initialize the adaptor (e.g., connect to WebSocket, init WASM) */
@@ -35,4 +62,13 @@ export interface Adaptor {
 }
 
 // Re-export generated types for convenience
-export type { CallGen, ResponseNextGen, Observable, EnhancedObservable, Router };
+export type {
+  CallGen,
+  ResponseNextGen,
+  Observable,
+  EnhancedObservable,
+  EnhancedObservableOptions,
+  Progress,
+  RateLimited,
+  Router,
+};
@@ -1,12 +1,21 @@
-import type { Adaptor, RequestEnum, WireResponse } from "./types";
+import { parseRateLimited } from "./types";
+import type { Adaptor, BinaryCodec, RequestEnum, WireResponse } from "./types";
 import * as wasm from "../../pkg/wasm_pathfinder";
 import init from "../../pkg/wasm_pathfinder";
 
 export interface WasmAdaptorOptions {
   /** Optional custom WASM initialization */
   wasmInit?: typeof init;
+  /** Pass requests and responses as MessagePack `Uint8Array`s (default: "json") */
+  encoding?: "json" | "msgpack";
 }
 
+/** MessagePack codec backed by the WASM module; initialize the module before use */
+export const wasmMsgpackCodec: BinaryCodec = {
+  encode: (request) => wasm.encode_request(request),
+  decode: (bytes) => wasm.decode_response(bytes) as WireResponse,
+};
+
 export function createWasmAdaptor(
   options: WasmAdaptorOptions = {},
 ): Adaptor {
@@ -29,11 +38,11 @@ export function createWasmAdaptor(
 
       return {
         subscribe: (observer) => {
-          // Send the request to WASM with a callback
-          wasm.send_request(request, (response: WireResponse) => {
+          const dispatch = (response: WireResponse) => {
             const [id, responseEnum] = response;
-            
-            if ("N" in responseEnum) {
+            const rateLimited = parseRateLimited(responseEnum);
+
+            if ("N" in responseEnum || "Progress" in responseEnum) {
               observer.next?.(response);
             } else if ("Error" in responseEnum) {
               observer.error?.(responseEnum.Error);
@@ -41,8 +50,20 @@ export function createWasmAdaptor(
               observer.complete?.(responseEnum.Complete);
             } else if ("Aborted" in responseEnum) {
               observer.error?.(responseEnum.Aborted);
+            } else if (rateLimited) {
+              observer.error?.(rateLimited.message, rateLimited);
             }
-          });
+          };
+
+          // Send the request to WASM with a callback
+          if (options.encoding === "msgpack") {
+            wasm.send_request_bytes(
+              wasmMsgpackCodec.encode(request),
+              (bytes: Uint8Array) => dispatch(wasmMsgpackCodec.decode(bytes)),
+            );
+          } else {
+            wasm.send_request(request, dispatch);
+          }
         },
       };
     },
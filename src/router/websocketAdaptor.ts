@@ -1,4 +1,5 @@
-import type { Adaptor, RequestEnum, WireResponse } from "./types";
+import { MSGPACK_SUBPROTOCOL } from "./types";
+import type { Adaptor, BinaryCodec, RequestEnum, WireResponse } from "./types";
 
 export interface WebSocketAdaptorOptions {
   url: string;
@@ -8,6 +9,12 @@ export interface WebSocketAdaptorOptions {
   autoReconnect?: boolean;
   /** Reconnect delay in ms (default: 1000) */
   reconnectDelay?: number;
+  /**
+   * Speak MessagePack in binary frames when the server accepts the
+   * `pathfinder.msgpack` subprotocol; falls back to JSON otherwise.
+   * `wasmMsgpackCodec` from the WASM adaptor provides one.
+   */
+  binaryCodec?: BinaryCodec;
 }
 
 export function createWebSocketAdaptor(
@@ -24,7 +31,11 @@ export function createWebSocketAdaptor(
   function connect(): Promise<void> {
     return new Promise((resolve, reject) => {
       try {
-        ws = new WebSocket(options.url, options.protocols);
+        const protocols = options.binaryCodec
+          ? [MSGPACK_SUBPROTOCOL, ...[options.protocols ?? []].flat()]
+          : options.protocols;
+        ws = new WebSocket(options.url, protocols);
+        ws.binaryType = "arraybuffer";
 
         ws.onopen = () => {
           console.log("WebSocket connected");
@@ -33,7 +44,10 @@ export function createWebSocketAdaptor(
 
         ws.onmessage = (event) => {
           try {
-            const response = JSON.parse(event.data) as WireResponse;
+            const response =
+              typeof event.data === "string"
+                ? (JSON.parse(event.data) as WireResponse)
+                : options.binaryCodec!.decode(new Uint8Array(event.data));
             const [id] = response;
             const observer = observers.get(id);
 
@@ -88,16 +102,24 @@ export function createWebSocketAdaptor(
               
               // Clean up observer on terminal responses
               const [id, responseEnum] = response;
-              if ("Error" in responseEnum || "Complete" in responseEnum || "Aborted" in responseEnum) {
+              if (
+                "Error" in responseEnum ||
+                "Complete" in responseEnum ||
+                "Aborted" in responseEnum ||
+                "RateLimited" in responseEnum
+              ) {
                 observers.delete(id);
               }
             },
           };
           observers.set(requestId, internalObserver);
 
-          // Send request over WebSocket
-          const json = JSON.stringify(request);
-          ws.send(json);
+          // Send request over WebSocket, in binary if the server agreed to it
+          if (options.binaryCodec && ws.protocol === MSGPACK_SUBPROTOCOL) {
+            ws.send(options.binaryCodec.encode(request));
+          } else {
+            ws.send(JSON.stringify(request));
+          }
         },
       };
     },